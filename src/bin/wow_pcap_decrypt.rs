@@ -0,0 +1,56 @@
+//! Prints every message of a world connection in a pcap or pcapng capture.
+//!
+//! Usage: `wow_pcap_decrypt <capture> <server port> <vanilla|tbc|wrath> <session key as hex>`
+//!
+//! The session key is the 40 byte session key in the same byte order as used by the crate.
+
+use std::process::exit;
+use wow_srp::pcap::{decrypt_capture, session_key_from_hex, Direction, Expansion};
+
+const USAGE: &str =
+    "Usage: wow_pcap_decrypt <capture> <server port> <vanilla|tbc|wrath> <session key as hex>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 4 {
+        fail(USAGE);
+    }
+
+    let capture = std::fs::read(&args[0])
+        .unwrap_or_else(|e| fail(&format!("Unable to read '{}': {}", args[0], e)));
+    let server_port: u16 = args[1]
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("Invalid port '{}'", args[1])));
+    let expansion = match args[2].to_ascii_lowercase().as_str() {
+        "vanilla" => Expansion::Vanilla,
+        "tbc" => Expansion::Tbc,
+        "wrath" => Expansion::Wrath,
+        _ => fail(&format!("Invalid expansion '{}'", args[2])),
+    };
+    let session_key = session_key_from_hex(&args[3])
+        .unwrap_or_else(|| fail(&format!("Invalid session key '{}'", args[3])));
+
+    let packets = decrypt_capture(&capture, server_port, expansion, session_key)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+
+    for packet in packets {
+        let direction = match packet.direction {
+            Direction::ClientToServer => "CMSG",
+            Direction::ServerToClient => "SMSG",
+        };
+
+        println!(
+            "{}.{:06} {} {:#06x} {}",
+            packet.timestamp.as_secs(),
+            packet.timestamp.subsec_micros(),
+            direction,
+            packet.opcode,
+            packet.body_hex()
+        );
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(1);
+}
@@ -35,21 +35,21 @@
 //! The client has some limitations that enable a simpler overall design:
 //!
 //! * Fixed [32 byte](`LARGE_SAFE_PRIME_LENGTH`) large safe prime length.
-//! Despite the field in the packet being variable the client is unable
-//! to use values greater than 32 bytes.
+//!   Despite the field in the packet being variable the client is unable
+//!   to use values greater than 32 bytes.
 //! * Only accepting valid [`NormalizedString`] values.
-//! This is done to unify the server and client implementations.
+//!   This is done to unify the server and client implementations.
 //! * [GENERATOR](crate::GENERATOR) can be only be a u8 despite the
-//! [`CMD_AUTH_LOGON_CHALLENGE_Server`](https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Server)
-//! packet having a variable sized generator field.
-//! This is done because there are no generator values larger than 255.
+//!   [`CMD_AUTH_LOGON_CHALLENGE_Server`](https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Server)
+//!   packet having a variable sized generator field.
+//!   This is done because there are no generator values larger than 255.
 //!
 
-use crate::error::MatchProofsError;
+use crate::error::{MatchProofsError, RngHealthError};
 use crate::key::{
     PrivateKey, Proof, PublicKey, ReconnectData, Salt, SessionKey, PRIVATE_KEY_LENGTH,
     PROOF_LENGTH, PUBLIC_KEY_LENGTH, RECONNECT_CHALLENGE_DATA_LENGTH, SALT_LENGTH,
-    SESSION_KEY_LENGTH,
+    SESSION_KEY_LENGTH, SHA1_HASH_LENGTH,
 };
 use crate::normalized_string::NormalizedString;
 use crate::primes::{Generator, LargeSafePrime, LARGE_SAFE_PRIME_LENGTH};
@@ -57,7 +57,12 @@ use crate::srp_internal::{
     calculate_interleaved, calculate_reconnect_proof, calculate_server_proof, calculate_u,
 };
 use crate::srp_internal_client::{calculate_client_S, calculate_client_proof_with_custom_value};
-use crate::{srp_internal, srp_internal_client};
+#[cfg(feature = "tbc-header")]
+use crate::tbc_header;
+use crate::trace::{enter_span, proof_mismatch, timed_math};
+#[cfg(feature = "wrath-header")]
+use crate::wrath_header;
+use crate::{srp_internal, srp_internal_client, vanilla_header};
 
 /// Contains the challenge data and proof for reconnection.
 ///
@@ -129,6 +134,74 @@ impl SrpClient {
             proof: *client_proof.as_le_bytes(),
         }
     }
+
+    /// Starts the Vanilla world server authentication with the username and session key of
+    /// this session.
+    ///
+    /// `server_seed` is from [`SMSG_AUTH_CHALLENGE`].
+    /// Returns the client seed and client proof for [`CMSG_AUTH_SESSION`] together with the
+    /// header crypto, see [`ProofSeed::into_client_header_crypto`](vanilla_header::ProofSeed::into_client_header_crypto).
+    ///
+    /// [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
+    /// [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+    #[must_use]
+    pub fn into_vanilla_client_crypto(
+        self,
+        server_seed: u32,
+    ) -> (
+        u32,
+        [u8; PROOF_LENGTH as usize],
+        vanilla_header::HeaderCrypto,
+    ) {
+        let seed = vanilla_header::ProofSeed::new();
+        let client_seed = seed.seed();
+
+        let (client_proof, crypto) = seed.into_client_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            server_seed,
+        );
+
+        (client_seed, client_proof, crypto)
+    }
+
+    /// Same as [`SrpClient::into_vanilla_client_crypto`] but for TBC.
+    #[cfg(feature = "tbc-header")]
+    #[must_use]
+    pub fn into_tbc_client_crypto(
+        self,
+        server_seed: u32,
+    ) -> (u32, [u8; PROOF_LENGTH as usize], tbc_header::HeaderCrypto) {
+        let seed = tbc_header::ProofSeed::new();
+        let client_seed = seed.seed();
+
+        let (client_proof, crypto) = seed.into_client_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            server_seed,
+        );
+
+        (client_seed, client_proof, crypto)
+    }
+
+    /// Same as [`SrpClient::into_vanilla_client_crypto`] but for Wrath.
+    #[cfg(feature = "wrath-header")]
+    #[must_use]
+    pub fn into_wrath_client_crypto(
+        self,
+        server_seed: u32,
+    ) -> (u32, [u8; PROOF_LENGTH as usize], wrath_header::ClientCrypto) {
+        let seed = wrath_header::ProofSeed::new();
+        let client_seed = seed.seed();
+
+        let (client_proof, crypto) = seed.into_client_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            server_seed,
+        );
+
+        (client_seed, client_proof, crypto)
+    }
 }
 
 /// Second step of the client connection. First is [`SrpClientUser`]. Next is [`SrpClient`].
@@ -179,6 +252,8 @@ impl SrpClientChallenge {
         self,
         server_proof: [u8; PROOF_LENGTH as usize],
     ) -> Result<SrpClient, MatchProofsError> {
+        enter_span!("verify_server_proof", username = %self.username);
+
         let client_server_proof = calculate_server_proof(
             &self.client_public_key,
             &self.client_proof,
@@ -187,6 +262,7 @@ impl SrpClientChallenge {
 
         let server_proof = Proof::from_le_bytes(server_proof);
         if server_proof != client_server_proof {
+            proof_mismatch!(*server_proof.as_le_bytes());
             return Err(MatchProofsError {
                 client_proof: *client_server_proof.as_le_bytes(),
                 server_proof: *server_proof.as_le_bytes(),
@@ -200,15 +276,53 @@ impl SrpClientChallenge {
     }
 }
 
+/// Hash of the username and password, `SHA1(UPPER(username) ":" UPPER(password))`.
+///
+/// This is all the client needs from the password in order to authenticate, so it can be
+/// stored for saved logins instead of the plaintext password.
+/// It should still be treated as a secret since it is enough to log in as the user,
+/// but it does not reveal the password itself.
+///
+/// The array is in the byte order of the SHA-1 output, **not** little endian.
+/// It is the same value as the `sha_pass_hash` column of some server databases.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct CredentialsHash {
+    hash: [u8; SHA1_HASH_LENGTH as usize],
+}
+
+impl CredentialsHash {
+    /// Calculates the hash from the username and password.
+    #[must_use]
+    pub fn new(username: &NormalizedString, password: &NormalizedString) -> Self {
+        Self {
+            hash: srp_internal::calculate_credentials_hash(username, password),
+        }
+    }
+
+    /// Creates the hash from a previously stored value, see [`CredentialsHash::as_bytes`].
+    #[must_use]
+    pub const fn from_bytes(hash: [u8; SHA1_HASH_LENGTH as usize]) -> Self {
+        Self { hash }
+    }
+
+    /// Returns the hash for storage.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; SHA1_HASH_LENGTH as usize] {
+        &self.hash
+    }
+}
+
 /// Starting point of the client. Next step is [`SrpClientChallenge`].
 ///
 /// Uses [`NormalizedString`]s for the reasons described there.
 ///
+/// Only the [`CredentialsHash`] of the password is kept.
+///
 /// All arrays are **little endian**.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct SrpClientUser {
     username: NormalizedString,
-    password: NormalizedString,
+    credentials_hash: CredentialsHash,
     client_private_key: PrivateKey,
 }
 
@@ -218,25 +332,83 @@ impl SrpClientUser {
     /// [`NormalizedString`] is used for the reasons described there.
     #[must_use]
     pub fn new(username: NormalizedString, password: NormalizedString) -> Self {
+        let credentials_hash = CredentialsHash::new(&username, &password);
+
+        Self::from_credentials_hash(username, credentials_hash)
+    }
+
+    /// Same as [`SrpClientUser::new`] but fails if the client private key does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the private key fails them.
+    pub fn try_new(
+        username: NormalizedString,
+        password: NormalizedString,
+    ) -> Result<Self, RngHealthError> {
+        let credentials_hash = CredentialsHash::new(&username, &password);
+
+        Self::try_from_credentials_hash(username, credentials_hash)
+    }
+
+    /// Creates a new [`SrpClientUser`] from username and a previously stored [`CredentialsHash`].
+    ///
+    /// The hash must have been created with the same `username`, otherwise the server
+    /// will reject the proof.
+    #[must_use]
+    pub fn from_credentials_hash(
+        username: NormalizedString,
+        credentials_hash: CredentialsHash,
+    ) -> Self {
         let client_private_key = PrivateKey::randomized();
 
-        Self::with_specific_private_key(username, password, *client_private_key.as_le_bytes())
+        Self::with_specific_private_key(
+            username,
+            credentials_hash,
+            *client_private_key.as_le_bytes(),
+        )
+    }
+
+    /// Same as [`SrpClientUser::from_credentials_hash`] but fails if the client private key does
+    /// not pass the [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the private key fails them.
+    pub fn try_from_credentials_hash(
+        username: NormalizedString,
+        credentials_hash: CredentialsHash,
+    ) -> Result<Self, RngHealthError> {
+        let client_private_key = PrivateKey::try_randomized()?;
+
+        Ok(Self::with_specific_private_key(
+            username,
+            credentials_hash,
+            *client_private_key.as_le_bytes(),
+        ))
     }
 
     pub(crate) const fn with_specific_private_key(
         username: NormalizedString,
-        password: NormalizedString,
+        credentials_hash: CredentialsHash,
         client_private_key: [u8; PRIVATE_KEY_LENGTH as usize],
     ) -> Self {
         let client_private_key = PrivateKey::from_le_bytes(client_private_key);
 
         Self {
             username,
-            password,
+            credentials_hash,
             client_private_key,
         }
     }
 
+    /// The [`CredentialsHash`] of the username and password, for storing saved logins.
+    #[must_use]
+    pub const fn credentials_hash(&self) -> &CredentialsHash {
+        &self.credentials_hash
+    }
+
     /// Takes the server supplied variables and computes the next step.
     ///
     /// The generator and large safe prime are **not** checked for validity.
@@ -264,29 +436,40 @@ impl SrpClientUser {
         server_public_key: PublicKey,
         salt: [u8; SALT_LENGTH as usize],
     ) -> SrpClientChallenge {
+        enter_span!("into_challenge", username = %self.username);
+
         let generator = Generator::from(generator);
         let large_safe_prime = LargeSafePrime::from_le_bytes(large_safe_prime);
 
         // Creating an invalid public key is extremely rare and is more likely bad crypto
-        let client_public_key = srp_internal_client::calculate_client_public_key(
-            &self.client_private_key,
-            &generator,
-            &large_safe_prime,
+        let client_public_key = timed_math!(
+            "client_public_key",
+            srp_internal_client::calculate_client_public_key(
+                &self.client_private_key,
+                &generator,
+                &large_safe_prime,
+            )
         )
         .expect("Invalid public key generated for client. This is extremely unlikely.");
 
         let salt = Salt::from_le_bytes(salt);
-        let x = srp_internal::calculate_x(&self.username, &self.password, &salt);
+        let x = srp_internal::calculate_x_from_credentials_hash(
+            self.credentials_hash.as_bytes(),
+            &salt,
+        );
 
         let u = &calculate_u(&client_public_key, &server_public_key);
         #[allow(non_snake_case)] // No better descriptor
-        let S = calculate_client_S(
-            &server_public_key,
-            &x,
-            &self.client_private_key,
-            u,
-            &generator,
-            &large_safe_prime,
+        let S = timed_math!(
+            "client_S",
+            calculate_client_S(
+                &server_public_key,
+                &x,
+                &self.client_private_key,
+                u,
+                &generator,
+                &large_safe_prime,
+            )
         );
         let session_key = calculate_interleaved(&S);
 
@@ -0,0 +1,77 @@
+//! Sources of the current time for the types that expire state.
+//!
+//! [`SystemClock`] uses [`Instant::now`] and is the default.
+//! [`ManualClock`] only moves when [`ManualClock::advance`] is called,
+//! which makes expiry deterministic in tests.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use wow_srp::clock::{Clock, ManualClock};
+//!
+//! let clock = ManualClock::new();
+//! let start = clock.now();
+//!
+//! // Clones share the same time
+//! clock.clone().advance(Duration::from_secs(10));
+//! assert_eq!(clock.now() - start, Duration::from_secs(10));
+//! ```
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Source of the current time.
+pub trait Clock: Send + Sync {
+    /// Current time.
+    ///
+    /// Must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// Clock that uses [`Instant::now`].
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves forward when told to.
+///
+/// Clones share the same time, so a clone can be kept in a test while the original
+/// is given away.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a new clock starting at the current time.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
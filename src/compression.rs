@@ -0,0 +1,288 @@
+//! Compression of world message bodies.
+//!
+//! Some server messages, like [`SMSG_COMPRESSED_UPDATE_OBJECT`] and [`SMSG_COMPRESSED_MOVES`],
+//! have a body that consists of the size of the decompressed body as a little endian `u32`
+//! followed by the zlib compressed body.
+//! The size field of the header is the size of the compressed body, so the header is written
+//! after compressing.
+//!
+//! [`Compression`] decides which opcodes are compressed and how large a decompressed
+//! body is allowed to be, which protects against small messages that decompress into
+//! very large bodies.
+//!
+//! The `ClientFramer` and `ClientDecoder` of the header modules decompress bodies
+//! when created with `with_compression`, and the `ServerEncoder` compresses them.
+//! When not using the codecs, `write_compressed_server_packet` and `put_compressed_server_packet`
+//! on the server encrypters compress the body and calculate the header size from the
+//! compressed body.
+//!
+//! The opcode is not changed, so a decompressed [`SMSG_COMPRESSED_UPDATE_OBJECT`]
+//! is still returned with opcode `0x1F6` but with the body of an `SMSG_UPDATE_OBJECT`.
+//!
+//! `compression` must be enabled for this to be enabled.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::compression::{Compression, SMSG_COMPRESSED_UPDATE_OBJECT};
+//!
+//! let compression = Compression::new(0x10000);
+//!
+//! let body = vec![0_u8; 1000];
+//! let compressed = compression.compress(&body).unwrap();
+//! assert!(compressed.len() < body.len());
+//!
+//! assert!(compression.is_compressed(SMSG_COMPRESSED_UPDATE_OBJECT));
+//! assert_eq!(compression.decompress(&compressed).unwrap(), body);
+//! ```
+//!
+//! [`SMSG_COMPRESSED_UPDATE_OBJECT`]: https://wowdev.wiki/SMSG_COMPRESSED_UPDATE_OBJECT
+//! [`SMSG_COMPRESSED_MOVES`]: https://wowdev.wiki/SMSG_COMPRESSED_MOVES
+
+use crate::error::{CompressionError, PacketSizeError};
+use flate2::{Compress, Decompress, FlushCompress, FlushDecompress, Status};
+use std::convert::{TryFrom, TryInto};
+
+/// Opcode of `SMSG_COMPRESSED_UPDATE_OBJECT` for all expansions.
+pub const SMSG_COMPRESSED_UPDATE_OBJECT: u16 = 0x1F6;
+
+/// Opcode of `SMSG_COMPRESSED_MOVES` for all expansions.
+pub const SMSG_COMPRESSED_MOVES: u16 = 0x2FB;
+
+/// Length of the decompressed size at the start of a compressed body.
+pub const DECOMPRESSED_SIZE_LENGTH: u8 = std::mem::size_of::<u32>() as u8;
+
+/// Which messages are compressed and how large they may be when decompressed.
+///
+/// Created through [`Compression::new`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Compression {
+    maximum_decompressed_size: u32,
+    opcodes: Vec<u16>,
+}
+
+impl Compression {
+    /// Creates a new compression that rejects bodies larger than `maximum_decompressed_size`
+    /// bytes after decompression.
+    ///
+    /// [`SMSG_COMPRESSED_UPDATE_OBJECT`] and [`SMSG_COMPRESSED_MOVES`] are compressed.
+    #[must_use]
+    pub fn new(maximum_decompressed_size: u32) -> Self {
+        Self {
+            maximum_decompressed_size,
+            opcodes: vec![SMSG_COMPRESSED_UPDATE_OBJECT, SMSG_COMPRESSED_MOVES],
+        }
+    }
+
+    /// Replaces the opcodes that are compressed.
+    #[must_use]
+    pub fn with_opcodes(mut self, opcodes: &[u16]) -> Self {
+        self.opcodes = opcodes.to_vec();
+        self
+    }
+
+    /// Maximum allowed size of a decompressed body.
+    #[must_use]
+    pub const fn maximum_decompressed_size(&self) -> u32 {
+        self.maximum_decompressed_size
+    }
+
+    /// Opcodes that are compressed.
+    #[must_use]
+    pub fn opcodes(&self) -> &[u16] {
+        &self.opcodes
+    }
+
+    /// Whether messages with `opcode` have compressed bodies.
+    #[must_use]
+    pub fn is_compressed(&self, opcode: u16) -> bool {
+        self.opcodes.contains(&opcode)
+    }
+
+    /// Compresses `body` and prefixes it with the decompressed size.
+    ///
+    /// # Errors
+    ///
+    /// If `body` is larger than the maximum decompressed size,
+    /// since the other side would not be able to decompress it.
+    pub fn compress(&self, body: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let size = self
+            .checked_size(body.len())
+            .ok_or(CompressionError::BodyTooLarge {
+                size: body.len(),
+                maximum: self.maximum_decompressed_size,
+            })?;
+
+        Ok(deflate(size, body))
+    }
+
+    /// Compresses the parts of `body` if `opcode` is compressed.
+    ///
+    /// Used by the `write_compressed_server_packet` functions of the header modules.
+    pub(crate) fn compress_packet_body(
+        &self,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<Option<Vec<u8>>, PacketSizeError> {
+        if !self.is_compressed(opcode) {
+            return Ok(None);
+        }
+
+        let length = body.iter().map(|part| part.len()).sum();
+        let size = self
+            .checked_size(length)
+            .ok_or(PacketSizeError::BodyTooLarge {
+                size: length,
+                maximum: self.maximum_decompressed_size as usize,
+            })?;
+
+        Ok(Some(match body {
+            [body] => deflate(size, body),
+            body => deflate(size, &body.concat()),
+        }))
+    }
+
+    /// Size of a body of `length` if it can be decompressed by the other side.
+    fn checked_size(&self, length: usize) -> Option<u32> {
+        u32::try_from(length)
+            .ok()
+            .filter(|size| *size <= self.maximum_decompressed_size)
+    }
+
+    /// Decompresses a body prefixed with the decompressed size.
+    ///
+    /// # Errors
+    ///
+    /// If `body` is too short to contain the size, if the size is larger than the
+    /// maximum decompressed size, or if the body does not decompress to exactly the size.
+    pub fn decompress(&self, body: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let (size, compressed) = match body.get(..usize::from(DECOMPRESSED_SIZE_LENGTH)) {
+            Some(size) => (
+                u32::from_le_bytes(size.try_into().unwrap_or_default()),
+                &body[usize::from(DECOMPRESSED_SIZE_LENGTH)..],
+            ),
+            None => return Err(CompressionError::MissingSize),
+        };
+
+        if size > self.maximum_decompressed_size {
+            return Err(CompressionError::DecompressedTooLarge {
+                size,
+                maximum: self.maximum_decompressed_size,
+            });
+        }
+
+        // The extra byte detects bodies that decompress to more than the size
+        let mut decompressed = vec![0_u8; size as usize + 1];
+        let mut decompress = Decompress::new(true);
+        let status = decompress
+            .decompress(compressed, &mut decompressed, FlushDecompress::Finish)
+            .map_err(|_| CompressionError::InvalidData)?;
+
+        if status != Status::StreamEnd || decompress.total_out() != u64::from(size) {
+            return Err(CompressionError::InvalidData);
+        }
+
+        decompressed.truncate(size as usize);
+        Ok(decompressed)
+    }
+}
+
+/// Compresses `body` of `size` bytes and prefixes it with the size.
+fn deflate(size: u32, body: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::with_capacity(usize::from(DECOMPRESSED_SIZE_LENGTH) + 64);
+    compressed.extend_from_slice(&size.to_le_bytes());
+
+    let mut compress = Compress::new(flate2::Compression::default(), true);
+    loop {
+        compressed.reserve(body.len() / 2 + 64);
+
+        let remaining = &body[compress.total_in() as usize..];
+        match compress.compress_vec(remaining, &mut compressed, FlushCompress::Finish) {
+            Ok(Status::Ok) | Ok(Status::BufError) => {}
+            // Errors are only possible with invalid flush values
+            Ok(Status::StreamEnd) | Err(_) => break,
+        }
+    }
+
+    compressed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compression::{Compression, SMSG_COMPRESSED_MOVES};
+    use crate::error::CompressionError;
+
+    #[test]
+    fn verify_round_trip() {
+        let compression = Compression::new(0x10000);
+
+        for body in [
+            Vec::new(),
+            vec![1, 2, 3],
+            (0..0x10000_u32).map(|i| i as u8).collect(),
+        ] {
+            let compressed = compression.compress(&body).unwrap();
+            assert_eq!(compressed[..4], (body.len() as u32).to_le_bytes());
+            assert_eq!(compression.decompress(&compressed).unwrap(), body);
+        }
+
+        // Generated with Python's zlib.compress
+        let compressed = [
+            3, 0, 0, 0, 0x78, 0x9C, 0x63, 0x64, 0x62, 0x06, 0x00, 0x00, 0x0D, 0x00, 0x07,
+        ];
+        assert_eq!(compression.decompress(&compressed).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn verify_errors() {
+        let compression = Compression::new(100);
+
+        assert_eq!(
+            compression.decompress(&[0, 0, 0]),
+            Err(CompressionError::MissingSize)
+        );
+
+        assert_eq!(
+            compression.compress(&[0; 101]),
+            Err(CompressionError::BodyTooLarge {
+                size: 101,
+                maximum: 100
+            })
+        );
+
+        let mut compressed = Compression::new(1000).compress(&[0; 101]).unwrap();
+        assert_eq!(
+            compression.decompress(&compressed),
+            Err(CompressionError::DecompressedTooLarge {
+                size: 101,
+                maximum: 100
+            })
+        );
+
+        // Decompresses to more than the size
+        compressed[0] = 100;
+        assert_eq!(
+            compression.decompress(&compressed),
+            Err(CompressionError::InvalidData)
+        );
+
+        // Decompresses to less than the size
+        let mut compressed = compression.compress(&[0; 10]).unwrap();
+        compressed[0] = 11;
+        assert_eq!(
+            compression.decompress(&compressed),
+            Err(CompressionError::InvalidData)
+        );
+
+        let mut compressed = compression.compress(&[0; 10]).unwrap();
+        compressed.truncate(compressed.len() - 2);
+        assert_eq!(
+            compression.decompress(&compressed),
+            Err(CompressionError::InvalidData)
+        );
+
+        let compression = compression.with_opcodes(&[0x1234]);
+        assert!(compression.is_compressed(0x1234));
+        assert!(!compression.is_compressed(SMSG_COMPRESSED_MOVES));
+    }
+}
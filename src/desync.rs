@@ -0,0 +1,266 @@
+//! Diagnostics for when the header ciphers of the client and server drift apart.
+//!
+//! If one side encrypts a header twice or skips encrypting a header, every header after that
+//! point will decrypt to garbage.
+//! This usually shows up as an unknown opcode or a nonsensical size followed by a disconnect.
+//!
+//! The decrypter halves in [`vanilla_header`](crate::vanilla_header), `tbc_header`, and
+//! `wrath_header` have `_checked` variants of the header decryption functions that take
+//! [`HeaderLimits`] and return a [`DesyncError`] for headers outside of the limits.
+//! The error contains the amount of bytes the decrypter appears to be out of step with
+//! the encrypter on the other side, found by decrypting the header at nearby keystream positions.
+//!
+//! Every encrypter and decrypter half also has a `fingerprint` function that returns
+//! a [`CipherFingerprint`] of the current position of the cipher.
+//! The fingerprint of an encrypter is the same as the fingerprint of the decrypter on the other
+//! side after both have processed the same amount of bytes,
+//! so the fingerprints in client and server logs can be compared to find out where the ciphers diverged.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::desync::HeaderLimits;
+//! use wow_srp::vanilla_header::DecrypterHalf;
+//! use wow_srp::vanilla_header::CLIENT_HEADER_LENGTH;
+//!
+//! fn read_header(decrypter: &mut DecrypterHalf, data: [u8; CLIENT_HEADER_LENGTH as usize]) {
+//!     // Opcodes above 0x500 do not exist in 1.12
+//!     let limits = HeaderLimits::new(0x8000, 0, 0x500).with_search_distance(8);
+//!
+//!     match decrypter.decrypt_client_header_checked(data, &limits) {
+//!         Ok(_header) => { /* Continue as normal */ }
+//!         Err(e) => {
+//!             eprintln!("{} at {}", e, decrypter.fingerprint());
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::error::{DesyncError, ImplausibleHeader};
+use crate::snapshot::CryptoSnapshot;
+use sha1::{Digest, Sha1};
+use std::fmt::{Display, Formatter};
+
+/// Limits for what is considered a plausible header.
+///
+/// Created through [`HeaderLimits::new`].
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct HeaderLimits {
+    maximum_body_size: u32,
+    minimum_opcode: u32,
+    maximum_opcode: u32,
+    search_distance: u8,
+}
+
+impl HeaderLimits {
+    /// Creates limits where bodies larger than `maximum_body_size` and opcodes
+    /// outside of `minimum_opcode..=maximum_opcode` are implausible.
+    ///
+    /// Headers with sizes smaller than the opcode are always implausible.
+    ///
+    /// No search for the keystream offset will be done unless
+    /// [`HeaderLimits::with_search_distance`] is used.
+    #[must_use]
+    pub const fn new(maximum_body_size: u32, minimum_opcode: u32, maximum_opcode: u32) -> Self {
+        Self {
+            maximum_body_size,
+            minimum_opcode,
+            maximum_opcode,
+            search_distance: 0,
+        }
+    }
+
+    /// Searches up to `distance` bytes in each direction for a keystream position
+    /// that decrypts an implausible header to a plausible one.
+    ///
+    /// Vanilla and TBC use the session key as a repeating keystream, so offsets
+    /// are only unique up to half the key length.
+    /// They also use the previous encrypted byte when decrypting, which means that the most
+    /// significant byte of the size is ignored during the search.
+    /// Wrath uses RC4, which can only be searched forward.
+    #[must_use]
+    pub const fn with_search_distance(mut self, distance: u8) -> Self {
+        self.search_distance = distance;
+        self
+    }
+
+    /// Maximum allowed size of the body, not including the opcode.
+    #[must_use]
+    pub const fn maximum_body_size(&self) -> u32 {
+        self.maximum_body_size
+    }
+
+    /// Smallest allowed opcode.
+    #[must_use]
+    pub const fn minimum_opcode(&self) -> u32 {
+        self.minimum_opcode
+    }
+
+    /// Largest allowed opcode.
+    #[must_use]
+    pub const fn maximum_opcode(&self) -> u32 {
+        self.maximum_opcode
+    }
+
+    /// Amount of bytes searched in each direction.
+    #[must_use]
+    pub const fn search_distance(&self) -> u8 {
+        self.search_distance
+    }
+
+    /// `size` includes the opcode, like the size field of the header.
+    pub(crate) const fn check(
+        &self,
+        size: u32,
+        opcode: u32,
+        opcode_length: u32,
+    ) -> Result<(), ImplausibleHeader> {
+        if size < opcode_length {
+            return Err(ImplausibleHeader::SizeSmallerThanOpcode);
+        }
+
+        if size - opcode_length > self.maximum_body_size {
+            return Err(ImplausibleHeader::BodyTooLarge {
+                maximum: self.maximum_body_size,
+            });
+        }
+
+        if opcode < self.minimum_opcode || opcode > self.maximum_opcode {
+            return Err(ImplausibleHeader::OpcodeOutOfRange {
+                minimum: self.minimum_opcode,
+                maximum: self.maximum_opcode,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks a decrypted header and searches for the offset if it is implausible.
+    ///
+    /// `plausible_at` decrypts the same header at a keystream position offset by the
+    /// argument and returns whether it is plausible.
+    pub(crate) fn check_or_search(
+        &self,
+        size: u32,
+        opcode: u32,
+        opcode_length: u32,
+        forward_only: bool,
+        mut plausible_at: impl FnMut(i32) -> bool,
+    ) -> Result<(), DesyncError> {
+        let reason = match self.check(size, opcode, opcode_length) {
+            Ok(()) => return Ok(()),
+            Err(reason) => reason,
+        };
+
+        let mut offset = None;
+        for distance in 1..=i32::from(self.search_distance) {
+            if plausible_at(distance) {
+                offset = Some(distance);
+                break;
+            }
+
+            if !forward_only && plausible_at(-distance) {
+                offset = Some(-distance);
+                break;
+            }
+        }
+
+        Err(DesyncError {
+            size,
+            opcode,
+            reason,
+            offset,
+        })
+    }
+}
+
+/// Stable identifier of the position of a cipher.
+///
+/// An encrypter and the decrypter on the other side of the connection have the same fingerprint
+/// as long as they are in sync.
+/// The fingerprint does not reveal the session key.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct CipherFingerprint {
+    value: u64,
+}
+
+impl CipherFingerprint {
+    /// Numeric value of the fingerprint.
+    #[must_use]
+    pub const fn as_u64(&self) -> u64 {
+        self.value
+    }
+
+    /// The kind of the snapshot is not included since the encrypter and decrypter
+    /// kinds are different for the same cipher position.
+    pub(crate) fn new(snapshot: &CryptoSnapshot) -> Self {
+        let hash = Sha1::new().chain_update(snapshot.state()).finalize();
+        let mut value = [0_u8; 8];
+        value.copy_from_slice(&hash[..8]);
+
+        Self {
+            value: u64::from_be_bytes(value),
+        }
+    }
+}
+
+impl Display for CipherFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::desync::HeaderLimits;
+    use crate::error::ImplausibleHeader;
+
+    #[test]
+    fn verify_check() {
+        let limits = HeaderLimits::new(100, 0x10, 0x20);
+
+        assert!(limits.check(104, 0x10, 4).is_ok());
+        assert!(limits.check(2, 0x20, 2).is_ok());
+
+        assert_eq!(
+            limits.check(3, 0x10, 4),
+            Err(ImplausibleHeader::SizeSmallerThanOpcode)
+        );
+        assert_eq!(
+            limits.check(105, 0x10, 4),
+            Err(ImplausibleHeader::BodyTooLarge { maximum: 100 })
+        );
+        assert_eq!(
+            limits.check(4, 0x21, 4),
+            Err(ImplausibleHeader::OpcodeOutOfRange {
+                minimum: 0x10,
+                maximum: 0x20
+            })
+        );
+    }
+
+    #[test]
+    fn verify_search() {
+        let limits = HeaderLimits::new(100, 0x10, 0x20);
+        let error = limits
+            .check_or_search(0, 0, 4, false, |_| true)
+            .unwrap_err();
+        assert_eq!(error.offset, None);
+
+        let limits = limits.with_search_distance(5);
+        let error = limits
+            .check_or_search(0, 0, 4, false, |offset| offset == -3)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(-3));
+
+        let error = limits
+            .check_or_search(0, 0, 4, true, |offset| offset == -3)
+            .unwrap_err();
+        assert_eq!(error.offset, None);
+
+        let error = limits
+            .check_or_search(0, 0, 4, true, |offset| offset >= 2)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(2));
+    }
+}
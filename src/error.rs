@@ -9,9 +9,50 @@
 //!
 //! [`MatchProofsError`] is returned when server and client proofs do not match.
 //! Often because of a wrong password.
+//!
+//! [`FrameError`] is returned from the framers in the header modules when a header is invalid.
+//!
+//! [`PacketSizeError`] is returned when writing a packet that does not fit in the header or buffer.
+//!
+//! [`SnapshotError`] is returned when a [`CryptoSnapshot`](crate::snapshot::CryptoSnapshot) can not be restored.
+//!
+//! [`ProxyError`] is returned from the [`proxy`](crate::proxy) module when a header can not be re-encrypted.
+//!
+//! `CompressionError` is returned when a body can not be compressed or decompressed.
+//! `compression` must be enabled for this to be enabled.
+//!
+//! [`DesyncError`] is returned from the checked header decryption functions when a header is implausible.
+//!
+//! `PcapError` is returned from the `pcap` module when a capture can not be decrypted.
+//! `pcap` must be enabled for this to be enabled.
+//!
+//! `AccountStoreError` is returned from `JsonLinesAccountStore` when the file can not be read or written.
+//! `json-account-store` must be enabled for this to be enabled.
+//!
+//! [`FormatError`] is returned from the [`formats`](crate::formats) module when a database value
+//! can not be imported.
+//!
+//! [`WorldAuthError`] is returned from [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard) when a
+//! world server authentication is rejected.
+//!
+//! [`SessionTicketError`] is returned when a [`SessionTicket`](crate::session_ticket::SessionTicket)
+//! can not be decoded.
+//!
+//! [`PasswordChangeError`] is returned from
+//! [`SrpServer::change_password`](crate::server::SrpServer::change_password) and
+//! [`SrpServer::rehash_on_login`](crate::server::SrpServer::rehash_on_login).
+//!
+//! `SealedVerifierError` is returned from `SealedVerifier` when a verifier can not be unsealed.
+//! `sealed-verifier` must be enabled for this to be enabled.
+//!
+//! [`RngHealthError`] is returned from [`rng_health`](crate::rng_health) when the random number
+//! generator has failed a health check, and from the `try_` versions of functions that
+//! create random values.
 
 use crate::error::NormalizedStringError::StringTooLong;
-use crate::key::PROOF_LENGTH;
+use crate::key::{RngSource, PROOF_LENGTH};
+#[cfg(feature = "pcap")]
+use crate::pcap::Direction;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result};
 
@@ -25,6 +66,8 @@ pub enum SrpError {
     InvalidPublicKey(InvalidPublicKeyError),
     /// The string either contains an invalid character or is too long.
     NormalizedStringError(NormalizedStringError),
+    /// A random value failed the [RNG health checks](crate::enable_rng_health_checks).
+    RngHealth(RngHealthError),
 }
 
 impl Error for SrpError {}
@@ -41,6 +84,9 @@ impl Display for SrpError {
             SrpError::NormalizedStringError(error) => {
                 write!(f, "{}", error)
             }
+            SrpError::RngHealth(error) => {
+                write!(f, "{}", error)
+            }
         }
     }
 }
@@ -63,6 +109,12 @@ impl From<NormalizedStringError> for SrpError {
     }
 }
 
+impl From<RngHealthError> for SrpError {
+    fn from(r: RngHealthError) -> Self {
+        Self::RngHealth(r)
+    }
+}
+
 /// [`DecrypterHalf`](crate::vanilla_header::DecrypterHalf) and
 /// [`EncrypterHalf`](crate::vanilla_header::EncrypterHalf) do not
 /// originate from the same [`HeaderCrypto`](crate::vanilla_header::HeaderCrypto).
@@ -157,3 +209,684 @@ impl Display for NormalizedStringError {
         }
     }
 }
+
+/// Error for the framers in [`vanilla_header`](crate::vanilla_header),
+/// `tbc_header`, and `wrath_header`.
+///
+/// The header has already been decrypted when this is returned, so the connection
+/// should be dropped.
+#[derive(Debug)]
+pub enum FrameError {
+    /// The size field is smaller than the size of the opcode.
+    SizeSmallerThanOpcode {
+        /// Size field of the header, including the opcode.
+        size: u32,
+    },
+    /// The body is larger than the maximum allowed body size.
+    BodyTooLarge {
+        /// Size of the body, not including the opcode.
+        size: u32,
+        /// Maximum allowed size of the body.
+        maximum: u32,
+    },
+    /// The body of a compressed message could not be decompressed.
+    #[cfg(feature = "compression")]
+    InvalidCompressedBody {
+        /// Opcode of the message.
+        opcode: u16,
+        /// Why the body could not be decompressed.
+        error: CompressionError,
+    },
+}
+
+impl Error for FrameError {}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            FrameError::SizeSmallerThanOpcode { size } => {
+                write!(f, "Header size '{}' is smaller than the opcode.", size)
+            }
+            FrameError::BodyTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Body size '{}' is larger than the maximum of '{}'.",
+                    size, maximum
+                )
+            }
+            #[cfg(feature = "compression")]
+            FrameError::InvalidCompressedBody { opcode, error } => {
+                write!(
+                    f,
+                    "Invalid compressed body for opcode '{:#06x}': {}",
+                    opcode, error
+                )
+            }
+        }
+    }
+}
+
+impl From<FrameError> for std::io::Error {
+    fn from(e: FrameError) -> Self {
+        Self::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Error for compressing and decompressing bodies in the [`compression`](crate::compression) module.
+#[cfg(feature = "compression")]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum CompressionError {
+    /// The body is too short to contain the decompressed size.
+    MissingSize,
+    /// The decompressed size is larger than the maximum allowed size.
+    DecompressedTooLarge {
+        /// Decompressed size from the start of the body.
+        size: u32,
+        /// Maximum allowed decompressed size.
+        maximum: u32,
+    },
+    /// The body is not valid zlib data or does not decompress to the decompressed size.
+    InvalidData,
+    /// The body to compress is larger than the maximum allowed decompressed size.
+    BodyTooLarge {
+        /// Size of the body.
+        size: usize,
+        /// Maximum allowed decompressed size.
+        maximum: u32,
+    },
+}
+
+#[cfg(feature = "compression")]
+impl Error for CompressionError {}
+
+#[cfg(feature = "compression")]
+impl Display for CompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CompressionError::MissingSize => {
+                write!(f, "Compressed body does not contain the decompressed size.")
+            }
+            CompressionError::DecompressedTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Decompressed size '{}' is larger than the maximum of '{}'.",
+                    size, maximum
+                )
+            }
+            CompressionError::InvalidData => {
+                write!(
+                    f,
+                    "Compressed body is not valid zlib data of the decompressed size."
+                )
+            }
+            CompressionError::BodyTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Body size '{}' is larger than the maximum decompressed size of '{}'.",
+                    size, maximum
+                )
+            }
+        }
+    }
+}
+
+/// Error for writing complete packets with the encrypter halves in
+/// [`vanilla_header`](crate::vanilla_header), `tbc_header`, and `wrath_header`.
+///
+/// The encryption state has not been advanced when this is returned.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum PacketSizeError {
+    /// The body is too large to be represented in the size field of the header.
+    BodyTooLarge {
+        /// Size of the body, not including the opcode.
+        size: usize,
+        /// Maximum size of the body for the header.
+        maximum: usize,
+    },
+    /// The buffer is too small for the header and body.
+    BufferTooSmall {
+        /// Size of the header and body.
+        required: usize,
+        /// Size of the buffer.
+        available: usize,
+    },
+}
+
+impl Error for PacketSizeError {}
+
+impl Display for PacketSizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            PacketSizeError::BodyTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Body size '{}' is larger than the maximum of '{}'.",
+                    size, maximum
+                )
+            }
+            PacketSizeError::BufferTooSmall {
+                required,
+                available,
+            } => {
+                write!(
+                    f,
+                    "Packet requires '{}' bytes but buffer only has '{}'.",
+                    required, available
+                )
+            }
+        }
+    }
+}
+
+impl From<PacketSizeError> for std::io::Error {
+    fn from(e: PacketSizeError) -> Self {
+        Self::new(std::io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// Error for parsing and restoring [`CryptoSnapshot`](crate::snapshot::CryptoSnapshot)s.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum SnapshotError {
+    /// The snapshot was created by an unsupported version of the library.
+    UnsupportedVersion {
+        /// Version of the snapshot.
+        version: u8,
+    },
+    /// The snapshot is from a different kind of crypto half.
+    InvalidKind,
+    /// The snapshot does not have the correct length for its kind.
+    InvalidLength,
+    /// The cipher state in the snapshot is not possible to reach.
+    InvalidState,
+}
+
+impl Error for SnapshotError {}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            SnapshotError::UnsupportedVersion { version } => {
+                write!(f, "Snapshot version '{}' is not supported.", version)
+            }
+            SnapshotError::InvalidKind => {
+                write!(f, "Snapshot is from a different kind of crypto half.")
+            }
+            SnapshotError::InvalidLength => {
+                write!(f, "Snapshot has an invalid length.")
+            }
+            SnapshotError::InvalidState => {
+                write!(f, "Snapshot contains an invalid cipher state.")
+            }
+        }
+    }
+}
+
+/// Error for the [`proxy`](crate::proxy) module.
+///
+/// The header has already been decrypted when this is returned, so the connections
+/// should be dropped.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ProxyError {
+    /// The size field is smaller than the size of the opcode.
+    SizeSmallerThanOpcode {
+        /// Size field of the header, including the opcode.
+        size: u32,
+    },
+    /// The size field is too large for the header of the receiving session.
+    SizeTooLarge {
+        /// Size field of the header, including the opcode.
+        size: u32,
+        /// Maximum size field of the receiving session.
+        maximum: u32,
+    },
+}
+
+impl Error for ProxyError {}
+
+impl Display for ProxyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ProxyError::SizeSmallerThanOpcode { size } => {
+                write!(f, "Header size '{}' is smaller than the opcode.", size)
+            }
+            ProxyError::SizeTooLarge { size, maximum } => {
+                write!(
+                    f,
+                    "Header size '{}' is larger than the maximum of '{}'.",
+                    size, maximum
+                )
+            }
+        }
+    }
+}
+
+/// Reason a header is considered implausible by [`HeaderLimits`](crate::desync::HeaderLimits).
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ImplausibleHeader {
+    /// The size field is smaller than the size of the opcode.
+    SizeSmallerThanOpcode,
+    /// The body is larger than the maximum allowed body size.
+    BodyTooLarge {
+        /// Maximum allowed size of the body.
+        maximum: u32,
+    },
+    /// The opcode is outside of the allowed range.
+    OpcodeOutOfRange {
+        /// Smallest allowed opcode.
+        minimum: u32,
+        /// Largest allowed opcode.
+        maximum: u32,
+    },
+}
+
+impl Display for ImplausibleHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ImplausibleHeader::SizeSmallerThanOpcode => {
+                write!(f, "size is smaller than the opcode")
+            }
+            ImplausibleHeader::BodyTooLarge { maximum } => {
+                write!(f, "body is larger than the maximum of '{}'", maximum)
+            }
+            ImplausibleHeader::OpcodeOutOfRange { minimum, maximum } => {
+                write!(
+                    f,
+                    "opcode is outside of '{:#06x}..={:#06x}'",
+                    minimum, maximum
+                )
+            }
+        }
+    }
+}
+
+/// Error for the checked header decryption functions described in the
+/// [`desync`](crate::desync) module.
+///
+/// The decrypter has already been advanced past the header when this is returned.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct DesyncError {
+    /// Decrypted size field, including the opcode.
+    pub size: u32,
+    /// Decrypted opcode.
+    pub opcode: u32,
+    /// Why the header is implausible.
+    pub reason: ImplausibleHeader,
+    /// Amount of bytes the decrypter is behind the encrypter on the other side.
+    ///
+    /// Negative if the decrypter is ahead.
+    /// [`None`] if no plausible header was found within the search distance.
+    pub offset: Option<i32>,
+}
+
+impl Error for DesyncError {}
+
+impl Display for DesyncError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "Implausible header with size '{}' and opcode '{:#06x}': {}.",
+            self.size, self.opcode, self.reason
+        )?;
+
+        match self.offset {
+            Some(offset) if offset > 0 => {
+                write!(f, " Decrypter appears to be {} bytes behind.", offset)
+            }
+            Some(offset) => write!(f, " Decrypter appears to be {} bytes ahead.", -offset),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error for the [`pcap`](crate::pcap) module.
+#[cfg(feature = "pcap")]
+#[derive(Debug)]
+pub enum PcapError {
+    /// The capture is neither a pcap nor a pcapng file.
+    UnknownFormat,
+    /// The capture ends in the middle of a block or record.
+    Truncated,
+    /// The link layer of the capture is not supported.
+    UnsupportedLinkType {
+        /// Link type as defined by the pcap specification.
+        link_type: u32,
+    },
+    /// No TCP segments were sent to or from the server port.
+    NoConnection {
+        /// Port the capture was searched for.
+        server_port: u16,
+    },
+    /// The first message of a direction is not the expected unencrypted message.
+    ///
+    /// This happens when the capture was started after the connection was established.
+    MissingHandshake {
+        /// Direction of the message.
+        direction: Direction,
+        /// Opcode of the first message.
+        opcode: u32,
+    },
+    /// A header did not decrypt correctly, usually because of a wrong session key.
+    InvalidHeader {
+        /// Direction of the message.
+        direction: Direction,
+        /// Error from the framer.
+        error: FrameError,
+    },
+}
+
+#[cfg(feature = "pcap")]
+impl Error for PcapError {}
+
+#[cfg(feature = "pcap")]
+impl Display for PcapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            PcapError::UnknownFormat => write!(f, "Capture is not a pcap or pcapng file."),
+            PcapError::Truncated => write!(f, "Capture is truncated or malformed."),
+            PcapError::UnsupportedLinkType { link_type } => {
+                write!(f, "Link type '{}' is not supported.", link_type)
+            }
+            PcapError::NoConnection { server_port } => {
+                write!(f, "No connection to port '{}' in capture.", server_port)
+            }
+            PcapError::MissingHandshake { direction, opcode } => {
+                write!(
+                    f,
+                    "First message of {:?} has opcode '{:#06x}' instead of the unencrypted handshake.",
+                    direction, opcode
+                )
+            }
+            PcapError::InvalidHeader { direction, error } => {
+                write!(f, "Invalid header in {:?}: {}", direction, error)
+            }
+        }
+    }
+}
+
+/// Error for [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard).
+#[derive(Debug)]
+pub enum WorldAuthError {
+    /// The connection has no outstanding seed.
+    ///
+    /// Either no seed was issued, or it has already been used or cancelled.
+    NoSeedIssued,
+    /// The seed was issued longer ago than the seed timeout.
+    SeedExpired,
+    /// The username and client seed have already been accepted within the replay window.
+    Replayed,
+    /// The client proof does not match.
+    ProofsDoNotMatch(MatchProofsError),
+}
+
+impl Error for WorldAuthError {}
+
+impl Display for WorldAuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            WorldAuthError::NoSeedIssued => {
+                write!(f, "No seed has been issued for the connection.")
+            }
+            WorldAuthError::SeedExpired => write!(f, "The seed for the connection has expired."),
+            WorldAuthError::Replayed => {
+                write!(f, "The client seed has already been used by the account.")
+            }
+            WorldAuthError::ProofsDoNotMatch(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<MatchProofsError> for WorldAuthError {
+    fn from(m: MatchProofsError) -> Self {
+        Self::ProofsDoNotMatch(m)
+    }
+}
+
+/// Error for `JsonLinesAccountStore`.
+#[cfg(feature = "json-account-store")]
+#[derive(Debug)]
+pub enum AccountStoreError {
+    /// The file could not be read or written.
+    Io(std::io::Error),
+    /// A line of the file is not a valid record.
+    InvalidRecord {
+        /// Line number, starting at 1.
+        line: usize,
+    },
+}
+
+#[cfg(feature = "json-account-store")]
+impl Error for AccountStoreError {}
+
+#[cfg(feature = "json-account-store")]
+impl Display for AccountStoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            AccountStoreError::Io(error) => write!(f, "Account store IO error: {}", error),
+            AccountStoreError::InvalidRecord { line } => {
+                write!(f, "Invalid account record on line '{}'.", line)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json-account-store")]
+impl From<std::io::Error> for AccountStoreError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Error for the [`formats`](crate::formats) module.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum FormatError {
+    /// A hex string contains a character that is not a hex digit.
+    InvalidHex,
+    /// A hex string has more significant digits than fit in the value.
+    TooLong {
+        /// Amount of digits without leading zeros.
+        length: usize,
+        /// Maximum amount of digits.
+        maximum: usize,
+    },
+    /// A binary value does not have the correct length.
+    InvalidLength {
+        /// Length of the value.
+        length: usize,
+        /// Required length.
+        expected: usize,
+    },
+    /// The password verifier is zero.
+    VerifierIsZero,
+    /// The password verifier is larger than or equal to the large safe prime.
+    VerifierTooLarge,
+}
+
+impl Error for FormatError {}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            FormatError::InvalidHex => write!(f, "Value contains invalid hex characters."),
+            FormatError::TooLong { length, maximum } => write!(
+                f,
+                "Value has '{}' hex digits, but the maximum is '{}'.",
+                length, maximum
+            ),
+            FormatError::InvalidLength { length, expected } => write!(
+                f,
+                "Value has a length of '{}', but must be '{}'.",
+                length, expected
+            ),
+            FormatError::VerifierIsZero => write!(f, "Password verifier is zero."),
+            FormatError::VerifierTooLarge => write!(
+                f,
+                "Password verifier is larger than or equal to the large safe prime."
+            ),
+        }
+    }
+}
+
+/// Error for decoding a [`SessionTicket`](crate::session_ticket::SessionTicket).
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum SessionTicketError {
+    /// A hex string contains a character that is not a hex digit, or has an odd length.
+    InvalidHex,
+    /// The value is too short, too long, or the username length does not match.
+    InvalidLength {
+        /// Length of the value in bytes.
+        length: usize,
+    },
+    /// The ticket was encoded with an unknown version.
+    UnsupportedVersion(u8),
+    /// The expansion is not known.
+    InvalidExpansion(u8),
+    /// The username is not a valid [`NormalizedString`](crate::normalized_string::NormalizedString).
+    InvalidUsername,
+    /// The HMAC does not match the ticket.
+    InvalidSignature,
+    /// The session key only contains zeros.
+    SessionKeyIsZero,
+}
+
+impl Error for SessionTicketError {}
+
+impl Display for SessionTicketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            SessionTicketError::InvalidHex => write!(f, "Ticket contains invalid hex."),
+            SessionTicketError::InvalidLength { length } => {
+                write!(f, "Ticket has an invalid length of '{}'.", length)
+            }
+            SessionTicketError::UnsupportedVersion(version) => {
+                write!(f, "Ticket version '{}' is not supported.", version)
+            }
+            SessionTicketError::InvalidExpansion(expansion) => {
+                write!(f, "Ticket has an invalid expansion '{}'.", expansion)
+            }
+            SessionTicketError::InvalidUsername => write!(f, "Ticket has an invalid username."),
+            SessionTicketError::InvalidSignature => {
+                write!(f, "Ticket signature does not match.")
+            }
+            SessionTicketError::SessionKeyIsZero => write!(f, "Session key is zero."),
+        }
+    }
+}
+
+/// Error for changing the password verifier of an
+/// [`AccountRecord`](crate::server::AccountRecord).
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum PasswordChangeError {
+    /// The record is for a different user than the session.
+    UsernameMismatch,
+    /// The `sha_pass_hash` does not match the password verifier of the record.
+    CredentialsHashMismatch,
+    /// The new salt failed the [RNG health checks](crate::enable_rng_health_checks).
+    RngHealth(RngHealthError),
+}
+
+impl Error for PasswordChangeError {}
+
+impl From<RngHealthError> for PasswordChangeError {
+    fn from(r: RngHealthError) -> Self {
+        Self::RngHealth(r)
+    }
+}
+
+impl Display for PasswordChangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            PasswordChangeError::UsernameMismatch => {
+                write!(f, "Record is for a different user than the session.")
+            }
+            PasswordChangeError::CredentialsHashMismatch => write!(
+                f,
+                "Credentials hash does not match the password verifier of the record."
+            ),
+            PasswordChangeError::RngHealth(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Error for a failed [RNG health check](crate::enable_rng_health_checks).
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum RngHealthError {
+    /// A value only contained zeros.
+    AllZeros {
+        /// Kind of value.
+        source: RngSource,
+    },
+    /// A value was identical to the previous value.
+    RepeatedOutput {
+        /// Kind of value.
+        source: RngSource,
+    },
+    /// A byte had the same value for too many values in a row.
+    StuckByte {
+        /// Kind of value.
+        source: RngSource,
+        /// Index of the byte.
+        position: usize,
+    },
+}
+
+impl Error for RngHealthError {}
+
+impl Display for RngHealthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            RngHealthError::AllZeros { source } => {
+                write!(f, "RNG health check failed for {}: all zeros.", source)
+            }
+            RngHealthError::RepeatedOutput { source } => write!(
+                f,
+                "RNG health check failed for {}: same value twice in a row.",
+                source
+            ),
+            RngHealthError::StuckByte { source, position } => write!(
+                f,
+                "RNG health check failed for {}: byte '{}' is stuck.",
+                source, position
+            ),
+        }
+    }
+}
+
+/// Error for `SealedVerifier`.
+#[cfg(feature = "sealed-verifier")]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum SealedVerifierError {
+    /// None of the keys have the id the verifier was sealed with.
+    UnknownKeyId(u32),
+    /// The key, username, or data is wrong.
+    DecryptionFailed,
+    /// The encoding does not have the correct length.
+    InvalidLength {
+        /// Length of the value.
+        length: usize,
+    },
+    /// The verifier was encoded with an unknown version.
+    UnsupportedVersion(u8),
+}
+
+#[cfg(feature = "sealed-verifier")]
+impl Error for SealedVerifierError {}
+
+#[cfg(feature = "sealed-verifier")]
+impl Display for SealedVerifierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            SealedVerifierError::UnknownKeyId(id) => {
+                write!(f, "No sealing key with id '{}'.", id)
+            }
+            SealedVerifierError::DecryptionFailed => {
+                write!(f, "Sealed verifier could not be decrypted.")
+            }
+            SealedVerifierError::InvalidLength { length } => {
+                write!(f, "Sealed verifier has an invalid length of '{}'.", length)
+            }
+            SealedVerifierError::UnsupportedVersion(version) => {
+                write!(f, "Sealed verifier version '{}' is not supported.", version)
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+/// Expansion of a client, which decides the world header crypto.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Expansion {
+    /// Uses [`vanilla_header`](crate::vanilla_header).
+    Vanilla,
+    /// Uses [`tbc_header`](crate::tbc_header).
+    Tbc,
+    /// Uses [`wrath_header`](crate::wrath_header).
+    Wrath,
+}
+
+impl Expansion {
+    /// Value used by the client for the expansion, `0` for Vanilla.
+    #[must_use]
+    pub const fn as_int(&self) -> u8 {
+        match self {
+            Expansion::Vanilla => 0,
+            Expansion::Tbc => 1,
+            Expansion::Wrath => 2,
+        }
+    }
+
+    /// Inverse of [`Expansion::as_int`].
+    #[must_use]
+    pub const fn from_int(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Expansion::Vanilla,
+            1 => Expansion::Tbc,
+            2 => Expansion::Wrath,
+            _ => return None,
+        })
+    }
+}
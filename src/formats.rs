@@ -0,0 +1,295 @@
+//! Conversion of password verifiers and salts to and from the database formats of other servers.
+//!
+//! The crate uses **little endian** arrays for all values, as returned by
+//! [`SrpVerifier::password_verifier`] and [`SrpVerifier::salt`].
+//! Other server projects use different layouts:
+//!
+//! * [`MangosColumns`] for `MaNGOS` and `CMaNGOS`, which store `v` and `s` as **big endian**
+//!   uppercase hex strings without leading zeros.
+//! * [`TrinityColumns`] for `TrinityCore` and `AzerothCore`, which store `verifier` and `salt`
+//!   as **little endian** `BINARY(32)` columns.
+//!
+//! Imported verifiers are checked to be neither zero nor larger than or equal to
+//! [the large safe prime](crate::LARGE_SAFE_PRIME_BIG_ENDIAN), since those can not be correct.
+//! Salts are not checked.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::formats::MangosColumns;
+//! use wow_srp::normalized_string::NormalizedString;
+//!
+//! // From the `account` table
+//! let v = "6A060B71673731";
+//! let s = "789CD089496C155B1C160DFF63744766";
+//!
+//! let columns = MangosColumns::new(v, s);
+//! let verifier = columns.to_verifier(NormalizedString::new("A").unwrap()).unwrap();
+//!
+//! assert_eq!(MangosColumns::from_verifier(&verifier), columns);
+//! ```
+
+use crate::error::FormatError;
+use crate::hex::{hex_encode_upper, try_hex_decode_big_endian, HexError};
+use crate::normalized_string::NormalizedString;
+use crate::server::SrpVerifier;
+use crate::{LARGE_SAFE_PRIME_BIG_ENDIAN, PASSWORD_VERIFIER_LENGTH, SALT_LENGTH};
+use std::convert::TryInto;
+
+/// The `v` and `s` columns of the `MaNGOS` and `CMaNGOS` `account` table.
+///
+/// Both are **big endian** hex strings.
+/// `MaNGOS` writes them in uppercase without leading zeros, but any case and
+/// any amount of leading zeros are accepted when converting to an [`SrpVerifier`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct MangosColumns {
+    /// Password verifier.
+    pub v: String,
+    /// Salt.
+    pub s: String,
+}
+
+impl MangosColumns {
+    /// Creates the columns from the database values.
+    #[must_use]
+    pub fn new(v: impl Into<String>, s: impl Into<String>) -> Self {
+        Self {
+            v: v.into(),
+            s: s.into(),
+        }
+    }
+
+    /// Creates the columns in the format written by `MaNGOS`.
+    #[must_use]
+    pub fn from_verifier(verifier: &SrpVerifier) -> Self {
+        Self {
+            v: to_mangos_hex(verifier.password_verifier()),
+            s: to_mangos_hex(verifier.salt()),
+        }
+    }
+
+    /// Creates an [`SrpVerifier`] from the columns.
+    ///
+    /// # Errors
+    ///
+    /// If either column is not valid hex or too long, or if the verifier is invalid.
+    pub fn to_verifier(&self, username: NormalizedString) -> Result<SrpVerifier, FormatError> {
+        let password_verifier = from_mangos_hex(&self.v)?;
+        let salt = from_mangos_hex(&self.s)?;
+        validate_verifier(&password_verifier)?;
+
+        Ok(SrpVerifier::from_database_values(
+            username,
+            password_verifier,
+            salt,
+        ))
+    }
+}
+
+/// The `salt` and `verifier` columns of the `TrinityCore` and `AzerothCore` `account` table.
+///
+/// Both are **little endian**, which is the same layout as used by the crate.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct TrinityColumns {
+    /// Salt.
+    pub salt: [u8; SALT_LENGTH as usize],
+    /// Password verifier.
+    pub verifier: [u8; PASSWORD_VERIFIER_LENGTH as usize],
+}
+
+impl TrinityColumns {
+    /// Creates the columns from the raw database values.
+    ///
+    /// # Errors
+    ///
+    /// If either column is not exactly 32 bytes.
+    pub fn from_slices(salt: &[u8], verifier: &[u8]) -> Result<Self, FormatError> {
+        let invalid_length = |length| FormatError::InvalidLength {
+            length,
+            expected: SALT_LENGTH as usize,
+        };
+
+        Ok(Self {
+            salt: salt.try_into().map_err(|_| invalid_length(salt.len()))?,
+            verifier: verifier
+                .try_into()
+                .map_err(|_| invalid_length(verifier.len()))?,
+        })
+    }
+
+    /// Creates the columns from `verifier`.
+    #[must_use]
+    pub const fn from_verifier(verifier: &SrpVerifier) -> Self {
+        Self {
+            salt: *verifier.salt(),
+            verifier: *verifier.password_verifier(),
+        }
+    }
+
+    /// Creates an [`SrpVerifier`] from the columns.
+    ///
+    /// # Errors
+    ///
+    /// If the verifier is invalid.
+    pub fn to_verifier(&self, username: NormalizedString) -> Result<SrpVerifier, FormatError> {
+        validate_verifier(&self.verifier)?;
+
+        Ok(SrpVerifier::from_database_values(
+            username,
+            self.verifier,
+            self.salt,
+        ))
+    }
+}
+
+/// Uppercase big endian hex without leading zero bytes, like `BN_bn2hex`.
+fn to_mangos_hex(value: &[u8; 32]) -> String {
+    let significant = value.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    if significant == 0 {
+        return "0".to_string();
+    }
+
+    let mut value = value[..significant].to_vec();
+    value.reverse();
+
+    hex_encode_upper(&value)
+}
+
+/// Parses big endian hex of any length up to 64 significant digits into little endian.
+fn from_mangos_hex(s: &str) -> Result<[u8; 32], FormatError> {
+    let mut value = [0_u8; 32];
+    try_hex_decode_big_endian(s.trim(), &mut value).map_err(|e| match e {
+        HexError::InvalidHex => FormatError::InvalidHex,
+        HexError::TooLong { digits } => FormatError::TooLong {
+            length: digits,
+            maximum: 64,
+        },
+    })?;
+
+    Ok(value)
+}
+
+fn validate_verifier(
+    verifier: &[u8; PASSWORD_VERIFIER_LENGTH as usize],
+) -> Result<(), FormatError> {
+    if verifier.iter().all(|b| *b == 0) {
+        return Err(FormatError::VerifierIsZero);
+    }
+
+    // Big endian comparison of the little endian verifier
+    if verifier.iter().rev().ge(LARGE_SAFE_PRIME_BIG_ENDIAN.iter()) {
+        return Err(FormatError::VerifierTooLarge);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::FormatError;
+    use crate::formats::{MangosColumns, TrinityColumns};
+    use crate::hex::hex_encode_upper;
+    use crate::normalized_string::NormalizedString;
+    use crate::server::SrpVerifier;
+    use crate::{LARGE_SAFE_PRIME_BIG_ENDIAN, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+
+    fn username() -> NormalizedString {
+        NormalizedString::new("A").unwrap()
+    }
+
+    #[test]
+    fn verify_mangos() {
+        let verifier = SrpVerifier::from_username_and_password(username(), username());
+        let columns = MangosColumns::from_verifier(&verifier);
+        assert_eq!(columns.to_verifier(username()).unwrap(), verifier);
+
+        let mut v = *verifier.password_verifier();
+        v.reverse();
+        assert_eq!(
+            columns.v.trim_start_matches('0'),
+            hex_encode_upper(&v).trim_start_matches('0')
+        );
+
+        // Leading zeros are removed
+        let mut password_verifier = [0_u8; 32];
+        password_verifier[0] = 0xBC;
+        password_verifier[1] = 0x0A;
+        let salt = [0_u8; 32];
+        let verifier = SrpVerifier::from_database_values(username(), password_verifier, salt);
+        let columns = MangosColumns::from_verifier(&verifier);
+        assert_eq!(columns, MangosColumns::new("0ABC", "0"));
+
+        // Unpadded and lowercase input
+        for v in ["abc", "ABC", "0abc", "0000000abc", " ABC\n"] {
+            let parsed = MangosColumns::new(v, "0").to_verifier(username()).unwrap();
+            assert_eq!(parsed, verifier);
+        }
+
+        let n = hex_encode_upper(&LARGE_SAFE_PRIME_BIG_ENDIAN);
+        assert_eq!(
+            MangosColumns::new(n.clone(), "1").to_verifier(username()),
+            Err(FormatError::VerifierTooLarge)
+        );
+        assert_eq!(
+            MangosColumns::new("0", "1").to_verifier(username()),
+            Err(FormatError::VerifierIsZero)
+        );
+        assert_eq!(
+            MangosColumns::new("XY", "1").to_verifier(username()),
+            Err(FormatError::InvalidHex)
+        );
+        assert_eq!(
+            MangosColumns::new("+A", "1").to_verifier(username()),
+            Err(FormatError::InvalidHex)
+        );
+        assert_eq!(
+            MangosColumns::new("1", format!("1{}", n)).to_verifier(username()),
+            Err(FormatError::TooLong {
+                length: 65,
+                maximum: 64
+            })
+        );
+
+        // N - 1 is the largest valid verifier
+        let below = format!("{}{}", &n[..63], "6");
+        assert!(n.ends_with('7'));
+        assert!(MangosColumns::new(below, "1")
+            .to_verifier(username())
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_trinity() {
+        let verifier = SrpVerifier::from_username_and_password(username(), username());
+        let columns = TrinityColumns::from_verifier(&verifier);
+        assert_eq!(&columns.verifier, verifier.password_verifier());
+        assert_eq!(&columns.salt, verifier.salt());
+
+        let columns = TrinityColumns::from_slices(&columns.salt, &columns.verifier).unwrap();
+        assert_eq!(columns.to_verifier(username()).unwrap(), verifier);
+
+        assert_eq!(
+            TrinityColumns::from_slices(&[0; 31], &[0; 32]),
+            Err(FormatError::InvalidLength {
+                length: 31,
+                expected: 32
+            })
+        );
+        assert_eq!(
+            TrinityColumns::from_slices(&[0; 32], &[1; 33]),
+            Err(FormatError::InvalidLength {
+                length: 33,
+                expected: 32
+            })
+        );
+
+        let columns = TrinityColumns {
+            salt: [0; 32],
+            verifier: LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+        };
+        assert_eq!(
+            columns.to_verifier(username()),
+            Err(FormatError::VerifierTooLarge)
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Framing shared by the header modules of all expansions.
+
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::FrameError;
+use crate::vanilla_header::{CLIENT_HEADER_LENGTH, SERVER_HEADER_LENGTH};
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
+
+/// Size in bytes of the opcode in a client header for all expansions.
+pub(crate) const CLIENT_OPCODE_LENGTH: u8 = std::mem::size_of::<u32>() as u8;
+/// Size in bytes of the opcode in a server header for all expansions.
+pub(crate) const SERVER_OPCODE_LENGTH: u8 = std::mem::size_of::<u16>() as u8;
+
+/// `decrypt` returns the size and opcode.
+///
+/// Client headers are the same size for all expansions.
+pub(crate) fn decrypt_client_header_from_buffer(
+    data: &[u8],
+    decrypt: impl FnOnce([u8; CLIENT_HEADER_LENGTH as usize]) -> (u16, u32),
+) -> Option<DecryptedHeader> {
+    let header = data.get(..CLIENT_HEADER_LENGTH as usize)?.try_into().ok()?;
+    let (size, opcode) = decrypt(header);
+
+    Some(DecryptedHeader {
+        size: size.into(),
+        opcode,
+        length: CLIENT_HEADER_LENGTH as usize,
+    })
+}
+
+/// `decrypt` returns the size and opcode.
+///
+/// Only valid for Vanilla and TBC since Wrath server headers are variable length.
+pub(crate) fn decrypt_server_header_from_buffer(
+    data: &[u8],
+    decrypt: impl FnOnce([u8; SERVER_HEADER_LENGTH as usize]) -> (u16, u16),
+) -> Option<DecryptedHeader> {
+    let header = data.get(..SERVER_HEADER_LENGTH as usize)?.try_into().ok()?;
+    let (size, opcode) = decrypt(header);
+
+    Some(DecryptedHeader {
+        size: size.into(),
+        opcode: opcode.into(),
+        length: SERVER_HEADER_LENGTH as usize,
+    })
+}
+
+/// Decompresses the body if the opcode is compressed.
+#[cfg(feature = "compression")]
+pub(crate) fn decompress_frame(
+    compression: Option<&Compression>,
+    frame: Option<(u16, Vec<u8>)>,
+) -> Result<Option<(u16, Vec<u8>)>, FrameError> {
+    match (compression, frame) {
+        (Some(compression), Some((opcode, body))) if compression.is_compressed(opcode) => {
+            let body = compression
+                .decompress(&body)
+                .map_err(|error| FrameError::InvalidCompressedBody { opcode, error })?;
+
+            Ok(Some((opcode, body)))
+        }
+        (_, frame) => Ok(frame),
+    }
+}
+
+/// Header values shared by all expansions and directions.
+pub(crate) struct DecryptedHeader {
+    pub size: u32,
+    pub opcode: u32,
+    /// Length of the encrypted header in bytes.
+    pub length: usize,
+}
+
+/// Buffering shared by the framers of all expansions.
+///
+/// Consumed bytes are skipped with an offset and only removed from the start of the buffer
+/// once they make up at least half of it, so that every frame does not move the rest of the buffer.
+#[derive(Debug, Clone)]
+pub(crate) struct FrameBuffer {
+    buffer: Vec<u8>,
+    /// Start of the bytes that have not been consumed.
+    read: usize,
+    /// Header that has been decrypted, but where the body has not been fully received.
+    header: Option<(u32, usize)>,
+    maximum_body_size: u32,
+}
+
+impl FrameBuffer {
+    pub(crate) const fn new(maximum_body_size: u32) -> Self {
+        Self {
+            buffer: Vec::new(),
+            read: 0,
+            header: None,
+            maximum_body_size,
+        }
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        if self.read > 0 && self.read >= self.buffer.len() / 2 {
+            self.buffer.drain(..self.read);
+            self.read = 0;
+        }
+
+        self.buffer.extend_from_slice(data);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.unread().len()
+    }
+
+    fn unread(&self) -> &[u8] {
+        &self.buffer[self.read..]
+    }
+
+    /// `decrypt_header` must return [`None`] without advancing the decryption state
+    /// if the buffer does not contain a full header.
+    pub(crate) fn next_frame(
+        &mut self,
+        opcode_length: u8,
+        decrypt_header: impl FnOnce(&[u8]) -> Option<DecryptedHeader>,
+    ) -> Result<Option<(u32, Vec<u8>)>, FrameError> {
+        let (opcode, body_size) = match self.header {
+            Some(header) => header,
+            None => {
+                let header = match decrypt_header(self.unread()) {
+                    Some(header) => header,
+                    None => return Ok(None),
+                };
+                self.read += header.length;
+
+                let body_size = match header.size.checked_sub(opcode_length.into()) {
+                    Some(body_size) => body_size,
+                    None => {
+                        return Err(FrameError::SizeSmallerThanOpcode { size: header.size });
+                    }
+                };
+
+                if body_size > self.maximum_body_size {
+                    return Err(FrameError::BodyTooLarge {
+                        size: body_size,
+                        maximum: self.maximum_body_size,
+                    });
+                }
+
+                let header = (header.opcode, body_size as usize);
+                self.header = Some(header);
+                header
+            }
+        };
+
+        if self.len() < body_size {
+            return Ok(None);
+        }
+
+        self.header = None;
+        let body = self.unread()[..body_size].to_vec();
+        self.read += body_size;
+
+        if self.read == self.buffer.len() {
+            self.buffer.clear();
+            self.read = 0;
+        }
+
+        Ok(Some((opcode, body)))
+    }
+
+    /// Fields that decide equality, ignoring how much of the buffer has been consumed.
+    fn key(&self) -> (&[u8], Option<(u32, usize)>, u32) {
+        (self.unread(), self.header, self.maximum_body_size)
+    }
+}
+
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for FrameBuffer {}
+
+impl PartialOrd for FrameBuffer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FrameBuffer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for FrameBuffer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::framing::{DecryptedHeader, FrameBuffer};
+
+    /// Unencrypted header of a single size byte and a single opcode byte.
+    fn header(data: &[u8]) -> Option<DecryptedHeader> {
+        match data {
+            [size, opcode, ..] => Some(DecryptedHeader {
+                size: (*size).into(),
+                opcode: (*opcode).into(),
+                length: 2,
+            }),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn frame_buffer_compacts() {
+        let mut buffer = FrameBuffer::new(16);
+        let mut stream = Vec::new();
+        for opcode in 0..100_u8 {
+            stream.extend_from_slice(&[4, opcode, opcode, opcode, opcode]);
+        }
+
+        let mut frames = Vec::new();
+        for chunk in stream.chunks(7) {
+            buffer.push(chunk);
+            while let Some(frame) = buffer.next_frame(1, header).unwrap() {
+                frames.push(frame);
+            }
+            assert!(buffer.buffer.len() <= 2 * (buffer.len() + 7));
+        }
+
+        assert_eq!(frames.len(), 100);
+        for (opcode, (frame_opcode, body)) in frames.into_iter().enumerate() {
+            assert_eq!(frame_opcode, opcode as u32);
+            assert_eq!(body, vec![opcode as u8; 3]);
+        }
+        assert_eq!(buffer.len(), 0);
+
+        // Consumed bytes do not affect equality
+        let mut a = FrameBuffer::new(16);
+        a.push(&[4, 1, 1, 1, 1, 4]);
+        assert!(a.next_frame(1, header).unwrap().is_some());
+        let mut b = FrameBuffer::new(16);
+        b.push(&[4]);
+        assert_eq!(a, b);
+    }
+}
@@ -1,8 +1,25 @@
-pub fn hex_encode(b: &[u8]) -> String {
+//! Hex encoding and decoding shared by the crate.
+//!
+//! Decoding only accepts the digits `[0-9a-fA-F]`, so signs and whitespace are rejected.
+
+/// Error for hex strings that can not be decoded.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub(crate) enum HexError {
+    /// A character is not a hex digit, or the string has an odd length.
+    InvalidHex,
+    /// The value has more significant digits than fit in the output.
+    TooLong {
+        /// Amount of significant digits.
+        digits: usize,
+    },
+}
+
+#[cfg(any(test, feature = "tracing", feature = "pcap"))]
+pub(crate) fn hex_encode(b: &[u8]) -> String {
     hex_encode_upper(b).to_lowercase()
 }
 
-pub fn hex_encode_upper(b: &[u8]) -> String {
+pub(crate) fn hex_encode_upper(b: &[u8]) -> String {
     let mut s = String::with_capacity(b.len() * 2);
 
     for i in b {
@@ -16,40 +33,54 @@ pub fn hex_encode_upper(b: &[u8]) -> String {
     s
 }
 
-pub fn hex_decode(s: &str) -> Vec<u8> {
-    assert_eq!(s.len() % 2, 0);
+/// Decodes hex with two digits per byte in the same order as the string.
+pub(crate) fn try_hex_decode(s: &str) -> Result<Vec<u8>, HexError> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 {
+        return Err(HexError::InvalidHex);
+    }
 
-    let mut key = Vec::with_capacity(s.len() / 2);
+    s.chunks(2)
+        .map(|c| {
+            let be_nibble = letters_to_numbers(c[0]).ok_or(HexError::InvalidHex)?;
+            let le_nibble = letters_to_numbers(c[1]).ok_or(HexError::InvalidHex)?;
+            Ok((be_nibble << 4) | le_nibble)
+        })
+        .collect()
+}
 
-    for c in s.chars().collect::<Vec<char>>().chunks(2) {
-        let be_nibble = letters_to_numbers(c[0]);
-        let le_nibble = letters_to_numbers(c[1]);
-        let value = (be_nibble << 4) | le_nibble;
-        key.push(value);
+/// Decodes a **big endian** hex number into the **little endian** `out`.
+///
+/// Leading zeros are optional and the string may have an odd length.
+/// An empty string is zero.
+pub(crate) fn try_hex_decode_big_endian(s: &str, out: &mut [u8]) -> Result<(), HexError> {
+    let digits = s.trim_start_matches('0').as_bytes();
+    if digits.len() > out.len() * 2 {
+        return Err(HexError::TooLong {
+            digits: digits.len(),
+        });
     }
 
-    key
+    out.iter_mut().for_each(|b| *b = 0);
+    for (i, c) in digits.iter().rev().enumerate() {
+        let nibble = letters_to_numbers(*c).ok_or(HexError::InvalidHex)?;
+        out[i / 2] |= nibble << ((i % 2) * 4);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) fn hex_decode(s: &str) -> Vec<u8> {
+    try_hex_decode(s).expect("invalid hex in test")
 }
 
-const fn letters_to_numbers(c: char) -> u8 {
+const fn letters_to_numbers(c: u8) -> Option<u8> {
     match c {
-        '0' => 0x0,
-        '1' => 0x1,
-        '2' => 0x2,
-        '3' => 0x3,
-        '4' => 0x4,
-        '5' => 0x5,
-        '6' => 0x6,
-        '7' => 0x7,
-        '8' => 0x8,
-        '9' => 0x9,
-        'A' | 'a' => 0xa,
-        'B' | 'b' => 0xb,
-        'C' | 'c' => 0xc,
-        'D' | 'd' => 0xd,
-        'E' | 'e' => 0xe,
-        'F' | 'f' => 0xf,
-        _ => panic!("invalid hex letter"),
+        b'0'..=b'9' => Some(c - b'0'),
+        b'A'..=b'F' => Some(c - b'A' + 0xa),
+        b'a'..=b'f' => Some(c - b'a' + 0xa),
+        _ => None,
     }
 }
 
@@ -74,3 +105,43 @@ const fn numbers_to_letters(b: u8) -> char {
         _ => panic!("invalid hex digit"),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::hex::{hex_encode, try_hex_decode, try_hex_decode_big_endian, HexError};
+
+    #[test]
+    fn verify_decode() {
+        assert_eq!(try_hex_decode("00aBfF"), Ok(vec![0x00, 0xAB, 0xFF]));
+        assert_eq!(try_hex_decode(""), Ok(vec![]));
+        assert_eq!(hex_encode(&[0x00, 0xAB, 0xFF]), "00abff");
+
+        for invalid in ["+A", "-1", " A", "0", "0G", "é0"] {
+            assert_eq!(
+                try_hex_decode(invalid),
+                Err(HexError::InvalidHex),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn verify_decode_big_endian() {
+        let mut out = [0xFF_u8; 2];
+        assert_eq!(try_hex_decode_big_endian("00abc", &mut out), Ok(()));
+        assert_eq!(out, [0xBC, 0x0A]);
+
+        assert_eq!(try_hex_decode_big_endian("", &mut out), Ok(()));
+        assert_eq!(out, [0, 0]);
+
+        assert_eq!(
+            try_hex_decode_big_endian("+A", &mut out),
+            Err(HexError::InvalidHex)
+        );
+        assert_eq!(
+            try_hex_decode_big_endian("1ABCD", &mut out),
+            Err(HexError::TooLong { digits: 5 })
+        );
+    }
+}
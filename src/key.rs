@@ -5,8 +5,11 @@ use std::convert::TryFrom;
 use crate::bigint;
 
 use rand::{thread_rng, RngCore};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
 
-use crate::error::InvalidPublicKeyError;
+use crate::error::{InvalidPublicKeyError, RngHealthError};
 #[cfg(test)]
 use crate::hex::*;
 use crate::primes::{LargeSafePrime, LARGE_SAFE_PRIME_LENGTH};
@@ -28,7 +31,8 @@ macro_rules! key_new {
         impl Default for $name {
             fn default() -> Self {
                 let mut key = [0_u8; $size];
-                thread_rng().fill_bytes(&mut key);
+                // Failures are still recorded for `rng_health`
+                let _ = fill_random(RngSource::$name, &mut key);
                 Self::from_le_bytes(key)
             }
         }
@@ -37,6 +41,13 @@ macro_rules! key_new {
             pub(crate) fn randomized() -> Self {
                 Self::default()
             }
+
+            /// Same as `randomized` but fails if the value does not pass the health checks.
+            pub(crate) fn try_randomized() -> Result<Self, RngHealthError> {
+                let mut key = [0_u8; $size];
+                fill_random(RngSource::$name, &mut key)?;
+                Ok(Self::from_le_bytes(key))
+            }
         }
     };
 }
@@ -312,7 +323,15 @@ key_new!(ReconnectData; RECONNECT_CHALLENGE_DATA_LENGTH as usize);
 key_no_checks_initialization!(ReconnectData; RECONNECT_CHALLENGE_DATA_LENGTH as usize);
 impl ReconnectData {
     pub fn randomize_data(&mut self) {
-        thread_rng().fill_bytes(&mut self.key);
+        // Failures are still recorded for `rng_health`
+        let _ = self.try_randomize_data();
+    }
+
+    /// Same as `randomize_data` but fails if the data does not pass the health checks.
+    ///
+    /// The data is replaced even if this fails.
+    pub fn try_randomize_data(&mut self) -> Result<(), RngHealthError> {
+        fill_random(RngSource::ReconnectData, &mut self.key)
     }
 }
 
@@ -325,14 +344,245 @@ pub const SESSION_KEY_LENGTH: u8 = PROOF_LENGTH * 2;
 key_wrapper!(SessionKey; SESSION_KEY_LENGTH as usize);
 key_no_checks_initialization!(SessionKey; SESSION_KEY_LENGTH as usize);
 
+/// Kind of random value checked by the [RNG health checks](enable_rng_health_checks).
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum RngSource {
+    /// Client and server private keys.
+    PrivateKey,
+    /// Salts for new password verifiers.
+    Salt,
+    /// Reconnect challenge data.
+    ReconnectData,
+    /// Seeds for the `ProofSeed` of the header modules.
+    ProofSeed,
+    /// Keys from `SealingKey::randomized`, only used with the `sealed-verifier` feature.
+    SealingKey,
+    /// Nonces of `SealedVerifier`, only used with the `sealed-verifier` feature.
+    Nonce,
+}
+
+impl RngSource {
+    const AMOUNT: usize = 6;
+
+    /// `ProofSeed`s are only 4 bytes, so a healthy RNG creates a seed of all zeros or the same
+    /// seed twice in a row about once every `2^32` seeds.
+    const fn is_short(self) -> bool {
+        matches!(self, RngSource::ProofSeed)
+    }
+
+    /// Amount of identical values in a row that fails the repeated output check.
+    const fn repeat_limit(self) -> u8 {
+        if self.is_short() {
+            3
+        } else {
+            2
+        }
+    }
+}
+
+impl Display for RngSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RngSource::PrivateKey => write!(f, "private key"),
+            RngSource::Salt => write!(f, "salt"),
+            RngSource::ReconnectData => write!(f, "reconnect data"),
+            RngSource::ProofSeed => write!(f, "proof seed"),
+            RngSource::SealingKey => write!(f, "sealing key"),
+            RngSource::Nonce => write!(f, "sealed verifier nonce"),
+        }
+    }
+}
+
+/// Amount of consecutive outputs a byte position must keep the same value
+/// before it is considered stuck.
+const STUCK_BYTE_RUN: u8 = 8;
+
+static RNG_HEALTH_CHECKS_ENABLED: AtomicBool = AtomicBool::new(false);
+static RNG_HEALTH: Mutex<RngHealth> = Mutex::new(RngHealth::NEW);
+
+/// Previous output of a single [`RngSource`].
+struct RngHistory {
+    previous: Vec<u8>,
+    /// Amount of identical outputs in a row, including the previous output.
+    repeats: u8,
+    runs: Vec<u8>,
+}
+
+impl RngHistory {
+    const NEW: Self = Self {
+        previous: Vec::new(),
+        repeats: 0,
+        runs: Vec::new(),
+    };
+}
+
+struct RngHealth {
+    failure: Option<RngHealthError>,
+    history: [RngHistory; RngSource::AMOUNT],
+}
+
+impl RngHealth {
+    const NEW: Self = Self {
+        failure: None,
+        history: [RngHistory::NEW; RngSource::AMOUNT],
+    };
+
+    /// Continuous test of a new output, similar to the FIPS 140 continuous RNG test.
+    ///
+    /// Fails if the output is all zeros, identical to the previous output of the same source,
+    /// or if a byte has had the same value for [`STUCK_BYTE_RUN`] outputs.
+    ///
+    /// Short sources are never checked for all zeros and must repeat the same output
+    /// [`RngSource::repeat_limit`] times in a row, since a healthy RNG would otherwise fail.
+    fn check(&mut self, source: RngSource, output: &[u8]) -> Result<(), RngHealthError> {
+        let history = &mut self.history[source as usize];
+
+        history.repeats = if history.previous == output {
+            history.repeats.saturating_add(1)
+        } else {
+            1
+        };
+
+        let result = if !source.is_short() && output.iter().all(|b| *b == 0) {
+            Err(RngHealthError::AllZeros { source })
+        } else if history.repeats >= source.repeat_limit() {
+            Err(RngHealthError::RepeatedOutput { source })
+        } else {
+            Ok(())
+        };
+
+        if history.previous.len() == output.len() {
+            for (i, run) in history.runs.iter_mut().enumerate() {
+                *run = if history.previous[i] == output[i] {
+                    run.saturating_add(1)
+                } else {
+                    1
+                };
+            }
+        } else {
+            history.runs = vec![1; output.len()];
+        }
+        history.previous = output.to_vec();
+
+        let result =
+            result.and_then(
+                |_| match history.runs.iter().position(|run| *run >= STUCK_BYTE_RUN) {
+                    Some(position) => Err(RngHealthError::StuckByte { source, position }),
+                    None => Ok(()),
+                },
+            );
+
+        if let Err(e) = result {
+            self.failure.get_or_insert(e);
+        }
+
+        result
+    }
+}
+
+fn rng_health_state() -> std::sync::MutexGuard<'static, RngHealth> {
+    RNG_HEALTH.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Fills `output` from the system RNG and runs the health checks if they are enabled.
+///
+/// `output` is filled even if the checks fail.
+pub(crate) fn fill_random(source: RngSource, output: &mut [u8]) -> Result<(), RngHealthError> {
+    thread_rng().fill_bytes(output);
+
+    if !RNG_HEALTH_CHECKS_ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let result = rng_health_state().check(source, output);
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        tracing::error!(error = %e, "RNG health check failed");
+    }
+
+    result
+}
+
+/// Creates a random seed for a `ProofSeed`.
+pub(crate) fn random_proof_seed() -> u32 {
+    let mut seed = [0_u8; 4];
+    // Failures are still recorded for `rng_health`
+    let _ = fill_random(RngSource::ProofSeed, &mut seed);
+    u32::from_le_bytes(seed)
+}
+
+/// Same as [`random_proof_seed`] but fails if the seed does not pass the health checks.
+pub(crate) fn try_random_proof_seed() -> Result<u32, RngHealthError> {
+    let mut seed = [0_u8; 4];
+    fill_random(RngSource::ProofSeed, &mut seed)?;
+    Ok(u32::from_le_bytes(seed))
+}
+
+/// Enables continuous health checks of the random values generated by the crate.
+///
+/// Every private key, salt, reconnect challenge data, `ProofSeed`, and `sealed-verifier` key and
+/// nonce is checked to not be all zeros, not be identical to the previous value of the same
+/// [`RngSource`], and to not have a byte that stays the same for 8 values in a row.
+/// `ProofSeed`s are only 4 bytes, so they are allowed to be all zeros and must be the same
+/// 3 times in a row to fail, otherwise a healthy RNG would fail about once every `2^32` seeds.
+/// The first failure is kept until [`reset_rng_health`] and is returned by [`rng_health`].
+///
+/// Functions that create random values still return values that fail the checks.
+/// The `try_` versions, such as
+/// [`SrpVerifier::try_into_proof`](crate::server::SrpVerifier::try_into_proof) and
+/// `ProofSeed::try_new`, return the [`RngHealthError`] instead.
+///
+/// Checks are disabled by default since they require a global lock for every random value.
+pub fn enable_rng_health_checks() {
+    RNG_HEALTH_CHECKS_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Disables the checks enabled by [`enable_rng_health_checks`].
+///
+/// A failure that has already happened is still returned by [`rng_health`]
+/// until [`reset_rng_health`] is called.
+pub fn disable_rng_health_checks() {
+    RNG_HEALTH_CHECKS_ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Returns `true` if the checks are enabled through [`enable_rng_health_checks`].
+pub fn rng_health_checks_enabled() -> bool {
+    RNG_HEALTH_CHECKS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns the first failure of the [RNG health checks](enable_rng_health_checks).
+///
+/// Servers should stop authenticating clients while this returns an error since the keys
+/// and salts created by a failing RNG can not be trusted.
+///
+/// # Errors
+///
+/// If a health check has failed since the checks were enabled or last reset.
+pub fn rng_health() -> Result<(), RngHealthError> {
+    match rng_health_state().failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Clears the failure returned by [`rng_health`] and the values the checks compare against.
+pub fn reset_rng_health() {
+    *rng_health_state() = RngHealth::NEW;
+}
+
 #[cfg(test)]
 #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
 mod test {
 
     use crate::bigint::Integer;
-    use crate::key::{PrivateKey, PublicKey, PUBLIC_KEY_LENGTH};
+    use crate::error::RngHealthError;
+    use crate::key::{
+        PrivateKey, PublicKey, RngHealth, RngSource, PRIVATE_KEY_LENGTH, PUBLIC_KEY_LENGTH,
+        RECONNECT_CHALLENGE_DATA_LENGTH, SALT_LENGTH, STUCK_BYTE_RUN,
+    };
     use crate::primes::LargeSafePrime;
     use crate::LARGE_SAFE_PRIME_LITTLE_ENDIAN;
+    use rand::{thread_rng, RngCore};
 
     #[test]
     fn double_large_safe_prime_is_unrepresentable() {
@@ -394,4 +644,71 @@ mod test {
         let k = PrivateKey::from_be_hex_str(DEADBEEF);
         assert_eq!(&k.as_be_hex_string(), PADDED_DEADBEEF);
     }
+
+    #[test]
+    fn rng_health_detects_failures() {
+        let mut health = RngHealth::NEW;
+        let source = RngSource::Salt;
+
+        assert_eq!(
+            health.check(source, &[0; 4]),
+            Err(RngHealthError::AllZeros { source })
+        );
+        assert!(health.check(source, &[1, 2, 3, 4]).is_ok());
+        assert_eq!(
+            health.check(source, &[1, 2, 3, 4]),
+            Err(RngHealthError::RepeatedOutput { source })
+        );
+        // Other sources are compared separately
+        assert!(health.check(RngSource::PrivateKey, &[1, 2, 3, 4]).is_ok());
+
+        // The first failure is kept
+        assert_eq!(health.failure, Some(RngHealthError::AllZeros { source }));
+
+        // Proof seeds are allowed to be zero and repeat once
+        let mut health = RngHealth::NEW;
+        let source = RngSource::ProofSeed;
+        assert!(health.check(source, &[0; 4]).is_ok());
+        assert!(health.check(source, &[0; 4]).is_ok());
+        assert_eq!(
+            health.check(source, &[0; 4]),
+            Err(RngHealthError::RepeatedOutput { source })
+        );
+        let source = RngSource::Salt;
+
+        let mut health = RngHealth::NEW;
+        for i in 1..STUCK_BYTE_RUN {
+            assert!(health.check(source, &[i, 0xAB]).is_ok());
+        }
+        assert_eq!(
+            health.check(source, &[STUCK_BYTE_RUN, 0xAB]),
+            Err(RngHealthError::StuckByte {
+                source,
+                position: 1
+            })
+        );
+    }
+
+    #[test]
+    fn rng_health_passes_for_system_rng() {
+        // Checked locally since enabling the global checks would affect other tests
+        let mut health = RngHealth::NEW;
+
+        for _ in 0..100 {
+            for (source, length) in [
+                (RngSource::Salt, SALT_LENGTH),
+                (RngSource::PrivateKey, PRIVATE_KEY_LENGTH),
+                (RngSource::ReconnectData, RECONNECT_CHALLENGE_DATA_LENGTH),
+                (RngSource::ProofSeed, 4),
+                (RngSource::SealingKey, 32),
+                (RngSource::Nonce, 12),
+            ] {
+                let mut output = vec![0_u8; length.into()];
+                thread_rng().fill_bytes(&mut output);
+                assert_eq!(health.check(source, &output), Ok(()));
+            }
+        }
+
+        assert_eq!(health.failure, None);
+    }
 }
@@ -19,13 +19,28 @@
 //!
 //! The crate is split into:
 //! * A [`server`] module containing structs for use on the server.
-//! Either `srp-fast-math` or `srp-default-math` must be enabled to for this to be enabled.
+//!   Either `srp-fast-math` or `srp-default-math` must be enabled to for this to be enabled.
 //! * A [`client`] module containing structs for use on the client.
-//! Either `srp-fast-math` or `srp-default-math` must be enabled to for this to be enabled.
+//!   Either `srp-fast-math` or `srp-default-math` must be enabled to for this to be enabled.
 //! * A [`vanilla_header`] module containing structs for decrypting Vanilla world packets.
 //! * A [`tbc_header`] module containing structs for decrypting TBC world packets.
 //! * A [`wrath_header`] module containing structs for decrypting Wrath world packets.
-//! `wrath-header` must be enabled for this to be enabled.
+//!   `wrath-header` must be enabled for this to be enabled.
+//! * A [`warden`] module containing structs for decrypting Warden messages.
+//!   `wrath-header` must be enabled for this to be enabled.
+//! * A `pcap` module for decrypting world connections in packet captures.
+//!   `pcap` must be enabled for this to be enabled.
+//! * A `compression` module for compressed world message bodies.
+//!   `compression` must be enabled for this to be enabled.
+//! * A [`formats`] module for importing and exporting verifiers in the database formats of other servers.
+//!   Either `srp-fast-math` or `srp-default-math` must be enabled to for this to be enabled.
+//! * A [`desync`] module for diagnosing header ciphers that are out of sync.
+//! * A [`proxy`] module for re-encrypting world packets between two sessions.
+//! * A [`world_auth`] module for rejecting replayed world server authentications.
+//! * A [`session_ticket`] module for handing the session key from the auth server to world servers.
+//! * A [`clock`] module with the sources of time used for expiry.
+//! * An [`observer`] module for receiving authentication events for audit logging and metrics.
+//! * A [`snapshot`] module for moving the state of the header crypto between processes.
 //! * An [`error`] module for errors that are shared by all modules.
 //! * A [`normalized_string`] module used for all modules to correctly handle strings.
 //!
@@ -53,13 +68,13 @@
 //! Two different arbitrary precision integer libraries can be used, either:
 //!
 //! * [num-bigint](https://crates.io/crates/num-bigint). A slow pure Rust implementation without
-//! external dependencies. It is enabled through the `srp-default-math` feature.
-//! This is enabled by default, and requires no opt in.
+//!   external dependencies. It is enabled through the `srp-default-math` feature.
+//!   This is enabled by default, and requires no opt in.
 //!
 //! * [rug](https://crates.io/crates/rug). A fast wrapper around the [GMP library](https://gmplib.org/)
-//! with external dependencies, as described in the [`gmp_mpfr_sys` documentation](https://docs.rs/gmp-mpfr-sys/1.4.6/gmp_mpfr_sys/index.html#building-on-gnulinux).
-//! This is enabled with the `srp-fast-math` feature and disabling default features.
-//! So **instead** of the above do this:
+//!   with external dependencies, as described in the [`gmp_mpfr_sys` documentation](https://docs.rs/gmp-mpfr-sys/1.4.6/gmp_mpfr_sys/index.html#building-on-gnulinux).
+//!   This is enabled with the `srp-fast-math` feature and disabling default features.
+//!   So **instead** of the above do this:
 //!
 //! ```toml
 //! [dependencies]
@@ -74,6 +89,9 @@
 //!
 //! The `wrath-header` feature gates features and dependencies related to [`wrath_header`].
 //! The `tbc-header` feature gates features and dependencies related to [`tbc_header`].
+//! The `compression` feature gates the `compression` module and decompression in the framers.
+//! The `pcap` feature gates the `pcap` module and the `wow_pcap_decrypt` binary.
+//! The `tracing` feature adds [`tracing`](https://docs.rs/tracing) spans and events to the SRP and header proof steps.
 //!
 //! # MSRV
 //!
@@ -105,13 +123,21 @@
     missing_docs
 )]
 
+pub use expansion::Expansion;
+pub use key::disable_rng_health_checks;
+pub use key::enable_rng_health_checks;
+pub use key::reset_rng_health;
+pub use key::rng_health;
+pub use key::rng_health_checks_enabled;
 pub use key::PublicKey;
+pub use key::RngSource;
 pub use key::PASSWORD_VERIFIER_LENGTH;
 pub use key::PROOF_LENGTH;
 pub use key::PUBLIC_KEY_LENGTH;
 pub use key::RECONNECT_CHALLENGE_DATA_LENGTH;
 pub use key::SALT_LENGTH;
 pub use key::SESSION_KEY_LENGTH;
+pub use key::SHA1_HASH_LENGTH;
 pub use primes::GENERATOR;
 pub use primes::GENERATOR_LENGTH;
 pub use primes::LARGE_SAFE_PRIME_BIG_ENDIAN;
@@ -122,23 +148,40 @@ pub use primes::LARGE_SAFE_PRIME_LITTLE_ENDIAN;
 pub(crate) mod bigint;
 #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
 pub mod client;
+pub mod clock;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod desync;
 pub mod error;
+mod expansion;
+#[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
+pub mod formats;
+pub(crate) mod framing;
+pub(crate) mod hex;
 mod key;
 pub mod normalized_string;
+pub mod observer;
+#[cfg(feature = "pcap")]
+pub mod pcap;
 pub(crate) mod primes;
+pub mod proxy;
 #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
 pub mod server;
+pub mod session_ticket;
+pub mod snapshot;
 #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
 pub(crate) mod srp_internal;
 #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
 pub(crate) mod srp_internal_client;
 #[cfg(feature = "tbc-header")]
 pub mod tbc_header;
+mod trace;
 pub mod vanilla_header;
 #[cfg(feature = "wrath-header")]
+pub mod warden;
+pub mod world_auth;
+#[cfg(feature = "wrath-header")]
 pub mod wrath_header;
 
-#[cfg(test)]
-pub(crate) mod hex;
 #[cfg(all(test, any(feature = "srp-default-math", feature = "srp-fast-math")))]
 mod test;
@@ -0,0 +1,347 @@
+//! Events for audit logging and metrics.
+//!
+//! An [`AuthObserver`] is attached with `with_observer` to the server types
+//! [`SrpVerifier`](crate::server::SrpVerifier), [`SrpProof`](crate::server::SrpProof),
+//! and [`SrpServer`](crate::server::SrpServer), and to the
+//! [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard).
+//! The observer is carried from [`SrpVerifier`](crate::server::SrpVerifier) to
+//! [`SrpServer`](crate::server::SrpServer) so it only needs to be attached once per login.
+//!
+//! World server proofs are reported by the `into_*_server_crypto` functions of
+//! [`SrpServer`](crate::server::SrpServer) and the `verify_*` functions of
+//! [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard).
+//! The `ProofSeed` of each header module does not have an observer.
+//!
+//! Every state transition is reported as an [`AuthEvent`].
+//! Events only contain the username, values that are sent in plain text over the network,
+//! and the outcome. Session keys, private keys, password verifiers, and proofs are never included.
+//!
+//! Observers are shared through an [`Arc`](std::sync::Arc), so the same observer can be
+//! attached to every login.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//! use wow_srp::observer::{AuthEvent, AuthObserver};
+//!
+//! struct FailedLogins(AtomicUsize);
+//!
+//! impl AuthObserver for FailedLogins {
+//!     fn on_event(&self, event: &AuthEvent<'_>) {
+//!         if let AuthEvent::ProofMismatch { username, .. } = event {
+//!             println!("Failed login for '{}'", username);
+//!             self.0.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let failed_logins = Arc::new(FailedLogins(AtomicUsize::new(0)));
+//! # #[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
+//! # {
+//! # use wow_srp::normalized_string::NormalizedString;
+//! # use wow_srp::server::SrpVerifier;
+//! # let username = NormalizedString::new("A").unwrap();
+//! # let verifier = SrpVerifier::from_username_and_password(username.clone(), username);
+//!
+//! let proof = verifier.with_observer(failed_logins.clone()).into_proof();
+//! # }
+//! ```
+
+use crate::Expansion;
+use crate::{PUBLIC_KEY_LENGTH, SALT_LENGTH};
+use std::cmp::Ordering;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// State transition of an authentication.
+///
+/// All arrays are **little endian**.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum AuthEvent<'a> {
+    /// The server public key and salt were created for the logon challenge.
+    ChallengeIssued {
+        /// Username of the account.
+        username: &'a str,
+        /// Server public key sent to the client.
+        server_public_key: [u8; PUBLIC_KEY_LENGTH as usize],
+        /// Salt sent to the client.
+        salt: [u8; SALT_LENGTH as usize],
+    },
+    /// The client sent an invalid public key.
+    ///
+    /// This is unlikely to happen by accident and usually means a malicious client.
+    InvalidPublicKey {
+        /// Username of the account.
+        username: &'a str,
+        /// Public key sent by the client.
+        client_public_key: [u8; PUBLIC_KEY_LENGTH as usize],
+    },
+    /// The client proof does not match, usually because of a wrong password.
+    ProofMismatch {
+        /// Username of the account.
+        username: &'a str,
+        /// Public key sent by the client.
+        client_public_key: [u8; PUBLIC_KEY_LENGTH as usize],
+    },
+    /// The client proof matches and the client is authenticated.
+    Success {
+        /// Username of the account.
+        username: &'a str,
+        /// Public key sent by the client.
+        client_public_key: [u8; PUBLIC_KEY_LENGTH as usize],
+    },
+    /// New reconnect challenge data was generated, after a successful login
+    /// or after a reconnect attempt.
+    ReconnectChallenge {
+        /// Username of the account.
+        username: &'a str,
+    },
+    /// The client proved that it knows the session key during reconnection.
+    ReconnectSuccess {
+        /// Username of the account.
+        username: &'a str,
+    },
+    /// The reconnect proof does not match.
+    ReconnectFailure {
+        /// Username of the account.
+        username: &'a str,
+    },
+    /// The client proof for the world server matches.
+    WorldProofSuccess {
+        /// Username of the account.
+        username: &'a str,
+        /// Header crypto used.
+        expansion: Expansion,
+        /// Seed sent by the server.
+        server_seed: u32,
+        /// Seed sent by the client.
+        client_seed: u32,
+    },
+    /// The client proof for the world server does not match.
+    WorldProofMismatch {
+        /// Username of the account.
+        username: &'a str,
+        /// Header crypto used.
+        expansion: Expansion,
+        /// Seed sent by the server.
+        server_seed: u32,
+        /// Seed sent by the client.
+        client_seed: u32,
+    },
+}
+
+/// Receives [`AuthEvent`]s, see the [module level documentation](crate::observer).
+///
+/// Events are sent synchronously from the authentication functions, so implementations
+/// should return quickly.
+pub trait AuthObserver: Send + Sync {
+    /// Called for every event.
+    fn on_event(&self, event: &AuthEvent<'_>);
+}
+
+/// Optional observer stored in the state machine types.
+///
+/// Does not take part in comparisons or hashing, so that values with and without
+/// an observer are equal.
+#[derive(Clone, Default)]
+pub(crate) struct ObserverSlot(Option<Arc<dyn AuthObserver>>);
+
+impl ObserverSlot {
+    pub(crate) const NONE: Self = Self(None);
+
+    pub(crate) fn new(observer: Arc<dyn AuthObserver>) -> Self {
+        Self(Some(observer))
+    }
+
+    pub(crate) fn emit(&self, event: AuthEvent<'_>) {
+        if let Some(observer) = &self.0 {
+            observer.on_event(&event);
+        }
+    }
+
+    /// Emits [`AuthEvent::WorldProofSuccess`] or [`AuthEvent::WorldProofMismatch`].
+    pub(crate) fn emit_world_proof(
+        &self,
+        username: &str,
+        expansion: Expansion,
+        server_seed: u32,
+        client_seed: u32,
+        matches: bool,
+    ) {
+        self.emit(if matches {
+            AuthEvent::WorldProofSuccess {
+                username,
+                expansion,
+                server_seed,
+                client_seed,
+            }
+        } else {
+            AuthEvent::WorldProofMismatch {
+                username,
+                expansion,
+                server_seed,
+                client_seed,
+            }
+        });
+    }
+}
+
+impl Debug for ObserverSlot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Some(AuthObserver)"),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+impl PartialEq for ObserverSlot {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ObserverSlot {}
+
+impl PartialOrd for ObserverSlot {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ObserverSlot {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for ObserverSlot {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(all(test, any(feature = "srp-default-math", feature = "srp-fast-math")))]
+mod test {
+    use crate::client::SrpClientUser;
+    use crate::normalized_string::NormalizedString;
+    use crate::observer::{AuthEvent, AuthObserver};
+    use crate::server::SrpVerifier;
+    use crate::vanilla_header::ProofSeed;
+    use crate::world_auth::WorldAuthGuard;
+    use crate::{Expansion, PublicKey, GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl AuthObserver for Recorder {
+        fn on_event(&self, event: &AuthEvent<'_>) {
+            let name = format!("{:?}", event);
+            let name = name.split(' ').next().unwrap().to_string();
+            self.0.lock().unwrap().push(name);
+        }
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn verify_events() {
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let username = NormalizedString::new("A").unwrap();
+        let verifier = SrpVerifier::from_username_and_password(username.clone(), username.clone());
+        let proof = verifier.with_observer(recorder.clone()).into_proof();
+        assert_eq!(recorder.take(), ["ChallengeIssued"]);
+
+        assert!(proof.client_public_key([0; 32]).is_err());
+        assert_eq!(recorder.take(), ["InvalidPublicKey"]);
+
+        let client = SrpClientUser::new(username.clone(), username.clone()).into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*proof.server_public_key()).unwrap(),
+            *proof.salt(),
+        );
+        let client_public_key = proof
+            .client_public_key(*client.client_public_key())
+            .unwrap();
+
+        assert!(proof
+            .clone()
+            .into_server(client_public_key, [0; 20])
+            .is_err());
+        let (mut server, server_proof) = proof
+            .into_server(client_public_key, *client.client_proof())
+            .unwrap();
+        assert_eq!(
+            recorder.take(),
+            ["ProofMismatch", "Success", "ReconnectChallenge"]
+        );
+
+        // Reading the challenge data does not notify the observer
+        let client = client.verify_server_proof(server_proof).unwrap();
+        let values = client.calculate_reconnect_values(*server.reconnect_challenge_data());
+        assert!(!server.verify_reconnection_attempt(values.challenge_data, [0; 20]));
+        let values = client.calculate_reconnect_values(*server.reconnect_challenge_data());
+        assert!(server.verify_reconnection_attempt(values.challenge_data, values.proof));
+        assert_eq!(
+            recorder.take(),
+            [
+                "ReconnectFailure",
+                "ReconnectChallenge",
+                "ReconnectSuccess",
+                "ReconnectChallenge"
+            ]
+        );
+
+        // The seed uses the observer of the server
+        let seed = ProofSeed::new();
+        let (client_seed, client_proof, _) = client.into_vanilla_client_crypto(seed.seed());
+        assert!(server
+            .clone()
+            .into_vanilla_server_crypto(seed, [0; 20], client_seed)
+            .is_err());
+        assert!(server
+            .into_vanilla_server_crypto(seed, client_proof, client_seed)
+            .is_ok());
+        assert_eq!(recorder.take(), ["WorldProofMismatch", "WorldProofSuccess"]);
+    }
+
+    #[test]
+    fn verify_world_event_values() {
+        struct Last(Mutex<Option<(String, Expansion, u32, u32)>>);
+        impl AuthObserver for Last {
+            fn on_event(&self, event: &AuthEvent<'_>) {
+                if let AuthEvent::WorldProofMismatch {
+                    username,
+                    expansion,
+                    server_seed,
+                    client_seed,
+                } = *event
+                {
+                    *self.0.lock().unwrap() =
+                        Some((username.to_string(), expansion, server_seed, client_seed));
+                }
+            }
+        }
+        let last = Arc::new(Last(Mutex::new(None)));
+
+        let username = NormalizedString::new("A").unwrap();
+        let mut guard = WorldAuthGuard::new(Duration::from_secs(30), Duration::from_secs(600))
+            .with_observer(last.clone());
+        let server_seed = guard.issue_seed(1);
+        assert!(guard
+            .verify_vanilla(1, &username, [0; 40], [0; 20], 2)
+            .is_err());
+
+        assert_eq!(
+            *last.0.lock().unwrap(),
+            Some(("A".to_string(), Expansion::Vanilla, server_seed, 2))
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::error::PcapError;
+use std::convert::TryInto;
+use std::time::Duration;
+
+const PCAP_MICROSECONDS: u32 = 0xA1B2_C3D4;
+const PCAP_NANOSECONDS: u32 = 0xA1B2_3C4D;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_PACKET: u32 = 2;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const PCAPNG_OPTION_END: u16 = 0;
+const PCAPNG_OPTION_TIMESTAMP_RESOLUTION: u16 = 9;
+
+/// A single captured link layer frame.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Record<'a> {
+    pub timestamp: Duration,
+    pub link_type: u32,
+    pub data: &'a [u8],
+}
+
+/// Reads every record from either a pcap or pcapng file.
+pub(crate) fn read_records(capture: &[u8]) -> Result<Vec<Record<'_>>, PcapError> {
+    let magic = capture.get(..4).ok_or(PcapError::UnknownFormat)?;
+    let magic = [magic[0], magic[1], magic[2], magic[3]];
+
+    if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER {
+        return read_pcapng(capture);
+    }
+
+    for big_endian in [false, true] {
+        let magic = Endian { big_endian }.u32(magic);
+        if magic == PCAP_MICROSECONDS || magic == PCAP_NANOSECONDS {
+            return read_pcap(capture, Endian { big_endian }, magic == PCAP_NANOSECONDS);
+        }
+    }
+
+    Err(PcapError::UnknownFormat)
+}
+
+fn read_pcap(
+    capture: &[u8],
+    endian: Endian,
+    nanoseconds: bool,
+) -> Result<Vec<Record<'_>>, PcapError> {
+    const HEADER_LENGTH: usize = 24;
+    const RECORD_HEADER_LENGTH: usize = 16;
+
+    let header = capture.get(..HEADER_LENGTH).ok_or(PcapError::Truncated)?;
+    let link_type = endian.u32_at(header, 20)?;
+
+    let mut records = Vec::new();
+    let mut rest = &capture[HEADER_LENGTH..];
+    while !rest.is_empty() {
+        let header = rest
+            .get(..RECORD_HEADER_LENGTH)
+            .ok_or(PcapError::Truncated)?;
+        let seconds = endian.u32_at(header, 0)?;
+        let fraction = endian.u32_at(header, 4)?;
+        let length = endian.u32_at(header, 8)? as usize;
+
+        let data = rest
+            .get(RECORD_HEADER_LENGTH..RECORD_HEADER_LENGTH + length)
+            .ok_or(PcapError::Truncated)?;
+
+        let fraction = if nanoseconds {
+            Duration::from_nanos(fraction.into())
+        } else {
+            Duration::from_micros(fraction.into())
+        };
+
+        records.push(Record {
+            timestamp: Duration::from_secs(seconds.into()) + fraction,
+            link_type,
+            data,
+        });
+
+        rest = &rest[RECORD_HEADER_LENGTH + length..];
+    }
+
+    Ok(records)
+}
+
+/// Link type and timestamp resolution of a pcapng interface.
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u32,
+    /// Amount of timestamp units in a second.
+    units_per_second: u64,
+}
+
+fn read_pcapng(capture: &[u8]) -> Result<Vec<Record<'_>>, PcapError> {
+    let mut records = Vec::new();
+    let mut interfaces = Vec::new();
+    let mut endian = Endian { big_endian: false };
+
+    let mut rest = capture;
+    while !rest.is_empty() {
+        let block_type = endian.u32_at(rest, 0)?;
+
+        if block_type == PCAPNG_SECTION_HEADER {
+            let byte_order = rest.get(8..12).ok_or(PcapError::Truncated)?;
+            endian = if u32::from_le_bytes(byte_order.try_into().unwrap()) == PCAPNG_BYTE_ORDER {
+                Endian { big_endian: false }
+            } else if u32::from_be_bytes(byte_order.try_into().unwrap()) == PCAPNG_BYTE_ORDER {
+                Endian { big_endian: true }
+            } else {
+                return Err(PcapError::UnknownFormat);
+            };
+
+            // Interfaces are only valid for the section they are in
+            interfaces.clear();
+        }
+
+        let length = endian.u32_at(rest, 4)? as usize;
+        if length < 12 || length % 4 != 0 {
+            return Err(PcapError::Truncated);
+        }
+        let block = rest.get(..length).ok_or(PcapError::Truncated)?;
+        let body = &block[8..length - 4];
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                interfaces.push(read_interface(body, endian)?);
+            }
+            PCAPNG_ENHANCED_PACKET | PCAPNG_PACKET => {
+                // The obsolete packet block has a 16 bit interface id followed by a drop count
+                let interface = if block_type == PCAPNG_ENHANCED_PACKET {
+                    endian.u32_at(body, 0)?
+                } else {
+                    endian.u16_at(body, 0)?.into()
+                };
+                let interface = interfaces
+                    .get(interface as usize)
+                    .ok_or(PcapError::Truncated)?;
+
+                let high = u64::from(endian.u32_at(body, 4)?);
+                let low = u64::from(endian.u32_at(body, 8)?);
+                let captured_length = endian.u32_at(body, 12)? as usize;
+
+                let data_offset = 20;
+                let data = body
+                    .get(data_offset..data_offset + captured_length)
+                    .ok_or(PcapError::Truncated)?;
+
+                records.push(Record {
+                    timestamp: timestamp((high << 32) | low, interface.units_per_second),
+                    link_type: interface.link_type,
+                    data,
+                });
+            }
+            PCAPNG_SIMPLE_PACKET => {
+                let interface = interfaces.first().ok_or(PcapError::Truncated)?;
+                let original_length = endian.u32_at(body, 0)? as usize;
+                let data = &body[4..];
+
+                records.push(Record {
+                    // Simple packets do not have timestamps
+                    timestamp: Duration::default(),
+                    link_type: interface.link_type,
+                    data: &data[..original_length.min(data.len())],
+                });
+            }
+            _ => {}
+        }
+
+        rest = &rest[length..];
+    }
+
+    Ok(records)
+}
+
+fn read_interface(body: &[u8], endian: Endian) -> Result<Interface, PcapError> {
+    let link_type = endian.u16_at(body, 0)?.into();
+    let mut units_per_second = 1_000_000;
+
+    let mut options = body.get(8..).unwrap_or_default();
+    while options.len() >= 4 {
+        let code = endian.u16_at(options, 0)?;
+        let length = endian.u16_at(options, 2)? as usize;
+        let value = options.get(4..4 + length).ok_or(PcapError::Truncated)?;
+
+        match code {
+            PCAPNG_OPTION_END => break,
+            PCAPNG_OPTION_TIMESTAMP_RESOLUTION => {
+                let resolution = *value.first().ok_or(PcapError::Truncated)?;
+                let exponent = u32::from(resolution & 0x7F);
+
+                units_per_second = if resolution & 0x80 == 0 {
+                    10_u64.checked_pow(exponent)
+                } else {
+                    2_u64.checked_pow(exponent)
+                }
+                .ok_or(PcapError::Truncated)?;
+            }
+            _ => {}
+        }
+
+        let padded_length = (length + 3) & !3;
+        options = options.get(4 + padded_length..).unwrap_or_default();
+    }
+
+    Ok(Interface {
+        link_type,
+        units_per_second,
+    })
+}
+
+fn timestamp(units: u64, units_per_second: u64) -> Duration {
+    let seconds = units / units_per_second;
+    let remainder = u128::from(units % units_per_second);
+    let nanoseconds = remainder * 1_000_000_000 / u128::from(units_per_second);
+
+    Duration::from_secs(seconds) + Duration::from_nanos(nanoseconds as u64)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Endian {
+    big_endian: bool,
+}
+
+impl Endian {
+    const fn u32(self, bytes: [u8; 4]) -> u32 {
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32_at(self, data: &[u8], offset: usize) -> Result<u32, PcapError> {
+        let bytes = data.get(offset..offset + 4).ok_or(PcapError::Truncated)?;
+
+        Ok(self.u32(bytes.try_into().unwrap()))
+    }
+
+    fn u16_at(self, data: &[u8], offset: usize) -> Result<u16, PcapError> {
+        let bytes = data.get(offset..offset + 2).ok_or(PcapError::Truncated)?;
+        let bytes = [bytes[0], bytes[1]];
+
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+}
@@ -0,0 +1,468 @@
+//! Functionality for decrypting world connections in packet captures.
+//!
+//! Given a pcap or pcapng capture of a world connection and the session key,
+//! [`decrypt_capture`] reconstructs both TCP directions and decrypts every header
+//! with the crypto of the chosen [`Expansion`].
+//!
+//! The unencrypted [`SMSG_AUTH_CHALLENGE`] and [`CMSG_AUTH_SESSION`] messages must be part
+//! of the capture since every header after them is encrypted.
+//! Only the first TCP connection to the server port is decrypted, and incomplete
+//! messages at the end of the capture are ignored.
+//!
+//! Captures using Ethernet, Linux cooked (`tcpdump -i any`), BSD loopback, and raw IP
+//! link types are supported.
+//!
+//! The `wow_pcap_decrypt` binary prints the messages from a capture file.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use wow_srp::pcap::{decrypt_capture, Direction, Expansion};
+//! use wow_srp::SESSION_KEY_LENGTH;
+//!
+//! fn print_opcodes(session_key: [u8; SESSION_KEY_LENGTH as usize]) {
+//!     let capture = std::fs::read("world.pcapng").unwrap();
+//!     let packets = decrypt_capture(&capture, 8085, Expansion::Wrath, session_key).unwrap();
+//!
+//!     for packet in packets {
+//!         match packet.direction {
+//!             Direction::ClientToServer => println!("CMSG {:#06x}", packet.opcode),
+//!             Direction::ServerToClient => println!("SMSG {:#06x}", packet.opcode),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
+//! [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+
+use crate::error::{FrameError, PcapError};
+use crate::hex::{hex_encode, try_hex_decode};
+use crate::pcap::tcp::Stream;
+use crate::vanilla_header::{CLIENT_HEADER_LENGTH, SERVER_HEADER_LENGTH};
+pub use crate::Expansion;
+use crate::{tbc_header, vanilla_header, wrath_header, SESSION_KEY_LENGTH};
+use std::convert::TryInto;
+use std::time::Duration;
+
+mod capture;
+mod tcp;
+
+/// Opcode of the unencrypted message the server starts the connection with.
+const SMSG_AUTH_CHALLENGE: u32 = 0x1EC;
+/// Opcode of the unencrypted message the client replies with.
+const CMSG_AUTH_SESSION: u32 = 0x1ED;
+
+/// Largest body that will be decrypted.
+const MAXIMUM_BODY_SIZE: u32 = wrath_header::SERVER_BODY_MAXIMUM_LENGTH;
+
+/// Sender of a message.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Direction {
+    /// Sent by the client.
+    ClientToServer,
+    /// Sent by the server.
+    ServerToClient,
+}
+
+/// A single decrypted message.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct WorldPacket {
+    /// Sender of the message.
+    pub direction: Direction,
+    /// Time since the Unix epoch of the TCP segment that completed the message.
+    ///
+    /// For segments that arrived out of order this is the segment that filled the gap.
+    pub timestamp: Duration,
+    /// Opcode of the message. Server opcodes always fit in a [`u16`].
+    pub opcode: u32,
+    /// Body of the message without the header.
+    pub body: Vec<u8>,
+}
+
+impl WorldPacket {
+    /// Lowercase hex of [`body`](WorldPacket::body) with two digits per byte.
+    #[must_use]
+    pub fn body_hex(&self) -> String {
+        hex_encode(&self.body)
+    }
+}
+
+/// Parses a session key written as hex in the same byte order as used by the crate.
+///
+/// Surrounding whitespace is ignored.
+/// Returns [`None`] if the string is not exactly [`SESSION_KEY_LENGTH`] bytes of hex.
+#[must_use]
+pub fn session_key_from_hex(s: &str) -> Option<[u8; SESSION_KEY_LENGTH as usize]> {
+    try_hex_decode(s.trim()).ok()?.try_into().ok()
+}
+
+/// Decrypts every message of the first connection to `server_port` in a pcap or pcapng capture.
+///
+/// Messages are returned in the order they were captured.
+///
+/// # Errors
+///
+/// If the capture can not be parsed, if there is no connection to `server_port`,
+/// or if the connection does not start with the unencrypted handshake.
+/// A header that does not decrypt to a valid size usually means that the session key is wrong.
+pub fn decrypt_capture(
+    capture: &[u8],
+    server_port: u16,
+    expansion: Expansion,
+    session_key: [u8; SESSION_KEY_LENGTH as usize],
+) -> Result<Vec<WorldPacket>, PcapError> {
+    let records = capture::read_records(capture)?;
+    let (client_to_server, server_to_client) = tcp::reassemble(&records, server_port)?;
+
+    let mut packets = decrypt_stream(
+        &client_to_server,
+        Direction::ClientToServer,
+        expansion,
+        session_key,
+    )?;
+    packets.extend(decrypt_stream(
+        &server_to_client,
+        Direction::ServerToClient,
+        expansion,
+        session_key,
+    )?);
+
+    // Stable sort keeps the order of messages completed by the same segment
+    packets.sort_by_key(|packet| packet.timestamp);
+
+    Ok(packets)
+}
+
+fn decrypt_stream(
+    stream: &Stream,
+    direction: Direction,
+    expansion: Expansion,
+    session_key: [u8; SESSION_KEY_LENGTH as usize],
+) -> Result<Vec<WorldPacket>, PcapError> {
+    let mut packets = Vec::new();
+    let mut handshake = Vec::new();
+    let mut framer: Option<Framer> = None;
+
+    for (timestamp, chunk) in &stream.chunks {
+        let framer = match &mut framer {
+            Some(framer) => {
+                framer.push(chunk);
+                framer
+            }
+            None => {
+                handshake.extend_from_slice(chunk);
+                let (opcode, body, length) = match read_handshake(&handshake, direction) {
+                    Some(handshake) => handshake,
+                    None => continue,
+                };
+
+                let expected = match direction {
+                    Direction::ClientToServer => CMSG_AUTH_SESSION,
+                    Direction::ServerToClient => SMSG_AUTH_CHALLENGE,
+                };
+                if opcode != expected {
+                    return Err(PcapError::MissingHandshake { direction, opcode });
+                }
+
+                packets.push(WorldPacket {
+                    direction,
+                    timestamp: *timestamp,
+                    opcode,
+                    body: body.to_vec(),
+                });
+
+                let new_framer = framer.insert(Framer::new(direction, expansion, session_key));
+                new_framer.push(&handshake[length..]);
+                new_framer
+            }
+        };
+
+        while let Some((opcode, body)) = framer
+            .next_frame()
+            .map_err(|error| PcapError::InvalidHeader { direction, error })?
+        {
+            packets.push(WorldPacket {
+                direction,
+                timestamp: *timestamp,
+                opcode,
+                body,
+            });
+        }
+    }
+
+    Ok(packets)
+}
+
+/// Returns the opcode, body, and total length of the unencrypted first message.
+fn read_handshake(data: &[u8], direction: Direction) -> Option<(u32, &[u8], usize)> {
+    let size = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+
+    let (opcode, header_length) = match direction {
+        Direction::ClientToServer => (
+            u32::from_le_bytes([*data.get(2)?, *data.get(3)?, *data.get(4)?, *data.get(5)?]),
+            CLIENT_HEADER_LENGTH,
+        ),
+        Direction::ServerToClient => (
+            u16::from_le_bytes([*data.get(2)?, *data.get(3)?]).into(),
+            SERVER_HEADER_LENGTH,
+        ),
+    };
+
+    // The size field includes the opcode, but a corrupt size could be smaller than it
+    let header_length = usize::from(header_length);
+    let length = (usize::from(size) + 2).max(header_length);
+    let body = data.get(header_length..length)?;
+
+    Some((opcode, body, length))
+}
+
+#[allow(clippy::large_enum_variant)] // Only created once per capture
+enum Framer {
+    VanillaServer(vanilla_header::ServerFramer),
+    VanillaClient(vanilla_header::ClientFramer),
+    TbcServer(tbc_header::ServerFramer),
+    TbcClient(tbc_header::ClientFramer),
+    WrathServer(wrath_header::ServerFramer),
+    WrathClient(wrath_header::ClientFramer),
+}
+
+impl Framer {
+    /// Client headers are decrypted by the server and server headers by the client.
+    fn new(
+        direction: Direction,
+        expansion: Expansion,
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+    ) -> Self {
+        match (expansion, direction) {
+            (Expansion::Vanilla, Direction::ClientToServer) => {
+                let (_, decrypter) = vanilla_header::HeaderCrypto::new(session_key).split();
+                Self::VanillaServer(vanilla_header::ServerFramer::new(
+                    decrypter,
+                    MAXIMUM_BODY_SIZE,
+                ))
+            }
+            (Expansion::Vanilla, Direction::ServerToClient) => {
+                let (_, decrypter) = vanilla_header::HeaderCrypto::new(session_key).split();
+                Self::VanillaClient(vanilla_header::ClientFramer::new(
+                    decrypter,
+                    MAXIMUM_BODY_SIZE,
+                ))
+            }
+            (Expansion::Tbc, Direction::ClientToServer) => {
+                let (_, decrypter) = tbc_header::HeaderCrypto::new(session_key).split();
+                Self::TbcServer(tbc_header::ServerFramer::new(decrypter, MAXIMUM_BODY_SIZE))
+            }
+            (Expansion::Tbc, Direction::ServerToClient) => {
+                let (_, decrypter) = tbc_header::HeaderCrypto::new(session_key).split();
+                Self::TbcClient(tbc_header::ClientFramer::new(decrypter, MAXIMUM_BODY_SIZE))
+            }
+            (Expansion::Wrath, Direction::ClientToServer) => {
+                let (_, decrypter) = wrath_header::ServerCrypto::new(session_key).split();
+                Self::WrathServer(wrath_header::ServerFramer::new(
+                    decrypter,
+                    MAXIMUM_BODY_SIZE,
+                ))
+            }
+            (Expansion::Wrath, Direction::ServerToClient) => {
+                let (_, decrypter) = wrath_header::ClientCrypto::new(session_key).split();
+                Self::WrathClient(wrath_header::ClientFramer::new(
+                    decrypter,
+                    MAXIMUM_BODY_SIZE,
+                ))
+            }
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        match self {
+            Self::VanillaServer(f) => f.push(data),
+            Self::VanillaClient(f) => f.push(data),
+            Self::TbcServer(f) => f.push(data),
+            Self::TbcClient(f) => f.push(data),
+            Self::WrathServer(f) => f.push(data),
+            Self::WrathClient(f) => f.push(data),
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<(u32, Vec<u8>)>, FrameError> {
+        fn widen(frame: Option<(u16, Vec<u8>)>) -> Option<(u32, Vec<u8>)> {
+            frame.map(|(opcode, body)| (opcode.into(), body))
+        }
+
+        Ok(match self {
+            Self::VanillaServer(f) => f.next_frame()?,
+            Self::VanillaClient(f) => widen(f.next_frame()?),
+            Self::TbcServer(f) => f.next_frame()?,
+            Self::TbcClient(f) => widen(f.next_frame()?),
+            Self::WrathServer(f) => f.next_frame()?,
+            Self::WrathClient(f) => widen(f.next_frame()?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{FrameError, PcapError};
+    use crate::pcap::{decrypt_capture, session_key_from_hex, Direction, Expansion, WorldPacket};
+    use crate::SESSION_KEY_LENGTH;
+    use std::fs::{read, read_to_string};
+    use std::time::Duration;
+
+    const SERVER_PORT: u16 = 8085;
+
+    fn read_session_key(path: &str) -> [u8; SESSION_KEY_LENGTH as usize] {
+        session_key_from_hex(&read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn summary(packets: &[WorldPacket]) -> Vec<(Direction, u32, String)> {
+        packets
+            .iter()
+            .map(|p| (p.direction, p.opcode, p.body_hex()))
+            .collect()
+    }
+
+    #[test]
+    fn verify_wrath_capture() {
+        // Loopback capture where the client sends the second half of a segment before
+        // the first half and then retransmits both halves.
+        let capture = read("tests/pcap/wrath_world.pcap").unwrap();
+        let session_key = read_session_key("tests/pcap/wrath_world_session_key.txt");
+
+        let packets =
+            decrypt_capture(&capture, SERVER_PORT, Expansion::Wrath, session_key).unwrap();
+
+        let mut summary = summary(&packets);
+        // Realm list with a 5 byte header
+        let (direction, opcode, body) = summary.pop().unwrap();
+        assert_eq!((direction, opcode), (Direction::ServerToClient, 0xA9));
+        assert_eq!(body.len(), 0x9000 * 2);
+
+        // Random challenge and client proof
+        assert!(summary[0].2.starts_with("01000000a3acf372"));
+        assert!(summary[1].2.starts_with("34300000"));
+        summary[0].2.clear();
+        summary[1].2.clear();
+
+        let expected = [
+            (Direction::ServerToClient, 0x1EC, ""),
+            (Direction::ClientToServer, 0x1ED, ""),
+            (Direction::ServerToClient, 0x1EE, "0c00000000000000000002"),
+            (Direction::ClientToServer, 0x37, ""),
+            (Direction::ClientToServer, 0x1DC, "010000002a000000"),
+            (Direction::ServerToClient, 0x3B, "00"),
+            (Direction::ServerToClient, 0x1DD, "01000000"),
+        ];
+        assert_eq!(
+            summary,
+            expected
+                .iter()
+                .map(|(d, o, b)| (*d, *o, b.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        // Both messages are completed by the segment that fills the gap
+        assert_eq!(packets[3].timestamp, packets[4].timestamp);
+        assert!(packets.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+
+    #[test]
+    fn verify_vanilla_pcapng() {
+        let capture = read("tests/pcap/vanilla_world.pcapng").unwrap();
+        let session_key = read_session_key("tests/pcap/vanilla_world_session_key.txt");
+
+        let packets =
+            decrypt_capture(&capture, SERVER_PORT, Expansion::Vanilla, session_key).unwrap();
+
+        let mut summary = summary(&packets);
+        // Random client proof
+        assert!(summary[1].2.starts_with("f3160000"));
+        summary[1].2.clear();
+
+        let expected = [
+            (Direction::ServerToClient, 0x1EC, "90994c11"),
+            (Direction::ClientToServer, 0x1ED, ""),
+            (Direction::ServerToClient, 0x1EE, "0c"),
+            (Direction::ClientToServer, 0x37, ""),
+            (Direction::ClientToServer, 0x1DC, "0200000010000000"),
+            (Direction::ServerToClient, 0x3B, "00"),
+            (Direction::ServerToClient, 0x1DD, "02000000"),
+        ];
+        assert_eq!(
+            summary,
+            expected
+                .iter()
+                .map(|(d, o, b)| (*d, *o, b.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        // Nanosecond if_tsresol
+        assert_eq!(
+            packets[0].timestamp,
+            Duration::new(1_792_361_196, 156_867_992)
+        );
+    }
+
+    #[test]
+    fn verify_errors() {
+        let capture = read("tests/pcap/vanilla_world.pcapng").unwrap();
+        let session_key = read_session_key("tests/pcap/vanilla_world_session_key.txt");
+
+        assert!(matches!(
+            decrypt_capture(&[1, 2, 3, 4], SERVER_PORT, Expansion::Vanilla, session_key),
+            Err(PcapError::UnknownFormat)
+        ));
+        assert!(matches!(
+            decrypt_capture(
+                &capture[..capture.len() - 1],
+                SERVER_PORT,
+                Expansion::Vanilla,
+                session_key
+            ),
+            Err(PcapError::Truncated)
+        ));
+        assert!(matches!(
+            decrypt_capture(&capture, 1234, Expansion::Vanilla, session_key),
+            Err(PcapError::NoConnection { server_port: 1234 })
+        ));
+
+        // Corrupts the encrypted size of the first client header after the handshake
+        let header = capture
+            .windows(6)
+            .position(|w| w == [0x14, 0xD7, 0x4C, 0x33, 0x8A, 0xAC])
+            .unwrap();
+        let mut corrupted = capture.clone();
+        corrupted[header + 1] = 0xDB;
+        assert!(matches!(
+            decrypt_capture(&corrupted, SERVER_PORT, Expansion::Vanilla, session_key),
+            Err(PcapError::InvalidHeader {
+                direction: Direction::ClientToServer,
+                error: FrameError::SizeSmallerThanOpcode { size: 0 }
+            })
+        ));
+
+        // Using the port of the client swaps the directions,
+        // so the challenge from the server is read as the client handshake.
+        let capture = read("tests/pcap/wrath_world.pcap").unwrap();
+        let session_key = read_session_key("tests/pcap/wrath_world_session_key.txt");
+        assert!(matches!(
+            decrypt_capture(&capture, 56318, Expansion::Wrath, session_key),
+            Err(PcapError::MissingHandshake {
+                direction: Direction::ClientToServer,
+                opcode: 0x101EC
+            })
+        ));
+    }
+
+    #[test]
+    fn verify_session_key_from_hex() {
+        let key = read_to_string("tests/pcap/vanilla_world_session_key.txt").unwrap();
+        let parsed = session_key_from_hex(&key).unwrap();
+        assert_eq!(session_key_from_hex(&key.to_uppercase()), Some(parsed));
+
+        let key = key.trim();
+        assert_eq!(session_key_from_hex(&key[2..]), None);
+        assert_eq!(session_key_from_hex(&format!("{}00", key)), None);
+        assert_eq!(session_key_from_hex(&format!("+{}", &key[1..])), None);
+    }
+}
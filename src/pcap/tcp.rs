@@ -0,0 +1,258 @@
+use crate::error::PcapError;
+use crate::pcap::capture::Record;
+use crate::pcap::Direction;
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+const LINK_TYPE_NULL: u32 = 0;
+const LINK_TYPE_ETHERNET: u32 = 1;
+const LINK_TYPE_RAW: u32 = 101;
+const LINK_TYPE_LINUX_SLL: u32 = 113;
+const LINK_TYPE_IPV4: u32 = 228;
+const LINK_TYPE_IPV6: u32 = 229;
+const LINK_TYPE_LINUX_SLL2: u32 = 276;
+
+const ETHER_TYPE_IPV4: u16 = 0x0800;
+const ETHER_TYPE_IPV6: u16 = 0x86DD;
+const ETHER_TYPE_VLAN: u16 = 0x8100;
+
+const IP_PROTOCOL_TCP: u8 = 6;
+
+const TCP_FLAG_SYN: u8 = 0x02;
+
+/// Source or destination of a TCP segment.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Endpoint {
+    address: IpAddr,
+    port: u16,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Segment<'a> {
+    source: Endpoint,
+    destination: Endpoint,
+    sequence: u32,
+    syn: bool,
+    payload: &'a [u8],
+}
+
+/// In order data of a single TCP direction.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Stream {
+    /// Contiguous chunks of data and the timestamp of the segment that made them contiguous.
+    pub chunks: Vec<(Duration, Vec<u8>)>,
+    next_sequence: Option<u32>,
+    /// Segments that arrived before the data in front of them.
+    out_of_order: Vec<(u32, Vec<u8>)>,
+}
+
+impl Stream {
+    fn add(&mut self, timestamp: Duration, segment: &Segment) {
+        let next_sequence = match self.next_sequence {
+            Some(next_sequence) => next_sequence,
+            None if segment.syn => segment.sequence.wrapping_add(1),
+            None => segment.sequence,
+        };
+        self.next_sequence = Some(next_sequence);
+
+        if segment.payload.is_empty() {
+            return;
+        }
+
+        let sequence = if segment.syn {
+            segment.sequence.wrapping_add(1)
+        } else {
+            segment.sequence
+        };
+        self.out_of_order.push((sequence, segment.payload.to_vec()));
+
+        self.flush(timestamp);
+    }
+
+    /// Moves every segment that is now in order into `chunks`.
+    fn flush(&mut self, timestamp: Duration) {
+        loop {
+            let next_sequence = match self.next_sequence {
+                Some(next_sequence) => next_sequence,
+                None => return,
+            };
+
+            // Segments that start before the next sequence are retransmissions,
+            // which may still contain new data at the end.
+            let position = self
+                .out_of_order
+                .iter()
+                .position(|(sequence, _)| (next_sequence.wrapping_sub(*sequence) as i32) >= 0);
+            let (sequence, payload) = match position {
+                Some(position) => self.out_of_order.swap_remove(position),
+                None => return,
+            };
+
+            let already_received = next_sequence.wrapping_sub(sequence) as usize;
+            if let Some(new) = payload.get(already_received..) {
+                if !new.is_empty() {
+                    self.next_sequence = Some(next_sequence.wrapping_add(new.len() as u32));
+                    self.chunks.push((timestamp, new.to_vec()));
+                }
+            }
+        }
+    }
+}
+
+/// Reassembles both directions of the first TCP connection to `server_port`.
+pub(crate) fn reassemble(
+    records: &[Record],
+    server_port: u16,
+) -> Result<(Stream, Stream), PcapError> {
+    let mut client_to_server = Stream::default();
+    let mut server_to_client = Stream::default();
+    let mut connection = None;
+
+    for record in records {
+        let segment = match parse_segment(record)? {
+            Some(segment) => segment,
+            None => continue,
+        };
+
+        let (direction, client, server) = if segment.destination.port == server_port {
+            (
+                Direction::ClientToServer,
+                segment.source,
+                segment.destination,
+            )
+        } else if segment.source.port == server_port {
+            (
+                Direction::ServerToClient,
+                segment.destination,
+                segment.source,
+            )
+        } else {
+            continue;
+        };
+
+        // Only the first connection is used
+        if *connection.get_or_insert((client, server)) != (client, server) {
+            continue;
+        }
+
+        match direction {
+            Direction::ClientToServer => client_to_server.add(record.timestamp, &segment),
+            Direction::ServerToClient => server_to_client.add(record.timestamp, &segment),
+        }
+    }
+
+    if connection.is_none() {
+        return Err(PcapError::NoConnection { server_port });
+    }
+
+    Ok((client_to_server, server_to_client))
+}
+
+/// Returns [`None`] for anything that is not a TCP segment.
+fn parse_segment<'a>(record: &Record<'a>) -> Result<Option<Segment<'a>>, PcapError> {
+    let data = record.data;
+
+    let ip = match record.link_type {
+        LINK_TYPE_NULL => data.get(4..),
+        LINK_TYPE_RAW | LINK_TYPE_IPV4 | LINK_TYPE_IPV6 => Some(data),
+        LINK_TYPE_ETHERNET => ethernet_payload(data),
+        LINK_TYPE_LINUX_SLL => data
+            .get(14..16)
+            .filter(|ether_type| is_ip(ether_type))
+            .and_then(|_| data.get(16..)),
+        LINK_TYPE_LINUX_SLL2 => data
+            .get(0..2)
+            .filter(|ether_type| is_ip(ether_type))
+            .and_then(|_| data.get(20..)),
+        link_type => return Err(PcapError::UnsupportedLinkType { link_type }),
+    };
+
+    Ok(ip.and_then(parse_ip))
+}
+
+fn is_ip(ether_type: &[u8]) -> bool {
+    let ether_type = u16::from_be_bytes([ether_type[0], ether_type[1]]);
+    ether_type == ETHER_TYPE_IPV4 || ether_type == ETHER_TYPE_IPV6
+}
+
+fn ethernet_payload(data: &[u8]) -> Option<&[u8]> {
+    let mut ether_type = u16::from_be_bytes(data.get(12..14)?.try_into().ok()?);
+    let mut offset = 14;
+
+    if ether_type == ETHER_TYPE_VLAN {
+        ether_type = u16::from_be_bytes(data.get(16..18)?.try_into().ok()?);
+        offset += 4;
+    }
+
+    if ether_type != ETHER_TYPE_IPV4 && ether_type != ETHER_TYPE_IPV6 {
+        return None;
+    }
+
+    data.get(offset..)
+}
+
+fn parse_ip(data: &[u8]) -> Option<Segment<'_>> {
+    match data.first()? >> 4 {
+        4 => {
+            let header_length = usize::from(data[0] & 0x0F) * 4;
+            let total_length = usize::from(u16::from_be_bytes(data.get(2..4)?.try_into().ok()?));
+            if *data.get(9)? != IP_PROTOCOL_TCP {
+                return None;
+            }
+
+            let source: [u8; 4] = data.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = data.get(16..20)?.try_into().ok()?;
+
+            // Ethernet frames can be padded past the end of the IP packet
+            let tcp = data.get(header_length..total_length)?;
+            parse_tcp(
+                tcp,
+                Ipv4Addr::from(source).into(),
+                Ipv4Addr::from(destination).into(),
+            )
+        }
+        6 => {
+            const HEADER_LENGTH: usize = 40;
+
+            let payload_length = usize::from(u16::from_be_bytes(data.get(4..6)?.try_into().ok()?));
+            // Extension headers are not supported
+            if *data.get(6)? != IP_PROTOCOL_TCP {
+                return None;
+            }
+
+            let source: [u8; 16] = data.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = data.get(24..40)?.try_into().ok()?;
+
+            let tcp = data.get(HEADER_LENGTH..HEADER_LENGTH + payload_length)?;
+            parse_tcp(
+                tcp,
+                Ipv6Addr::from(source).into(),
+                Ipv6Addr::from(destination).into(),
+            )
+        }
+        _ => None,
+    }
+}
+
+fn parse_tcp(data: &[u8], source: IpAddr, destination: IpAddr) -> Option<Segment<'_>> {
+    let source_port = u16::from_be_bytes(data.get(0..2)?.try_into().ok()?);
+    let destination_port = u16::from_be_bytes(data.get(2..4)?.try_into().ok()?);
+    let sequence = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+    let header_length = usize::from(data.get(12)? >> 4) * 4;
+    let flags = *data.get(13)?;
+
+    Some(Segment {
+        source: Endpoint {
+            address: source,
+            port: source_port,
+        },
+        destination: Endpoint {
+            address: destination,
+            port: destination_port,
+        },
+        sequence,
+        syn: flags & TCP_FLAG_SYN != 0,
+        payload: data.get(header_length..)?,
+    })
+}
@@ -16,7 +16,7 @@ use crate::bigint;
 pub const LARGE_SAFE_PRIME_LENGTH: u8 = 32;
 
 /// Static large safe prime (`N`) value.
-/// The big endian version of [LARGE_SAFE_PRIME_LITTLE_ENDIAN].
+/// The big endian version of [`LARGE_SAFE_PRIME_LITTLE_ENDIAN`].
 /// This version should not be sent over the network and should generally not be used.
 ///
 /// Only here for completeness sake.
@@ -30,7 +30,7 @@ pub const LARGE_SAFE_PRIME_BIG_ENDIAN: [u8; LARGE_SAFE_PRIME_LENGTH as usize] =
 ];
 
 /// Static large safe prime (`N`) value.
-/// The little endian version of [LARGE_SAFE_PRIME_BIG_ENDIAN].
+/// The little endian version of [`LARGE_SAFE_PRIME_BIG_ENDIAN`].
 /// This is the version that should be sent over the network in the
 /// [CMD_AUTH_LOGON_CHALLENGE_Server](https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Server)
 /// packet.
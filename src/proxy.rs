@@ -0,0 +1,553 @@
+//! Functionality for proxying world connections between two different sessions.
+//!
+//! A proxy that sits between a real client and a server has two separate sessions,
+//! one with the client and one with the server, each with their own session key.
+//! Headers must be decrypted with the crypto of one session and encrypted with the crypto
+//! of the other, while bodies are passed through untouched.
+//!
+//! The sessions do not need to be for the same expansion, so a Wrath client can be proxied
+//! to a server that uses the Vanilla protocol.
+//! Server headers that are too large for the client are returned as [`ProxyError::SizeTooLarge`].
+//!
+//! The [`Proxy`] takes the crypto of both sessions as [`ClientFacing`] and [`ServerFacing`].
+//! It can be split into [`ClientToServer`] and [`ServerToClient`] in order to handle
+//! each direction independently.
+//! Data can be given in whatever chunks it arrives in from the sockets.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::proxy::{ClientFacing, Proxy, ServerFacing};
+//! use wow_srp::error::ProxyError;
+//! use wow_srp::vanilla_header;
+//!
+//! fn create_proxy(
+//!     client_crypto: vanilla_header::HeaderCrypto,
+//!     server_crypto: vanilla_header::HeaderCrypto,
+//! ) -> Proxy {
+//!     Proxy::new(
+//!         ClientFacing::Vanilla(client_crypto),
+//!         ServerFacing::Vanilla(server_crypto),
+//!     )
+//! }
+//!
+//! fn from_client(proxy: &mut Proxy, data_from_client: &[u8]) -> Result<Vec<u8>, ProxyError> {
+//!     let mut data_to_server = Vec::new();
+//!     proxy.client_to_server(data_from_client, &mut data_to_server)?;
+//!
+//!     Ok(data_to_server)
+//! }
+//! ```
+
+use crate::error::ProxyError;
+use crate::framing::{
+    decrypt_client_header_from_buffer, decrypt_server_header_from_buffer, DecryptedHeader,
+    CLIENT_OPCODE_LENGTH, SERVER_OPCODE_LENGTH,
+};
+#[cfg(feature = "tbc-header")]
+use crate::tbc_header;
+use crate::vanilla_header;
+#[cfg(feature = "wrath-header")]
+use crate::wrath_header;
+use std::convert::TryFrom;
+
+/// Crypto for the session between the real client and the proxy.
+///
+/// The proxy acts as the server in this session.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+pub enum ClientFacing {
+    /// Client uses the Vanilla protocol.
+    Vanilla(vanilla_header::HeaderCrypto),
+    /// Client uses the TBC protocol.
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::HeaderCrypto),
+    /// Client uses the Wrath protocol.
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ServerCrypto),
+}
+
+/// Crypto for the session between the proxy and the real server.
+///
+/// The proxy acts as the client in this session.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+pub enum ServerFacing {
+    /// Server uses the Vanilla protocol.
+    Vanilla(vanilla_header::HeaderCrypto),
+    /// Server uses the TBC protocol.
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::HeaderCrypto),
+    /// Server uses the Wrath protocol.
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ClientCrypto),
+}
+
+/// Re-encrypts data in both directions between two sessions.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Proxy {
+    client_to_server: ClientToServer,
+    server_to_client: ServerToClient,
+}
+
+impl Proxy {
+    /// Creates a new proxy from the crypto of both sessions.
+    #[must_use]
+    pub fn new(client: ClientFacing, server: ServerFacing) -> Self {
+        let (client_encrypter, client_decrypter) = match client {
+            ClientFacing::Vanilla(c) => {
+                let (e, d) = c.split();
+                (
+                    ServerHeaderEncrypter::Vanilla(e),
+                    ClientHeaderDecrypter::Vanilla(d),
+                )
+            }
+            #[cfg(feature = "tbc-header")]
+            ClientFacing::Tbc(c) => {
+                let (e, d) = c.split();
+                (ServerHeaderEncrypter::Tbc(e), ClientHeaderDecrypter::Tbc(d))
+            }
+            #[cfg(feature = "wrath-header")]
+            ClientFacing::Wrath(c) => {
+                let (e, d) = c.split();
+                (
+                    ServerHeaderEncrypter::Wrath(e),
+                    ClientHeaderDecrypter::Wrath(d),
+                )
+            }
+        };
+
+        let (server_encrypter, server_decrypter) = match server {
+            ServerFacing::Vanilla(c) => {
+                let (e, d) = c.split();
+                (
+                    ClientHeaderEncrypter::Vanilla(e),
+                    ServerHeaderDecrypter::Vanilla(d),
+                )
+            }
+            #[cfg(feature = "tbc-header")]
+            ServerFacing::Tbc(c) => {
+                let (e, d) = c.split();
+                (ClientHeaderEncrypter::Tbc(e), ServerHeaderDecrypter::Tbc(d))
+            }
+            #[cfg(feature = "wrath-header")]
+            ServerFacing::Wrath(c) => {
+                let (e, d) = c.split();
+                (
+                    ClientHeaderEncrypter::Wrath(e),
+                    ServerHeaderDecrypter::Wrath(d),
+                )
+            }
+        };
+
+        Self {
+            client_to_server: ClientToServer {
+                decrypter: client_decrypter,
+                encrypter: server_encrypter,
+                state: Transcoder::new(),
+            },
+            server_to_client: ServerToClient {
+                decrypter: server_decrypter,
+                encrypter: client_encrypter,
+                state: Transcoder::new(),
+            },
+        }
+    }
+
+    /// Convenience wrapper for [`ClientToServer::transcode`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ClientToServer::transcode`].
+    pub fn client_to_server(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), ProxyError> {
+        self.client_to_server.transcode(data, out)
+    }
+
+    /// Convenience wrapper for [`ServerToClient::transcode`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ServerToClient::transcode`].
+    pub fn server_to_client(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), ProxyError> {
+        self.server_to_client.transcode(data, out)
+    }
+
+    /// Split the [`Proxy`] into the two directions for use with split connections.
+    #[allow(clippy::missing_const_for_fn)] // Clippy does not consider `self` arg
+    #[must_use]
+    pub fn split(self) -> (ClientToServer, ServerToClient) {
+        (self.client_to_server, self.server_to_client)
+    }
+}
+
+/// Re-encrypts data sent from the real client to the real server.
+///
+/// Created through [`Proxy::split`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ClientToServer {
+    decrypter: ClientHeaderDecrypter,
+    encrypter: ClientHeaderEncrypter,
+    state: Transcoder,
+}
+
+impl ClientToServer {
+    /// Appends the re-encrypted version of `data` to `out`.
+    ///
+    /// Partial headers are kept until the rest of the header is received.
+    ///
+    /// # Errors
+    ///
+    /// If the size of a header is smaller than the opcode.
+    /// The connection should be dropped when this happens.
+    pub fn transcode(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), ProxyError> {
+        let decrypter = &mut self.decrypter;
+        let encrypter = &mut self.encrypter;
+
+        self.state
+            .transcode(data, out, CLIENT_OPCODE_LENGTH, |buffer, out| {
+                let header = match decrypter.decrypt(buffer) {
+                    Some(header) => header,
+                    None => return Ok(None),
+                };
+
+                // Client headers have a 2 byte size field for all expansions
+                encrypter.encrypt(out, header.size as u16, header.opcode);
+
+                Ok(Some(header))
+            })
+    }
+}
+
+/// Re-encrypts data sent from the real server to the real client.
+///
+/// Created through [`Proxy::split`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ServerToClient {
+    decrypter: ServerHeaderDecrypter,
+    encrypter: ServerHeaderEncrypter,
+    state: Transcoder,
+}
+
+impl ServerToClient {
+    /// Appends the re-encrypted version of `data` to `out`.
+    ///
+    /// Partial headers are kept until the rest of the header is received.
+    ///
+    /// # Errors
+    ///
+    /// If the size of a header is smaller than the opcode, or if the size
+    /// can not be represented in the header of the client.
+    /// The connection should be dropped when this happens.
+    pub fn transcode(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), ProxyError> {
+        let decrypter = &mut self.decrypter;
+        let encrypter = &mut self.encrypter;
+
+        self.state
+            .transcode(data, out, SERVER_OPCODE_LENGTH, |buffer, out| {
+                let header = match decrypter.decrypt(buffer) {
+                    Some(header) => header,
+                    None => return Ok(None),
+                };
+
+                encrypter.encrypt(out, header.size, header.opcode)?;
+
+                Ok(Some(header))
+            })
+    }
+}
+
+/// Tracks where in the stream the next header is.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+struct Transcoder {
+    /// Bytes of a header that has not been fully received.
+    header: Vec<u8>,
+    /// Bytes of the current body that have not been passed through yet.
+    body_remaining: usize,
+}
+
+impl Transcoder {
+    /// Every header fits in this.
+    const MAXIMUM_HEADER_LENGTH: usize = vanilla_header::CLIENT_HEADER_LENGTH as usize;
+
+    const fn new() -> Self {
+        Self {
+            header: Vec::new(),
+            body_remaining: 0,
+        }
+    }
+
+    /// `reencrypt` must return [`None`] without advancing the decryption state
+    /// if the buffer does not contain a full header.
+    fn transcode(
+        &mut self,
+        mut data: &[u8],
+        out: &mut Vec<u8>,
+        opcode_length: u8,
+        mut reencrypt: impl FnMut(&[u8], &mut Vec<u8>) -> Result<Option<DecryptedHeader>, ProxyError>,
+    ) -> Result<(), ProxyError> {
+        while !data.is_empty() {
+            if self.body_remaining > 0 {
+                let length = self.body_remaining.min(data.len());
+                out.extend_from_slice(&data[..length]);
+
+                self.body_remaining -= length;
+                data = &data[length..];
+                continue;
+            }
+
+            let buffered = self.header.len();
+            let length = (Self::MAXIMUM_HEADER_LENGTH - buffered).min(data.len());
+            self.header.extend_from_slice(&data[..length]);
+
+            let header = match reencrypt(&self.header, out)? {
+                Some(header) => header,
+                None => {
+                    // The full header would have been available if there was more data
+                    debug_assert_eq!(length, data.len());
+                    return Ok(());
+                }
+            };
+
+            data = &data[header.length - buffered..];
+            self.header.clear();
+
+            self.body_remaining = header
+                .size
+                .checked_sub(opcode_length.into())
+                .ok_or(ProxyError::SizeSmallerThanOpcode { size: header.size })?
+                as usize;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+enum ClientHeaderDecrypter {
+    Vanilla(vanilla_header::DecrypterHalf),
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::DecrypterHalf),
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ServerDecrypterHalf),
+}
+
+impl ClientHeaderDecrypter {
+    fn decrypt(&mut self, data: &[u8]) -> Option<DecryptedHeader> {
+        decrypt_client_header_from_buffer(data, |header| match self {
+            Self::Vanilla(d) => {
+                let header = d.decrypt_client_header(header);
+                (header.size, header.opcode)
+            }
+            #[cfg(feature = "tbc-header")]
+            Self::Tbc(d) => {
+                let header = d.decrypt_client_header(header);
+                (header.size, header.opcode)
+            }
+            #[cfg(feature = "wrath-header")]
+            Self::Wrath(d) => {
+                let header = d.decrypt_client_header(header);
+                (header.size, header.opcode)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+enum ClientHeaderEncrypter {
+    Vanilla(vanilla_header::EncrypterHalf),
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::EncrypterHalf),
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ClientEncrypterHalf),
+}
+
+impl ClientHeaderEncrypter {
+    fn encrypt(&mut self, out: &mut Vec<u8>, size: u16, opcode: u32) {
+        let header = match self {
+            Self::Vanilla(e) => e.encrypt_client_header(size, opcode),
+            #[cfg(feature = "tbc-header")]
+            Self::Tbc(e) => e.encrypt_client_header(size, opcode),
+            #[cfg(feature = "wrath-header")]
+            Self::Wrath(e) => e.encrypt_client_header(size, opcode),
+        };
+
+        out.extend_from_slice(&header);
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+enum ServerHeaderDecrypter {
+    Vanilla(vanilla_header::DecrypterHalf),
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::DecrypterHalf),
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ClientDecrypterHalf),
+}
+
+impl ServerHeaderDecrypter {
+    fn decrypt(&mut self, data: &[u8]) -> Option<DecryptedHeader> {
+        match self {
+            Self::Vanilla(d) => decrypt_server_header_from_buffer(data, |header| {
+                let header = d.decrypt_server_header(header);
+                (header.size, header.opcode)
+            }),
+            #[cfg(feature = "tbc-header")]
+            Self::Tbc(d) => decrypt_server_header_from_buffer(data, |header| {
+                let header = d.decrypt_server_header(header);
+                (header.size, header.opcode)
+            }),
+            #[cfg(feature = "wrath-header")]
+            Self::Wrath(d) => {
+                let (header, length) = d.decrypt_server_header_from_buffer(data)?;
+
+                Some(DecryptedHeader {
+                    size: header.size,
+                    opcode: header.opcode.into(),
+                    length,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)] // Wrath crypto is large but only created once per connection
+enum ServerHeaderEncrypter {
+    Vanilla(vanilla_header::EncrypterHalf),
+    #[cfg(feature = "tbc-header")]
+    Tbc(tbc_header::EncrypterHalf),
+    #[cfg(feature = "wrath-header")]
+    Wrath(wrath_header::ServerEncrypterHalf),
+}
+
+impl ServerHeaderEncrypter {
+    /// Server opcodes are always 2 bytes.
+    fn encrypt(&mut self, out: &mut Vec<u8>, size: u32, opcode: u32) -> Result<(), ProxyError> {
+        let opcode = opcode as u16;
+        let small_size = || {
+            u16::try_from(size).map_err(|_| ProxyError::SizeTooLarge {
+                size,
+                maximum: u16::MAX.into(),
+            })
+        };
+
+        match self {
+            Self::Vanilla(e) => {
+                out.extend_from_slice(&e.encrypt_server_header(small_size()?, opcode))
+            }
+            #[cfg(feature = "tbc-header")]
+            Self::Tbc(e) => out.extend_from_slice(&e.encrypt_server_header(small_size()?, opcode)),
+            #[cfg(feature = "wrath-header")]
+            Self::Wrath(e) => out.extend_from_slice(e.encrypt_server_header(size, opcode)),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ProxyError;
+    use crate::proxy::{ClientFacing, Proxy, ServerFacing};
+    use crate::vanilla_header;
+    #[cfg(feature = "wrath-header")]
+    use crate::wrath_header;
+
+    const CLIENT_SESSION_KEY: [u8; 40] = [0x11; 40];
+    const SERVER_SESSION_KEY: [u8; 40] = [0x22; 40];
+
+    #[cfg(feature = "wrath-header")]
+    fn proxy() -> Proxy {
+        Proxy::new(
+            ClientFacing::Wrath(wrath_header::ServerCrypto::new(CLIENT_SESSION_KEY)),
+            ServerFacing::Vanilla(vanilla_header::HeaderCrypto::new(SERVER_SESSION_KEY)),
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "wrath-header")]
+    fn verify_wrath_client_to_vanilla_server() {
+        let (mut proxy_client_to_server, mut proxy_server_to_client) = proxy().split();
+
+        let mut client = wrath_header::ClientCrypto::new(CLIENT_SESSION_KEY);
+        let mut from_client = vec![0_u8; 64];
+        let mut length = client
+            .write_client_packet(&mut from_client, 0x1ED, &[&[1, 2, 3]])
+            .unwrap();
+        length += client
+            .write_client_packet(&mut from_client[length..], 0x37, &[])
+            .unwrap();
+        from_client.truncate(length);
+
+        let mut to_server = Vec::new();
+        for chunk in from_client.chunks(1) {
+            proxy_client_to_server
+                .transcode(chunk, &mut to_server)
+                .unwrap();
+        }
+
+        let (_, decrypter) = vanilla_header::HeaderCrypto::new(SERVER_SESSION_KEY).split();
+        let mut framer = vanilla_header::ServerFramer::new(decrypter, 16);
+        framer.push(&to_server);
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1ED, vec![1, 2, 3])));
+        assert_eq!(framer.next_frame().unwrap(), Some((0x37, vec![])));
+        assert_eq!(framer.buffered_len(), 0);
+
+        let mut server = vanilla_header::HeaderCrypto::new(SERVER_SESSION_KEY);
+        let mut from_server = vec![0_u8; 64];
+        let length = server
+            .write_server_packet(&mut from_server, 0x1EE, &[&[4; 10]])
+            .unwrap();
+        from_server.truncate(length);
+
+        let mut to_client = Vec::new();
+        for chunk in from_server.chunks(3) {
+            proxy_server_to_client
+                .transcode(chunk, &mut to_client)
+                .unwrap();
+        }
+
+        let (_, decrypter) = wrath_header::ClientCrypto::new(CLIENT_SESSION_KEY).split();
+        let mut framer = wrath_header::ClientFramer::new(decrypter, 16);
+        framer.push(&to_client);
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1EE, vec![4; 10])));
+        assert_eq!(framer.buffered_len(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "wrath-header")]
+    fn verify_size_too_large() {
+        let mut proxy = Proxy::new(
+            ClientFacing::Vanilla(vanilla_header::HeaderCrypto::new(CLIENT_SESSION_KEY)),
+            ServerFacing::Wrath(wrath_header::ClientCrypto::new(SERVER_SESSION_KEY)),
+        );
+
+        let mut server = wrath_header::ServerCrypto::new(SERVER_SESSION_KEY);
+        let header = server.encrypt_server_header(0x1_0000, 0x1EE).to_vec();
+        assert_eq!(
+            proxy.server_to_client(&header, &mut Vec::new()),
+            Err(ProxyError::SizeTooLarge {
+                size: 0x1_0000,
+                maximum: 0xFFFF
+            })
+        );
+    }
+
+    #[test]
+    fn verify_size_smaller_than_opcode() {
+        let mut proxy = Proxy::new(
+            ClientFacing::Vanilla(vanilla_header::HeaderCrypto::new(CLIENT_SESSION_KEY)),
+            ServerFacing::Vanilla(vanilla_header::HeaderCrypto::new(SERVER_SESSION_KEY)),
+        );
+
+        let mut client = vanilla_header::HeaderCrypto::new(CLIENT_SESSION_KEY);
+        let header = client.encrypt_client_header(2, 0x37);
+        assert_eq!(
+            proxy.client_to_server(&header, &mut Vec::new()),
+            Err(ProxyError::SizeSmallerThanOpcode { size: 2 })
+        );
+    }
+}
@@ -61,7 +61,7 @@
 //! * First an [`SrpVerifier`] is created using the database values,
 //! * Then [`SrpVerifier::into_proof`] is called to convert it into an [`SrpProof`],
 //! * Finally [`SrpProof::into_server`] is called to convert it into an [`SrpServer`] and a
-//! server proof.
+//!   server proof.
 //!
 //! The [`SrpServer`] means that the client has been correctly authenticated
 //! and can be sent the realmlist.
@@ -69,6 +69,13 @@
 //! The [`SrpServer`] does _NOT_ provide any rate limiting or time based expiration of the
 //! ability to reconnect, this means that the implementer will need to ensure that clients
 //! do not abuse the functionality.
+//! The [`SessionStore`] trait and [`InMemorySessionStore`] implementation store [`SrpServer`]s
+//! with an expiry time and a limit on failed reconnects.
+//! [`LoginPolicy`] throttles and suspends logins after failed proofs.
+//! The [`AccountStore`] trait with [`InMemoryAccountStore`] and `JsonLinesAccountStore`
+//! implementations loads and saves [`AccountRecord`]s.
+//! [`SrpVerifier::from_unknown_username`] creates a verifier for usernames that do not exist,
+//! so that they can not be told apart from real accounts until the proof step.
 //!
 //! The state machine goes like this:
 //! ```text
@@ -130,15 +137,41 @@
 //! ```
 //!
 
-use crate::error::MatchProofsError;
+use crate::error::{MatchProofsError, RngHealthError, SrpError};
 use crate::key::{
     PrivateKey, Proof, PublicKey, ReconnectData, Salt, SessionKey, PROOF_LENGTH, PUBLIC_KEY_LENGTH,
-    RECONNECT_CHALLENGE_DATA_LENGTH, SALT_LENGTH, SESSION_KEY_LENGTH,
+    RECONNECT_CHALLENGE_DATA_LENGTH, SALT_LENGTH, SESSION_KEY_LENGTH, SHA1_HASH_LENGTH,
 };
 use crate::key::{Verifier, PASSWORD_VERIFIER_LENGTH};
 use crate::normalized_string::NormalizedString;
+use crate::observer::{AuthEvent, AuthObserver, ObserverSlot};
+use crate::session_ticket::SessionTicket;
 use crate::srp_internal::calculate_reconnect_proof;
-use crate::{error::InvalidPublicKeyError, srp_internal};
+#[cfg(feature = "tbc-header")]
+use crate::tbc_header;
+use crate::trace::{enter_span, proof_mismatch, timed_math};
+#[cfg(feature = "wrath-header")]
+use crate::wrath_header;
+use crate::{error::InvalidPublicKeyError, srp_internal, vanilla_header, Expansion};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::sync::Arc;
+
+mod account_store;
+mod login_policy;
+mod password_change;
+#[cfg(feature = "sealed-verifier")]
+mod sealed_verifier;
+mod session_store;
+
+#[cfg(feature = "json-account-store")]
+pub use account_store::JsonLinesAccountStore;
+pub use account_store::{AccountRecord, AccountStore, BanStatus, InMemoryAccountStore};
+pub use login_policy::{LoginDecision, LoginPolicy, LOGIN_RESULT_SUSPENDED};
+pub use password_change::{PasswordChange, RehashPolicy, RehashZeroSalts};
+#[cfg(feature = "sealed-verifier")]
+pub use sealed_verifier::{SealedVerifier, SealingKey, SEALED_VERIFIER_LENGTH};
+pub use session_store::{InMemorySessionStore, ReconnectOutcome, SessionStore};
 
 /// Creates and contains the username, password verifier, and salt values.
 /// First step of the server, next is [`SrpProof`].
@@ -204,6 +237,7 @@ pub struct SrpVerifier {
     username: NormalizedString,
     password_verifier: Verifier,
     salt: Salt,
+    observer: ObserverSlot,
 }
 
 impl SrpVerifier {
@@ -255,6 +289,111 @@ impl SrpVerifier {
         Self::with_specific_salt(username, password, &salt)
     }
 
+    /// Same as [`SrpVerifier::from_username_and_password`] but fails if the salt does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the salt fails them.
+    pub fn try_from_username_and_password(
+        username: NormalizedString,
+        password: NormalizedString,
+    ) -> Result<Self, RngHealthError> {
+        let salt = Salt::try_randomized()?;
+
+        Ok(Self::with_specific_salt(username, password, &salt))
+    }
+
+    /// Creates a verifier with a new random salt from a `sha_pass_hash`, which is
+    /// `SHA1(UPPER(username) ":" UPPER(password))`.
+    ///
+    /// Older account tables often only store this hash instead of a verifier and salt.
+    /// Since it is the intermediate value used when calculating the verifier, the verifier can
+    /// be created without knowing the password.
+    /// The result is the same as [`SrpVerifier::from_username_and_password`] with the same password.
+    ///
+    /// `sha_pass_hash` is in the byte order of the SHA-1 output, so a hex column should be decoded
+    /// from left to right without reversing.
+    /// The username of the hash must be the same as `username`, see
+    /// [`normalized_string`](`crate::normalized_string`) for the rules.
+    #[must_use]
+    pub fn from_username_and_sha_pass_hash(
+        username: NormalizedString,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+    ) -> Self {
+        let salt = Salt::randomized();
+
+        Self::from_username_and_sha_pass_hash_with_salt(
+            username,
+            sha_pass_hash,
+            *salt.as_le_bytes(),
+        )
+    }
+
+    /// Same as [`SrpVerifier::from_username_and_sha_pass_hash`] but fails if the salt does not
+    /// pass the [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the salt fails them.
+    pub fn try_from_username_and_sha_pass_hash(
+        username: NormalizedString,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+    ) -> Result<Self, RngHealthError> {
+        let salt = Salt::try_randomized()?;
+
+        Ok(Self::from_username_and_sha_pass_hash_with_salt(
+            username,
+            sha_pass_hash,
+            *salt.as_le_bytes(),
+        ))
+    }
+
+    /// Same as [`SrpVerifier::from_username_and_sha_pass_hash`] but keeps an existing salt,
+    /// for tables that also store a salt.
+    ///
+    /// The salt is **little endian**.
+    #[must_use]
+    pub fn from_username_and_sha_pass_hash_with_salt(
+        username: NormalizedString,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+        salt: [u8; SALT_LENGTH as usize],
+    ) -> Self {
+        let salt = Salt::from_le_bytes(salt);
+        let password_verifier =
+            srp_internal::calculate_password_verifier_from_credentials_hash(&sha_pass_hash, &salt);
+
+        Self::from_database_values(username, password_verifier, *salt.as_le_bytes())
+    }
+
+    /// Creates a verifier for a username that does not exist in the database.
+    ///
+    /// Replying with an error for unknown usernames, or with a random salt that changes
+    /// between requests, tells the client which usernames exist.
+    /// The salt and verifier created here are derived from the username and `server_secret`,
+    /// so they are the same every time for the same username.
+    /// The rest of the state machine is the same as for real accounts and
+    /// fails at [`SrpProof::into_server`] with a [`MatchProofsError`], no matter the password.
+    ///
+    /// `server_secret` should be random, at least 20 bytes, and kept the same between restarts.
+    /// If it changes, the salts of unknown usernames will change as well.
+    ///
+    /// The time taken is close to the time taken by [`SrpVerifier::from_database_values`]
+    /// and [`SrpVerifier::into_proof`] for real accounts, but the database lookup for
+    /// the missing account should take a similar amount of time as for a real account.
+    #[must_use]
+    pub fn from_unknown_username(username: NormalizedString, server_secret: &[u8]) -> Self {
+        let salt = derive_fake_value(server_secret, b"salt", &username);
+
+        let mut password_verifier = derive_fake_value(server_secret, b"verifier", &username);
+        // Little endian, so this clears the most significant bit which keeps the verifier
+        // below the large safe prime, and the lowest bit makes sure it is not zero.
+        password_verifier[PASSWORD_VERIFIER_LENGTH as usize - 1] &= 0x7F;
+        password_verifier[0] |= 1;
+
+        Self::from_database_values(username, password_verifier, salt)
+    }
+
     /// See [`normalized_string`](`crate::normalized_string`) for more information on the string format.
     /// Both arrays are **little endian**.
     #[must_use]
@@ -267,15 +406,25 @@ impl SrpVerifier {
             username,
             password_verifier: Verifier::from_le_bytes(password_verifier),
             salt: Salt::from_le_bytes(salt),
+            observer: ObserverSlot::NONE,
         }
     }
 
+    /// Attaches an [`AuthObserver`] that is carried to the [`SrpProof`] and [`SrpServer`].
+    ///
+    /// See the [`observer`](crate::observer) module.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn AuthObserver>) -> Self {
+        self.observer = ObserverSlot::new(observer);
+        self
+    }
+
     /// Converts to an [`SrpProof`], consuming the [`SrpVerifier`].
     ///
-    /// # Panics:
+    /// # Panics
     ///
     /// * Panics if the RNG returns an error. If RNG does not work the authentication server
-    /// should not continue functioning and therefore panics.
+    ///   should not continue functioning and therefore panics.
     /// * _Very_ rarely panic if the server generated public key is invalid.
     ///
     /// There are only two invalid states for the randomly generated server public key:
@@ -285,15 +434,35 @@ impl SrpVerifier {
     /// This is 2 out of `2^256` possible states. The chances of this occurring naturally are very slim.
     /// It is significantly more likely that the RNG of the system has been compromised in which case
     /// authentication is not possible.
+    /// [`rng_health`](crate::rng_health) can be used to detect a failing RNG before this happens.
     #[doc(alias = "M")]
     #[doc(alias = "M1")]
     #[doc(alias = "M2")]
     #[must_use]
     pub fn into_proof(self) -> SrpProof {
+        enter_span!("into_proof", username = %self.username);
+
         let server_private_key = PrivateKey::randomized();
 
-        Self::with_specific_private_key(self, server_private_key)
-            .expect("The generated public key was invalid. This is insanely unlikely and even if you only see this error once you should probably check that your random number generation has not been compromised in some way. See documentation for SrpVerifier. Please report this on Github at 'https://github.com/gtker/wow_srp'.")
+        Self::with_specific_private_key(self, server_private_key).expect(INVALID_PUBLIC_KEY)
+    }
+
+    /// Same as [`SrpVerifier::into_proof`] but fails if the server private key does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the private key fails them.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`SrpVerifier::into_proof`].
+    pub fn try_into_proof(self) -> Result<SrpProof, RngHealthError> {
+        enter_span!("into_proof", username = %self.username);
+
+        let server_private_key = PrivateKey::try_randomized()?;
+
+        Ok(Self::with_specific_private_key(self, server_private_key).expect(INVALID_PUBLIC_KEY))
     }
 
     fn with_specific_salt(
@@ -311,17 +480,24 @@ impl SrpVerifier {
         self,
         server_private_key: PrivateKey,
     ) -> Result<SrpProof, InvalidPublicKeyError> {
-        let server_public_key = srp_internal::calculate_server_public_key(
-            &self.password_verifier,
-            &server_private_key,
+        let server_public_key = timed_math!(
+            "server_public_key",
+            srp_internal::calculate_server_public_key(&self.password_verifier, &server_private_key)
         )?;
 
+        self.observer.emit(AuthEvent::ChallengeIssued {
+            username: self.username.as_ref(),
+            server_public_key: *server_public_key.as_le_bytes(),
+            salt: *self.salt.as_le_bytes(),
+        });
+
         Ok(SrpProof {
             username: self.username,
             server_public_key,
             salt: self.salt,
             server_private_key,
             password_verifier: self.password_verifier,
+            observer: self.observer,
         })
     }
 }
@@ -400,6 +576,8 @@ pub struct SrpProof {
     server_private_key: PrivateKey,
 
     password_verifier: Verifier,
+
+    observer: ObserverSlot,
 }
 
 impl SrpProof {
@@ -425,6 +603,43 @@ impl SrpProof {
         self.salt.as_le_bytes()
     }
 
+    /// Attaches an [`AuthObserver`] that is carried to the [`SrpServer`].
+    ///
+    /// See the [`observer`](crate::observer) module.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn AuthObserver>) -> Self {
+        self.observer = ObserverSlot::new(observer);
+        self
+    }
+
+    /// Creates a [`PublicKey`] from the client public key, see [`PublicKey::from_le_bytes`].
+    ///
+    /// This is the same as calling [`PublicKey::from_le_bytes`] directly, except that
+    /// the observer is notified of invalid keys.
+    ///
+    /// # Errors
+    ///
+    /// If the public key is invalid.
+    pub fn client_public_key(
+        &self,
+        client_public_key: [u8; PUBLIC_KEY_LENGTH as usize],
+    ) -> Result<PublicKey, InvalidPublicKeyError> {
+        PublicKey::from_le_bytes(client_public_key).map_err(|e| {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                username = %self.username,
+                client_public_key = %crate::trace::Hex(&client_public_key),
+                error = ?e,
+                "invalid client public key"
+            );
+            self.observer.emit(AuthEvent::InvalidPublicKey {
+                username: self.username.as_ref(),
+                client_public_key,
+            });
+            e
+        })
+    }
+
     /// Converts to an [`SrpServer`] and server proof by using the client supplied public key and proof,
     /// consuming the [`SrpProof`].
     ///
@@ -498,11 +713,44 @@ impl SrpProof {
         client_public_key: PublicKey,
         client_proof: [u8; PROOF_LENGTH as usize],
     ) -> Result<(SrpServer, [u8; PROOF_LENGTH as usize]), MatchProofsError> {
-        let session_key = srp_internal::calculate_session_key(
-            &client_public_key,
-            &self.server_public_key,
-            &self.password_verifier,
-            &self.server_private_key,
+        self.into_server_with(client_public_key, client_proof, || {
+            Ok(ReconnectData::randomized())
+        })
+    }
+
+    /// Same as [`SrpProof::into_server`] but fails if the reconnect challenge data does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// [`SrpError::ProofsDoNotMatch`] if the `client_proof` does not match the internal server proof,
+    /// or [`SrpError::RngHealth`] if the checks are enabled and the reconnect challenge data fails them.
+    pub fn try_into_server(
+        self,
+        client_public_key: PublicKey,
+        client_proof: [u8; PROOF_LENGTH as usize],
+    ) -> Result<(SrpServer, [u8; PROOF_LENGTH as usize]), SrpError> {
+        self.into_server_with(client_public_key, client_proof, || {
+            Ok(ReconnectData::try_randomized()?)
+        })
+    }
+
+    fn into_server_with<E: From<MatchProofsError>>(
+        self,
+        client_public_key: PublicKey,
+        client_proof: [u8; PROOF_LENGTH as usize],
+        reconnect_challenge_data: impl FnOnce() -> Result<ReconnectData, E>,
+    ) -> Result<(SrpServer, [u8; PROOF_LENGTH as usize]), E> {
+        enter_span!("into_server", username = %self.username);
+
+        let session_key = timed_math!(
+            "session_key",
+            srp_internal::calculate_session_key(
+                &client_public_key,
+                &self.server_public_key,
+                &self.password_verifier,
+                &self.server_private_key,
+            )
         );
 
         let server_calculated_proof = srp_internal::calculate_client_proof(
@@ -515,10 +763,17 @@ impl SrpProof {
 
         let client_calculated_proof = Proof::from_le_bytes(client_proof);
         if client_calculated_proof != server_calculated_proof {
+            proof_mismatch!(client_proof);
+            self.observer.emit(AuthEvent::ProofMismatch {
+                username: self.username.as_ref(),
+                client_public_key: *client_public_key.as_le_bytes(),
+            });
+
             return Err(MatchProofsError {
                 client_proof: *client_calculated_proof.as_le_bytes(),
                 server_proof: *server_calculated_proof.as_le_bytes(),
-            });
+            }
+            .into());
         }
 
         let server_proof = srp_internal::calculate_server_proof(
@@ -527,19 +782,52 @@ impl SrpProof {
             &session_key,
         );
 
-        let reconnect_challenge_data = ReconnectData::randomized();
+        let reconnect_challenge_data = reconnect_challenge_data()?;
+
+        self.observer.emit(AuthEvent::Success {
+            username: self.username.as_ref(),
+            client_public_key: *client_public_key.as_le_bytes(),
+        });
+        self.observer.emit(AuthEvent::ReconnectChallenge {
+            username: self.username.as_ref(),
+        });
 
         Ok((
             SrpServer {
                 username: self.username,
                 session_key,
                 reconnect_challenge_data,
+                observer: self.observer,
             },
             *server_proof.as_le_bytes(),
         ))
     }
 }
 
+const INVALID_PUBLIC_KEY: &str = "The generated public key was invalid. This is insanely unlikely and even if you only see this error once you should probably check that your random number generation has not been compromised in some way. See documentation for SrpVerifier. Please report this on Github at 'https://github.com/gtker/wow_srp'.";
+
+/// `HMAC-SHA1(secret, label | 0 | username | counter)` for two counters, truncated to 32 bytes.
+fn derive_fake_value(
+    server_secret: &[u8],
+    label: &[u8],
+    username: &NormalizedString,
+) -> [u8; SALT_LENGTH as usize] {
+    let mut value = [0_u8; SALT_LENGTH as usize];
+
+    for (counter, chunk) in value.chunks_mut(20).enumerate() {
+        let mut hmac: Hmac<Sha1> = Hmac::new_from_slice(server_secret).unwrap();
+        hmac.update(label);
+        hmac.update(&[0]);
+        hmac.update(username.as_ref().as_bytes());
+        hmac.update(&[counter as u8]);
+
+        let hmac = hmac.finalize().into_bytes();
+        chunk.copy_from_slice(&hmac[..chunk.len()]);
+    }
+
+    value
+}
+
 /// The final step of authentication. Contains the session key, and reconnect logic.
 ///
 /// This represents the final struct used in authentication, if this struct is constructed,
@@ -547,7 +835,8 @@ impl SrpProof {
 /// The session key is used internally in the struct for reconnection,
 /// and externally for decryption of packets.
 ///
-/// This struct should be saved in session storage to allow for clients to reconnect.
+/// This struct should be saved in session storage to allow for clients to reconnect,
+/// for example an [`InMemorySessionStore`].
 /// If a client disconnects and reconnects properly again, the old struct should be replaced with
 /// the new one because the session key will be different.
 ///
@@ -612,9 +901,26 @@ pub struct SrpServer {
     username: NormalizedString,
     session_key: SessionKey,
     reconnect_challenge_data: ReconnectData,
+    observer: ObserverSlot,
 }
 
 impl SrpServer {
+    /// Attaches an [`AuthObserver`], replacing the one carried from the [`SrpProof`].
+    ///
+    /// See the [`observer`](crate::observer) module.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn AuthObserver>) -> Self {
+        self.observer = ObserverSlot::new(observer);
+        self
+    }
+
+    /// The [`normalized_string`](`crate::normalized_string`) representation of the username,
+    /// see that for more details.
+    #[must_use]
+    pub fn username(&self) -> &str {
+        self.username.as_ref()
+    }
+
     /// Called `S` in [RFC2945](https://tools.ietf.org/html/rfc2945) and sometimes `K` or `key`
     /// in other literature.
     ///
@@ -630,6 +936,119 @@ impl SrpServer {
         self.session_key.as_le_bytes()
     }
 
+    /// Creates a [`SessionTicket`] for handing the session key to a world server.
+    ///
+    /// `issued_at` is seconds since the Unix epoch.
+    #[must_use]
+    pub fn session_ticket(
+        &self,
+        realm_id: u32,
+        expansion: Expansion,
+        issued_at: u64,
+    ) -> SessionTicket {
+        SessionTicket::new(
+            self.username.clone(),
+            *self.session_key.as_le_bytes(),
+            issued_at,
+            realm_id,
+            expansion,
+        )
+    }
+
+    /// Verifies the Vanilla world server authentication with the username and session key of
+    /// this session.
+    ///
+    /// `proof_seed` is the seed sent in [`SMSG_AUTH_CHALLENGE`], and `client_proof` and
+    /// `client_seed` are from [`CMSG_AUTH_SESSION`].
+    /// See [`ProofSeed::into_server_header_crypto`](vanilla_header::ProofSeed::into_server_header_crypto).
+    ///
+    /// # Errors
+    ///
+    /// If the `client_proof` does not match the server generated proof.
+    ///
+    /// [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
+    /// [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+    pub fn into_vanilla_server_crypto(
+        self,
+        proof_seed: vanilla_header::ProofSeed,
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<vanilla_header::HeaderCrypto, MatchProofsError> {
+        let crypto = proof_seed.into_server_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            client_proof,
+            client_seed,
+        );
+        self.observer.emit_world_proof(
+            self.username.as_ref(),
+            Expansion::Vanilla,
+            proof_seed.seed(),
+            client_seed,
+            crypto.is_ok(),
+        );
+
+        crypto
+    }
+
+    /// Same as [`SrpServer::into_vanilla_server_crypto`] but for TBC.
+    ///
+    /// # Errors
+    ///
+    /// If the `client_proof` does not match the server generated proof.
+    #[cfg(feature = "tbc-header")]
+    pub fn into_tbc_server_crypto(
+        self,
+        proof_seed: tbc_header::ProofSeed,
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<tbc_header::HeaderCrypto, MatchProofsError> {
+        let crypto = proof_seed.into_server_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            client_proof,
+            client_seed,
+        );
+        self.observer.emit_world_proof(
+            self.username.as_ref(),
+            Expansion::Tbc,
+            proof_seed.seed(),
+            client_seed,
+            crypto.is_ok(),
+        );
+
+        crypto
+    }
+
+    /// Same as [`SrpServer::into_vanilla_server_crypto`] but for Wrath.
+    ///
+    /// # Errors
+    ///
+    /// If the `client_proof` does not match the server generated proof.
+    #[cfg(feature = "wrath-header")]
+    pub fn into_wrath_server_crypto(
+        self,
+        proof_seed: wrath_header::ProofSeed,
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<wrath_header::ServerCrypto, MatchProofsError> {
+        let crypto = proof_seed.into_server_header_crypto(
+            &self.username,
+            *self.session_key.as_le_bytes(),
+            client_proof,
+            client_seed,
+        );
+        self.observer.emit_world_proof(
+            self.username.as_ref(),
+            Expansion::Wrath,
+            proof_seed.seed(),
+            client_seed,
+            crypto.is_ok(),
+        );
+
+        crypto
+    }
+
     /// Server data to be included in the reconnection challenge.
     ///
     /// Must be sent to the client as random data for the reconnect challenge.
@@ -669,6 +1088,47 @@ impl SrpServer {
         &mut self,
         client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
         client_proof: [u8; PROOF_LENGTH as usize],
+    ) -> bool {
+        enter_span!("verify_reconnection_attempt", username = %self.username);
+
+        let reconnect_verified = self.check_reconnection_proof(client_data, client_proof);
+
+        self.reconnect_challenge_data.randomize_data();
+        self.observer.emit(AuthEvent::ReconnectChallenge {
+            username: self.username.as_ref(),
+        });
+
+        reconnect_verified
+    }
+
+    /// Same as [`SrpServer::verify_reconnection_attempt`] but fails if the new reconnect challenge
+    /// data does not pass the [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the new challenge data fails them.
+    /// The challenge data is still replaced, but it must not be sent to the client.
+    pub fn try_verify_reconnection_attempt(
+        &mut self,
+        client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+    ) -> Result<bool, RngHealthError> {
+        enter_span!("verify_reconnection_attempt", username = %self.username);
+
+        let reconnect_verified = self.check_reconnection_proof(client_data, client_proof);
+
+        self.reconnect_challenge_data.try_randomize_data()?;
+        self.observer.emit(AuthEvent::ReconnectChallenge {
+            username: self.username.as_ref(),
+        });
+
+        Ok(reconnect_verified)
+    }
+
+    fn check_reconnection_proof(
+        &self,
+        client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
     ) -> bool {
         let server_proof = calculate_reconnect_proof(
             &self.username,
@@ -681,7 +1141,15 @@ impl SrpServer {
 
         let reconnect_verified = server_proof == client_proof;
 
-        self.reconnect_challenge_data.randomize_data();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(verified = reconnect_verified, "reconnection attempt");
+
+        let username = self.username.as_ref();
+        self.observer.emit(if reconnect_verified {
+            AuthEvent::ReconnectSuccess { username }
+        } else {
+            AuthEvent::ReconnectFailure { username }
+        });
 
         reconnect_verified
     }
@@ -690,10 +1158,14 @@ impl SrpServer {
 #[cfg(test)]
 mod test {
 
+    use crate::client::SrpClientUser;
+    use crate::error::SrpError;
     use crate::hex::*;
     use crate::key::{PrivateKey, Proof, PublicKey, Salt};
     use crate::normalized_string::NormalizedString;
     use crate::server::SrpVerifier;
+    use crate::{GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+    use std::convert::TryInto;
 
     #[test]
     fn verify_known_client_values() {
@@ -741,4 +1213,131 @@ mod test {
             Proof::from_be_hex_str("0e006885b6f27a1843043270d2c83c4e1a22780b")
         );
     }
+
+    #[test]
+    fn verify_unknown_username() {
+        use crate::client::SrpClientUser;
+        use crate::{PublicKey, GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+
+        let secret = b"server secret for unknown usernames";
+        let n = |s: &str| NormalizedString::new(s).unwrap();
+
+        let verifier = SrpVerifier::from_unknown_username(n("A"), secret);
+        assert_eq!(verifier, SrpVerifier::from_unknown_username(n("A"), secret));
+        assert_eq!(
+            hex_encode(verifier.salt()),
+            hex_encode(SrpVerifier::from_unknown_username(n("a"), secret).salt())
+        );
+
+        let other = SrpVerifier::from_unknown_username(n("B"), secret);
+        assert_ne!(verifier.salt(), other.salt());
+        assert_ne!(verifier.password_verifier(), other.password_verifier());
+
+        let other = SrpVerifier::from_unknown_username(n("A"), b"other secret");
+        assert_ne!(verifier.salt(), other.salt());
+        assert_eq!(verifier.password_verifier()[31] & 0x80, 0);
+
+        let proof = verifier.into_proof();
+        let client = SrpClientUser::new(n("A"), n("A")).into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*proof.server_public_key()).unwrap(),
+            *proof.salt(),
+        );
+        assert!(proof
+            .into_server(
+                PublicKey::from_le_bytes(*client.client_public_key()).unwrap(),
+                *client.client_proof(),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn verify_sha_pass_hash() {
+        let username = NormalizedString::new("Alice").unwrap();
+        let password = NormalizedString::new("password123").unwrap();
+
+        // SHA1("ALICE:PASSWORD123")
+        let sha_pass_hash: [u8; 20] = hex_decode("A9580A42C739DA2DD0348F037915039CF6D0CA9F")
+            .try_into()
+            .unwrap();
+
+        let salt = Salt::from_be_hex_str(
+            "65771e13b30bea9f4ef6c8390a594e297c9739e38ab02316bf1522ed5571813c",
+        );
+        let expected = SrpVerifier::with_specific_salt(username.clone(), password.clone(), &salt);
+        let verifier = SrpVerifier::from_username_and_sha_pass_hash_with_salt(
+            username.clone(),
+            sha_pass_hash,
+            *salt.as_le_bytes(),
+        );
+        assert_eq!(verifier, expected);
+
+        let verifier =
+            SrpVerifier::from_username_and_sha_pass_hash(username.clone(), sha_pass_hash);
+        let expected = SrpVerifier::with_specific_salt(
+            username.clone(),
+            password.clone(),
+            &Salt::from_le_bytes(*verifier.salt()),
+        );
+        assert_eq!(verifier, expected);
+    }
+
+    #[test]
+    fn verify_checked_constructors() {
+        let username = NormalizedString::new("Alice").unwrap();
+        let password = NormalizedString::new("password123").unwrap();
+
+        let verifier =
+            SrpVerifier::try_from_username_and_password(username.clone(), password.clone())
+                .unwrap();
+        let expected = SrpVerifier::with_specific_salt(
+            username.clone(),
+            password.clone(),
+            &Salt::from_le_bytes(*verifier.salt()),
+        );
+        assert_eq!(verifier, expected);
+
+        let proof = verifier.try_into_proof().unwrap();
+        assert_eq!(proof.salt(), expected.salt());
+
+        let client = SrpClientUser::try_new(username.clone(), password.clone()).unwrap();
+        let sha_pass_hash = *client.credentials_hash().as_bytes();
+        let client = client.into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*proof.server_public_key()).unwrap(),
+            *proof.salt(),
+        );
+        let client_public_key = PublicKey::from_le_bytes(*client.client_public_key()).unwrap();
+
+        assert!(matches!(
+            proof.clone().try_into_server(client_public_key, [0; 20]),
+            Err(SrpError::ProofsDoNotMatch(_))
+        ));
+        let (mut server, server_proof) = proof
+            .try_into_server(client_public_key, *client.client_proof())
+            .unwrap();
+
+        let client = client.verify_server_proof(server_proof).unwrap();
+        let values = client.calculate_reconnect_values(*server.reconnect_challenge_data());
+        let previous = *server.reconnect_challenge_data();
+        assert_eq!(
+            server.try_verify_reconnection_attempt(values.challenge_data, values.proof),
+            Ok(true)
+        );
+        assert_ne!(*server.reconnect_challenge_data(), previous);
+
+        let verifier =
+            SrpVerifier::try_from_username_and_sha_pass_hash(username.clone(), sha_pass_hash)
+                .unwrap();
+        assert_eq!(
+            verifier,
+            SrpVerifier::with_specific_salt(
+                username,
+                password,
+                &Salt::from_le_bytes(*verifier.salt())
+            )
+        );
+    }
 }
@@ -0,0 +1,441 @@
+#[cfg(feature = "json-account-store")]
+use crate::error::AccountStoreError;
+use crate::normalized_string::NormalizedString;
+use crate::server::SrpVerifier;
+use crate::{PASSWORD_VERIFIER_LENGTH, SALT_LENGTH};
+use std::collections::HashMap;
+use std::convert::Infallible;
+#[cfg(feature = "json-account-store")]
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+#[cfg(feature = "json-account-store")]
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Whether an account is allowed to log in.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "json-account-store",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum BanStatus {
+    /// The account can log in.
+    #[default]
+    NotBanned,
+    /// The account can not log in until the time has passed.
+    Suspended {
+        /// Seconds since the Unix epoch.
+        until: u64,
+    },
+    /// The account can never log in.
+    Banned,
+}
+
+/// Everything stored about an account.
+///
+/// The username, password verifier, and salt come from an [`SrpVerifier`],
+/// the remaining fields are up to the server.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct AccountRecord {
+    username: NormalizedString,
+    password_verifier: [u8; PASSWORD_VERIFIER_LENGTH as usize],
+    salt: [u8; SALT_LENGTH as usize],
+    /// PIN for clients that support it.
+    pub pin: Option<u32>,
+    /// Secret for authenticator tokens for clients that support it.
+    pub authenticator_secret: Option<Vec<u8>>,
+    /// Server specific flags, for example GM levels.
+    pub flags: u32,
+    /// Whether the account is allowed to log in.
+    pub ban_status: BanStatus,
+}
+
+impl AccountRecord {
+    /// Creates a record from the values of `verifier` with no PIN, no authenticator,
+    /// no flags, and no ban.
+    #[must_use]
+    pub fn new(verifier: &SrpVerifier) -> Self {
+        Self {
+            username: verifier.username.clone(),
+            password_verifier: *verifier.password_verifier(),
+            salt: *verifier.salt(),
+            pin: None,
+            authenticator_secret: None,
+            flags: 0,
+            ban_status: BanStatus::NotBanned,
+        }
+    }
+
+    /// Username of the account.
+    #[must_use]
+    pub fn username(&self) -> &str {
+        self.username.as_ref()
+    }
+
+    /// See [`SrpVerifier::password_verifier`].
+    #[must_use]
+    pub const fn password_verifier(&self) -> &[u8; PASSWORD_VERIFIER_LENGTH as usize] {
+        &self.password_verifier
+    }
+
+    /// See [`SrpVerifier::salt`].
+    #[must_use]
+    pub const fn salt(&self) -> &[u8; SALT_LENGTH as usize] {
+        &self.salt
+    }
+
+    /// Replaces the password verifier and salt, for example after a password change.
+    ///
+    /// The username of `verifier` is not used.
+    pub fn set_verifier(&mut self, verifier: &SrpVerifier) {
+        self.password_verifier = *verifier.password_verifier();
+        self.salt = *verifier.salt();
+    }
+
+    /// Creates an [`SrpVerifier`] for authenticating the account.
+    #[must_use]
+    pub fn to_verifier(&self) -> SrpVerifier {
+        SrpVerifier::from_database_values(self.username.clone(), self.password_verifier, self.salt)
+    }
+}
+
+/// Storage of [`AccountRecord`]s.
+///
+/// All functions take `&self` so that the store can be shared between connections,
+/// implementations must handle synchronization internally.
+///
+/// [`InMemoryAccountStore`] keeps accounts in memory, and `JsonLinesAccountStore`
+/// keeps them in a file if `json-account-store` is enabled.
+pub trait AccountStore: Send + Sync {
+    /// Error returned by the store.
+    type Error: std::error::Error;
+
+    /// Returns the record for `username`, or [`None`] if the account does not exist.
+    ///
+    /// # Errors
+    ///
+    /// If the store can not be read.
+    fn load(&self, username: &NormalizedString) -> Result<Option<AccountRecord>, Self::Error>;
+
+    /// Inserts `record`, replacing any record with the same username.
+    ///
+    /// # Errors
+    ///
+    /// If the store can not be written.
+    fn save(&self, record: AccountRecord) -> Result<(), Self::Error>;
+
+    /// Removes the record for `username`.
+    ///
+    /// Returns `true` if the account existed.
+    ///
+    /// # Errors
+    ///
+    /// If the store can not be written.
+    fn remove(&self, username: &NormalizedString) -> Result<bool, Self::Error>;
+}
+
+/// [`AccountStore`] that keeps records in a [`HashMap`] behind a [`Mutex`].
+///
+/// Intended for tests and small servers where accounts do not need to survive restarts.
+///
+/// # Example
+///
+/// ```
+/// use wow_srp::normalized_string::NormalizedString;
+/// use wow_srp::server::{AccountRecord, AccountStore, InMemoryAccountStore, SrpVerifier};
+///
+/// let store = InMemoryAccountStore::new();
+///
+/// let username = NormalizedString::new("Alice").unwrap();
+/// let password = NormalizedString::new("password123").unwrap();
+/// let verifier = SrpVerifier::from_username_and_password(username.clone(), password);
+/// store.save(AccountRecord::new(&verifier)).unwrap();
+///
+/// let record = store.load(&username).unwrap().unwrap();
+/// let proof = record.to_verifier().into_proof();
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryAccountStore {
+    accounts: Mutex<HashMap<NormalizedString, AccountRecord>>,
+}
+
+impl InMemoryAccountStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<NormalizedString, AccountRecord>> {
+        self.accounts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl AccountStore for InMemoryAccountStore {
+    type Error = Infallible;
+
+    fn load(&self, username: &NormalizedString) -> Result<Option<AccountRecord>, Self::Error> {
+        Ok(self.lock().get(username).cloned())
+    }
+
+    fn save(&self, record: AccountRecord) -> Result<(), Self::Error> {
+        self.lock().insert(record.username.clone(), record);
+        Ok(())
+    }
+
+    fn remove(&self, username: &NormalizedString) -> Result<bool, Self::Error> {
+        Ok(self.lock().remove(username).is_some())
+    }
+}
+
+/// Line format of [`JsonLinesAccountStore`].
+#[cfg(feature = "json-account-store")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonRecord {
+    username: String,
+    password_verifier: [u8; PASSWORD_VERIFIER_LENGTH as usize],
+    salt: [u8; SALT_LENGTH as usize],
+    #[serde(default)]
+    pin: Option<u32>,
+    #[serde(default)]
+    authenticator_secret: Option<Vec<u8>>,
+    #[serde(default)]
+    flags: u32,
+    #[serde(default)]
+    ban_status: BanStatus,
+}
+
+/// [`AccountStore`] that keeps records in a file with one JSON object per line.
+///
+/// All records are kept in memory and the whole file is rewritten on every change,
+/// through a temporary file in the same directory that replaces the original.
+/// This makes it suitable for development and small servers, not for thousands of accounts.
+///
+/// `json-account-store` must be enabled for this to be enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// use wow_srp::server::{AccountStore, JsonLinesAccountStore};
+/// use wow_srp::normalized_string::NormalizedString;
+///
+/// let store = JsonLinesAccountStore::open("accounts.jsonl").unwrap();
+/// let record = store.load(&NormalizedString::new("Alice").unwrap()).unwrap();
+/// ```
+#[cfg(feature = "json-account-store")]
+#[derive(Debug)]
+pub struct JsonLinesAccountStore {
+    path: PathBuf,
+    accounts: Mutex<HashMap<NormalizedString, AccountRecord>>,
+}
+
+#[cfg(feature = "json-account-store")]
+impl JsonLinesAccountStore {
+    /// Opens the store at `path`, which is created on the first change if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// If the file exists but can not be read, or contains invalid records.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AccountStoreError> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut accounts = HashMap::new();
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for (i, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let invalid = || AccountStoreError::InvalidRecord { line: i + 1 };
+                    let record: JsonRecord = serde_json::from_str(&line).map_err(|_| invalid())?;
+                    let username = NormalizedString::new(record.username).map_err(|_| invalid())?;
+
+                    accounts.insert(
+                        username.clone(),
+                        AccountRecord {
+                            username,
+                            password_verifier: record.password_verifier,
+                            salt: record.salt,
+                            pin: record.pin,
+                            authenticator_secret: record.authenticator_secret,
+                            flags: record.flags,
+                            ban_status: record.ban_status,
+                        },
+                    );
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            path,
+            accounts: Mutex::new(accounts),
+        })
+    }
+
+    /// Path of the file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<NormalizedString, AccountRecord>> {
+        self.accounts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, accounts: &HashMap<NormalizedString, AccountRecord>) -> std::io::Result<()> {
+        let mut records: Vec<&AccountRecord> = accounts.values().collect();
+        records.sort_by(|a, b| a.username.cmp(&b.username));
+
+        let mut contents = Vec::new();
+        for record in records {
+            let record = JsonRecord {
+                username: record.username.to_string(),
+                password_verifier: record.password_verifier,
+                salt: record.salt,
+                pin: record.pin,
+                authenticator_secret: record.authenticator_secret.clone(),
+                flags: record.flags,
+                ban_status: record.ban_status,
+            };
+            serde_json::to_writer(&mut contents, &record)?;
+            contents.push(b'\n');
+        }
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+#[cfg(feature = "json-account-store")]
+impl AccountStore for JsonLinesAccountStore {
+    type Error = AccountStoreError;
+
+    fn load(&self, username: &NormalizedString) -> Result<Option<AccountRecord>, Self::Error> {
+        Ok(self.lock().get(username).cloned())
+    }
+
+    fn save(&self, record: AccountRecord) -> Result<(), Self::Error> {
+        let mut accounts = self.lock();
+
+        let previous = accounts.insert(record.username.clone(), record.clone());
+        if let Err(e) = self.write(&accounts) {
+            // Keep memory and file the same
+            match previous {
+                Some(previous) => accounts.insert(record.username, previous),
+                None => accounts.remove(&record.username),
+            };
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, username: &NormalizedString) -> Result<bool, Self::Error> {
+        let mut accounts = self.lock();
+
+        let previous = match accounts.remove(username) {
+            Some(previous) => previous,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = self.write(&accounts) {
+            accounts.insert(username.clone(), previous);
+            return Err(e.into());
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::normalized_string::NormalizedString;
+    use crate::server::{
+        AccountRecord, AccountStore, BanStatus, InMemoryAccountStore, SrpVerifier,
+    };
+
+    fn record(username: &str) -> AccountRecord {
+        let username = NormalizedString::new(username).unwrap();
+        let password = NormalizedString::new("password").unwrap();
+        AccountRecord::new(&SrpVerifier::from_username_and_password(username, password))
+    }
+
+    fn verify_store(store: &impl AccountStore) {
+        let username = NormalizedString::new("a").unwrap();
+        assert_eq!(store.load(&username).unwrap(), None);
+
+        let mut account = record("A");
+        store.save(account.clone()).unwrap();
+        assert_eq!(store.load(&username).unwrap(), Some(account.clone()));
+
+        account.pin = Some(1234);
+        account.authenticator_secret = Some(vec![1, 2, 3]);
+        account.flags = 3;
+        account.ban_status = BanStatus::Suspended {
+            until: 1_700_000_000,
+        };
+        account.set_verifier(&SrpVerifier::from_username_and_password(
+            NormalizedString::new("A").unwrap(),
+            NormalizedString::new("other").unwrap(),
+        ));
+        store.save(account.clone()).unwrap();
+        store.save(record("B")).unwrap();
+        assert_eq!(store.load(&username).unwrap(), Some(account.clone()));
+
+        let verifier = account.to_verifier();
+        assert_eq!(verifier.username(), "A");
+        assert_eq!(verifier.salt(), account.salt());
+        assert_eq!(verifier.password_verifier(), account.password_verifier());
+
+        assert!(store.remove(&username).unwrap());
+        assert!(!store.remove(&username).unwrap());
+        assert_eq!(store.load(&username).unwrap(), None);
+    }
+
+    #[test]
+    fn verify_in_memory() {
+        verify_store(&InMemoryAccountStore::new());
+    }
+
+    #[test]
+    #[cfg(feature = "json-account-store")]
+    fn verify_json_lines() {
+        use crate::error::AccountStoreError;
+        use crate::server::JsonLinesAccountStore;
+
+        let path =
+            std::env::temp_dir().join(format!("wow_srp_accounts_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = JsonLinesAccountStore::open(&path).unwrap();
+        verify_store(&store);
+
+        let mut account = record("C");
+        account.ban_status = BanStatus::Banned;
+        store.save(account.clone()).unwrap();
+
+        // Reopening reads the same records
+        let store = JsonLinesAccountStore::open(&path).unwrap();
+        let b = NormalizedString::new("B").unwrap();
+        let c = NormalizedString::new("C").unwrap();
+        assert!(store.load(&b).unwrap().is_some());
+        assert_eq!(store.load(&c).unwrap(), Some(account));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        std::fs::write(&path, "{}\n").unwrap();
+        assert!(matches!(
+            JsonLinesAccountStore::open(&path),
+            Err(AccountStoreError::InvalidRecord { line: 1 })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
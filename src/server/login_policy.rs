@@ -0,0 +1,391 @@
+use crate::clock::{Clock, SystemClock};
+use crate::normalized_string::NormalizedString;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Result code for a suspended account in [`CMD_AUTH_LOGON_CHALLENGE_Server`].
+///
+/// [`CMD_AUTH_LOGON_CHALLENGE_Server`]: https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Server
+pub const LOGIN_RESULT_SUSPENDED: u8 = 0x0C;
+
+/// What to do with the next [`CMD_AUTH_LOGON_CHALLENGE_Client`], from [`LoginPolicy::check`].
+///
+/// [`CMD_AUTH_LOGON_CHALLENGE_Client`]: https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Client
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum LoginDecision {
+    /// Answer the challenge as normal.
+    Allow,
+    /// Wait for `duration` before answering the challenge as normal.
+    Delay {
+        /// How long to wait.
+        duration: Duration,
+    },
+    /// Reject the challenge with [`LOGIN_RESULT_SUSPENDED`].
+    Suspend {
+        /// How long the suspension lasts.
+        remaining: Duration,
+    },
+}
+
+impl LoginDecision {
+    /// Result code to send in [`CMD_AUTH_LOGON_CHALLENGE_Server`], or [`None`] if the
+    /// challenge should be answered.
+    ///
+    /// [`CMD_AUTH_LOGON_CHALLENGE_Server`]: https://wowdev.wiki/CMD_AUTH_LOGON_CHALLENGE_Server
+    #[must_use]
+    pub const fn result_code(&self) -> Option<u8> {
+        match self {
+            LoginDecision::Allow | LoginDecision::Delay { .. } => None,
+            LoginDecision::Suspend { .. } => Some(LOGIN_RESULT_SUSPENDED),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Failures {
+    times: VecDeque<Instant>,
+    suspended_until: Option<Instant>,
+}
+
+impl Failures {
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some(time) = self.times.front() {
+            if now.saturating_duration_since(*time) < window {
+                break;
+            }
+            self.times.pop_front();
+        }
+
+        if matches!(self.suspended_until, Some(until) if until <= now) {
+            self.suspended_until = None;
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.times.is_empty() && self.suspended_until.is_none()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    accounts: HashMap<NormalizedString, Failures>,
+    addresses: HashMap<IpAddr, Failures>,
+}
+
+/// Throttling of failed logins per account and per IP address.
+///
+/// Failed logins are counted in a sliding window.
+/// When either the account or the address has more failures than the allowed amount,
+/// challenges are delayed with exponential backoff.
+/// When either reaches the suspension threshold, challenges are rejected for the
+/// suspension duration.
+///
+/// An [`InvalidPublicKeyError`](crate::error::InvalidPublicKeyError) can only be caused by a
+/// client deliberately sending invalid data, so [`LoginPolicy::record_invalid_public_key`]
+/// suspends the address immediately.
+/// The account is not affected since anybody can send the username of another account.
+///
+/// All functions take `&self` so that the policy can be shared between connections.
+///
+/// # Example
+///
+/// ```
+/// use std::net::IpAddr;
+/// use std::time::Duration;
+/// use wow_srp::normalized_string::NormalizedString;
+/// use wow_srp::server::{LoginDecision, LoginPolicy};
+///
+/// fn challenge_received(policy: &LoginPolicy, username: &NormalizedString, address: IpAddr) {
+///     match policy.check(username, address) {
+///         LoginDecision::Allow => { /* Send the challenge */ }
+///         LoginDecision::Delay { duration } => { /* Sleep, then send the challenge */ }
+///         decision @ LoginDecision::Suspend { .. } => {
+///             let result = decision.result_code();
+///             // Send the result and close the connection
+///         }
+///     }
+/// }
+///
+/// let policy = LoginPolicy::new(Duration::from_secs(15 * 60))
+///     .with_backoff(3, Duration::from_secs(1), Duration::from_secs(30))
+///     .with_suspension(10, Duration::from_secs(60 * 60));
+/// ```
+#[derive(Debug)]
+pub struct LoginPolicy<C: Clock = SystemClock> {
+    state: Mutex<State>,
+    window: Duration,
+    free_failures: u32,
+    base_delay: Duration,
+    maximum_delay: Duration,
+    suspension_threshold: u32,
+    suspension_duration: Duration,
+    clock: C,
+}
+
+impl LoginPolicy<SystemClock> {
+    /// Creates a policy that counts failures within `window`.
+    ///
+    /// By default 3 failures are allowed before delaying, starting at 1 second and
+    /// doubling up to 30 seconds.
+    /// After 10 failures logins are suspended for 15 minutes.
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self::with_clock(window, SystemClock)
+    }
+}
+
+impl<C: Clock> LoginPolicy<C> {
+    /// Same as [`LoginPolicy::new`] but with a specific [`Clock`].
+    #[must_use]
+    pub fn with_clock(window: Duration, clock: C) -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            window,
+            free_failures: 3,
+            base_delay: Duration::from_secs(1),
+            maximum_delay: Duration::from_secs(30),
+            suspension_threshold: 10,
+            suspension_duration: Duration::from_secs(15 * 60),
+            clock,
+        }
+    }
+
+    /// Allows `free_failures` failures before delaying.
+    /// The delay starts at `base_delay` and doubles for every failure, up to `maximum_delay`.
+    #[must_use]
+    pub const fn with_backoff(
+        mut self,
+        free_failures: u32,
+        base_delay: Duration,
+        maximum_delay: Duration,
+    ) -> Self {
+        self.free_failures = free_failures;
+        self.base_delay = base_delay;
+        self.maximum_delay = maximum_delay;
+        self
+    }
+
+    /// Suspends logins for `duration` after `threshold` failures.
+    ///
+    /// A `threshold` of 0 never suspends because of failed proofs.
+    #[must_use]
+    pub const fn with_suspension(mut self, threshold: u32, duration: Duration) -> Self {
+        self.suspension_threshold = threshold;
+        self.suspension_duration = duration;
+        self
+    }
+
+    /// Decides what to do with the next challenge from `username` at `address`.
+    #[must_use]
+    pub fn check(&self, username: &NormalizedString, address: IpAddr) -> LoginDecision {
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let account = self.current(&mut state.accounts, username, now);
+        let address = self.current(&mut state.addresses, &address, now);
+
+        let remaining = account
+            .1
+            .max(address.1)
+            .map(|until| until.saturating_duration_since(now));
+        if let Some(remaining) = remaining {
+            return LoginDecision::Suspend { remaining };
+        }
+
+        let failures = account.0.max(address.0);
+        if failures < self.free_failures {
+            return LoginDecision::Allow;
+        }
+
+        let exponent = (failures - self.free_failures).min(31);
+        let duration = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .map_or(self.maximum_delay, |delay| delay.min(self.maximum_delay));
+
+        LoginDecision::Delay { duration }
+    }
+
+    /// Records a [`MatchProofsError`](crate::error::MatchProofsError) from
+    /// [`SrpProof::into_server`](crate::server::SrpProof::into_server), usually a wrong password.
+    pub fn record_proof_mismatch(&self, username: &NormalizedString, address: IpAddr) {
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let account = state.accounts.entry(username.clone()).or_default();
+        self.add_failure(account, now);
+
+        let address = state.addresses.entry(address).or_default();
+        self.add_failure(address, now);
+    }
+
+    /// Records an [`InvalidPublicKeyError`](crate::error::InvalidPublicKeyError), which
+    /// suspends `address` immediately.
+    pub fn record_invalid_public_key(&self, address: IpAddr) {
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let address = state.addresses.entry(address).or_default();
+        address.times.push_back(now);
+        address.suspended_until = Some(now + self.suspension_duration);
+    }
+
+    /// Records a successful login, which clears the failures of `username`.
+    ///
+    /// The failures of the address are kept since one address can try many accounts.
+    pub fn record_success(&self, username: &NormalizedString) {
+        self.lock().accounts.remove(username);
+    }
+
+    /// Removes accounts and addresses without failures in the window or active suspensions.
+    ///
+    /// Returns the amount of removed entries.
+    pub fn remove_stale(&self) -> usize {
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let before = state.accounts.len() + state.addresses.len();
+        for failures in state.accounts.values_mut() {
+            failures.prune(now, self.window);
+        }
+        for failures in state.addresses.values_mut() {
+            failures.prune(now, self.window);
+        }
+        state.accounts.retain(|_, failures| !failures.is_stale());
+        state.addresses.retain(|_, failures| !failures.is_stale());
+
+        before - (state.accounts.len() + state.addresses.len())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Amount of failures in the window and end of suspension for `key`.
+    fn current<K: Hash + Eq>(
+        &self,
+        map: &mut HashMap<K, Failures>,
+        key: &K,
+        now: Instant,
+    ) -> (u32, Option<Instant>) {
+        match map.get_mut(key) {
+            Some(failures) => {
+                failures.prune(now, self.window);
+                (failures.times.len() as u32, failures.suspended_until)
+            }
+            None => (0, None),
+        }
+    }
+
+    fn add_failure(&self, failures: &mut Failures, now: Instant) {
+        failures.prune(now, self.window);
+        failures.times.push_back(now);
+
+        if self.suspension_threshold != 0
+            && failures.times.len() >= self.suspension_threshold as usize
+        {
+            failures.suspended_until = Some(now + self.suspension_duration);
+            failures.times.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock::ManualClock;
+    use crate::normalized_string::NormalizedString;
+    use crate::server::{LoginDecision, LoginPolicy, LOGIN_RESULT_SUSPENDED};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    const ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const OTHER_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn delay(seconds: u64) -> LoginDecision {
+        LoginDecision::Delay {
+            duration: Duration::from_secs(seconds),
+        }
+    }
+
+    #[test]
+    fn verify_backoff() {
+        let clock = ManualClock::new();
+        let policy = LoginPolicy::with_clock(Duration::from_secs(60), clock.clone())
+            .with_backoff(2, Duration::from_secs(1), Duration::from_secs(5))
+            .with_suspension(0, Duration::from_secs(0));
+        let username = NormalizedString::new("A").unwrap();
+
+        assert_eq!(policy.check(&username, ADDRESS), LoginDecision::Allow);
+
+        let mut expected = vec![LoginDecision::Allow, delay(1), delay(2), delay(4)];
+        expected.extend([delay(5); 3]);
+        for expected in expected {
+            policy.record_proof_mismatch(&username, ADDRESS);
+            assert_eq!(policy.check(&username, ADDRESS), expected);
+        }
+
+        // Both the account and the address are throttled
+        let other = NormalizedString::new("B").unwrap();
+        assert_eq!(policy.check(&username, OTHER_ADDRESS), delay(5));
+        assert_eq!(policy.check(&other, ADDRESS), delay(5));
+        assert_eq!(policy.check(&other, OTHER_ADDRESS), LoginDecision::Allow);
+
+        // Success only clears the account
+        policy.record_success(&username);
+        assert_eq!(policy.check(&username, OTHER_ADDRESS), LoginDecision::Allow);
+        assert_eq!(policy.check(&username, ADDRESS), delay(5));
+
+        // Failures leave the window
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(policy.check(&username, ADDRESS), LoginDecision::Allow);
+        assert_eq!(policy.remove_stale(), 1);
+    }
+
+    #[test]
+    fn verify_suspension() {
+        let clock = ManualClock::new();
+        let policy = LoginPolicy::with_clock(Duration::from_secs(60), clock.clone())
+            .with_suspension(3, Duration::from_secs(100));
+        let username = NormalizedString::new("A").unwrap();
+
+        for _ in 0..3 {
+            policy.record_proof_mismatch(&username, ADDRESS);
+        }
+        let decision = policy.check(&username, OTHER_ADDRESS);
+        assert_eq!(
+            decision,
+            LoginDecision::Suspend {
+                remaining: Duration::from_secs(100)
+            }
+        );
+        assert_eq!(decision.result_code(), Some(LOGIN_RESULT_SUSPENDED));
+        assert_eq!(LoginDecision::Allow.result_code(), None);
+
+        clock.advance(Duration::from_secs(40));
+        assert_eq!(
+            policy.check(&username, ADDRESS),
+            LoginDecision::Suspend {
+                remaining: Duration::from_secs(60)
+            }
+        );
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(policy.check(&username, ADDRESS), LoginDecision::Allow);
+
+        // Invalid public keys only suspend the address
+        policy.record_invalid_public_key(OTHER_ADDRESS);
+        assert_eq!(
+            policy.check(&username, OTHER_ADDRESS),
+            LoginDecision::Suspend {
+                remaining: Duration::from_secs(100)
+            }
+        );
+        assert_eq!(policy.check(&username, ADDRESS), LoginDecision::Allow);
+
+        clock.advance(Duration::from_secs(100));
+        assert_eq!(policy.remove_stale(), 3);
+    }
+}
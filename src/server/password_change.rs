@@ -0,0 +1,289 @@
+use crate::error::PasswordChangeError;
+use crate::normalized_string::NormalizedString;
+use crate::server::{AccountRecord, SrpServer, SrpVerifier};
+use crate::SHA1_HASH_LENGTH;
+
+/// The record before and after a password change or salt rotation.
+///
+/// `old` is the record that was passed in and `new` has the new password verifier and salt,
+/// with all other fields unchanged.
+/// The database should only be updated to `new` if the stored record is still `old`,
+/// for example with a transaction or an `UPDATE ... WHERE` on the old verifier and salt,
+/// so that two concurrent changes can not overwrite each other.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct PasswordChange {
+    /// Record before the change.
+    pub old: AccountRecord,
+    /// Record after the change.
+    pub new: AccountRecord,
+}
+
+/// Decides which accounts should have their salt rotated on the next successful login,
+/// see [`SrpServer::rehash_on_login`].
+///
+/// Implemented for closures taking an [`AccountRecord`].
+pub trait RehashPolicy {
+    /// Returns `true` if `record` should get a new salt and password verifier.
+    fn needs_rehash(&self, record: &AccountRecord) -> bool;
+}
+
+impl<F: Fn(&AccountRecord) -> bool> RehashPolicy for F {
+    fn needs_rehash(&self, record: &AccountRecord) -> bool {
+        self(record)
+    }
+}
+
+/// [`RehashPolicy`] for records with a salt of all zeros,
+/// which some imports use when the original salt is not known.
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct RehashZeroSalts;
+
+impl RehashPolicy for RehashZeroSalts {
+    fn needs_rehash(&self, record: &AccountRecord) -> bool {
+        record.salt().iter().all(|b| *b == 0)
+    }
+}
+
+impl SrpServer {
+    /// Creates a new password verifier with a new random salt for `record`.
+    ///
+    /// Since an [`SrpServer`] only exists after [`SrpProof::into_server`](crate::server::SrpProof::into_server)
+    /// has succeeded, the user has already proven that they know the current password.
+    ///
+    /// # Errors
+    ///
+    /// If `record` is not for the user of this session.
+    pub fn change_password(
+        &self,
+        record: &AccountRecord,
+        new_password: NormalizedString,
+    ) -> Result<PasswordChange, PasswordChangeError> {
+        self.check_username(record)?;
+
+        let verifier = SrpVerifier::from_username_and_password(self.username.clone(), new_password);
+
+        Ok(Self::new_change(record, &verifier))
+    }
+
+    /// Same as [`SrpServer::change_password`] but fails if the new salt does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SrpServer::change_password`], and [`PasswordChangeError::RngHealth`] if the
+    /// checks are enabled and the salt fails them.
+    pub fn try_change_password(
+        &self,
+        record: &AccountRecord,
+        new_password: NormalizedString,
+    ) -> Result<PasswordChange, PasswordChangeError> {
+        self.check_username(record)?;
+
+        let verifier =
+            SrpVerifier::try_from_username_and_password(self.username.clone(), new_password)?;
+
+        Ok(Self::new_change(record, &verifier))
+    }
+
+    /// Rotates the salt of `record` after a successful login if `policy` requires it.
+    ///
+    /// The server never learns the password during a login, so the new verifier is created
+    /// from the `sha_pass_hash` of the account, see
+    /// [`SrpVerifier::from_username_and_sha_pass_hash`].
+    /// Accounts without a stored `sha_pass_hash` can only be rotated through
+    /// [`SrpServer::change_password`].
+    ///
+    /// Returns [`None`] if `policy` does not require a rotation.
+    ///
+    /// # Errors
+    ///
+    /// If `record` is not for the user of this session, or if `sha_pass_hash` does not
+    /// match the current password verifier of `record`.
+    pub fn rehash_on_login(
+        &self,
+        record: &AccountRecord,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+        policy: &impl RehashPolicy,
+    ) -> Result<Option<PasswordChange>, PasswordChangeError> {
+        if !self.needs_rehash(record, sha_pass_hash, policy)? {
+            return Ok(None);
+        }
+
+        let verifier =
+            SrpVerifier::from_username_and_sha_pass_hash(self.username.clone(), sha_pass_hash);
+
+        Ok(Some(Self::new_change(record, &verifier)))
+    }
+
+    /// Same as [`SrpServer::rehash_on_login`] but fails if the new salt does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`SrpServer::rehash_on_login`], and [`PasswordChangeError::RngHealth`] if the
+    /// checks are enabled and the salt fails them.
+    pub fn try_rehash_on_login(
+        &self,
+        record: &AccountRecord,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+        policy: &impl RehashPolicy,
+    ) -> Result<Option<PasswordChange>, PasswordChangeError> {
+        if !self.needs_rehash(record, sha_pass_hash, policy)? {
+            return Ok(None);
+        }
+
+        let verifier =
+            SrpVerifier::try_from_username_and_sha_pass_hash(self.username.clone(), sha_pass_hash)?;
+
+        Ok(Some(Self::new_change(record, &verifier)))
+    }
+
+    fn needs_rehash(
+        &self,
+        record: &AccountRecord,
+        sha_pass_hash: [u8; SHA1_HASH_LENGTH as usize],
+        policy: &impl RehashPolicy,
+    ) -> Result<bool, PasswordChangeError> {
+        self.check_username(record)?;
+
+        if !policy.needs_rehash(record) {
+            return Ok(false);
+        }
+
+        let current = SrpVerifier::from_username_and_sha_pass_hash_with_salt(
+            self.username.clone(),
+            sha_pass_hash,
+            *record.salt(),
+        );
+        if current.password_verifier() != record.password_verifier() {
+            return Err(PasswordChangeError::CredentialsHashMismatch);
+        }
+
+        Ok(true)
+    }
+
+    fn check_username(&self, record: &AccountRecord) -> Result<(), PasswordChangeError> {
+        if record.username() != self.username() {
+            return Err(PasswordChangeError::UsernameMismatch);
+        }
+
+        Ok(())
+    }
+
+    fn new_change(record: &AccountRecord, verifier: &SrpVerifier) -> PasswordChange {
+        let mut new = record.clone();
+        new.set_verifier(verifier);
+
+        PasswordChange {
+            old: record.clone(),
+            new,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::SrpClientUser;
+    use crate::error::PasswordChangeError;
+    use crate::normalized_string::NormalizedString;
+    use crate::server::{AccountRecord, RehashZeroSalts, SrpServer, SrpVerifier};
+    use crate::srp_internal::calculate_credentials_hash;
+    use crate::{PublicKey, GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+
+    fn n(s: &str) -> NormalizedString {
+        NormalizedString::new(s).unwrap()
+    }
+
+    fn login(record: &AccountRecord, password: &str) -> Option<SrpServer> {
+        let client = SrpClientUser::new(n(record.username()), n(password));
+        let proof = record.to_verifier().into_proof();
+
+        let client = client.into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*proof.server_public_key()).unwrap(),
+            *proof.salt(),
+        );
+        proof
+            .into_server(
+                PublicKey::from_le_bytes(*client.client_public_key()).unwrap(),
+                *client.client_proof(),
+            )
+            .ok()
+            .map(|(server, _)| server)
+    }
+
+    #[test]
+    fn verify_change_password() {
+        let mut record =
+            AccountRecord::new(&SrpVerifier::from_username_and_password(n("A"), n("OLD")));
+        record.flags = 3;
+
+        let server = login(&record, "OLD").unwrap();
+        let change = server.change_password(&record, n("NEW")).unwrap();
+        assert_eq!(change.old, record);
+        assert_eq!(change.new.flags, 3);
+        assert_ne!(change.new.salt(), record.salt());
+
+        assert!(login(&change.new, "NEW").is_some());
+        assert!(login(&change.new, "OLD").is_none());
+
+        let other = AccountRecord::new(&SrpVerifier::from_username_and_password(n("B"), n("B")));
+        assert_eq!(
+            server.change_password(&other, n("NEW")),
+            Err(PasswordChangeError::UsernameMismatch)
+        );
+
+        let change = server.try_change_password(&record, n("NEW")).unwrap();
+        assert!(login(&change.new, "NEW").is_some());
+        assert_eq!(
+            server.try_change_password(&other, n("NEW")),
+            Err(PasswordChangeError::UsernameMismatch)
+        );
+    }
+
+    #[test]
+    fn verify_rehash_on_login() {
+        let sha_pass_hash = calculate_credentials_hash(&n("A"), &n("PASSWORD"));
+        let record = AccountRecord::new(&SrpVerifier::from_username_and_sha_pass_hash_with_salt(
+            n("A"),
+            sha_pass_hash,
+            [0; 32],
+        ));
+
+        let server = login(&record, "PASSWORD").unwrap();
+
+        let change = server
+            .rehash_on_login(&record, sha_pass_hash, &RehashZeroSalts)
+            .unwrap()
+            .unwrap();
+        assert_eq!(change.old, record);
+        assert_ne!(change.new.salt(), &[0; 32]);
+        assert!(login(&change.new, "PASSWORD").is_some());
+
+        assert_eq!(
+            server.rehash_on_login(&change.new, sha_pass_hash, &RehashZeroSalts),
+            Ok(None)
+        );
+        assert_eq!(
+            server.rehash_on_login(&record, sha_pass_hash, &|_: &AccountRecord| false),
+            Ok(None)
+        );
+
+        let wrong = calculate_credentials_hash(&n("A"), &n("WRONG"));
+        assert_eq!(
+            server.rehash_on_login(&record, wrong, &RehashZeroSalts),
+            Err(PasswordChangeError::CredentialsHashMismatch)
+        );
+
+        let change = server
+            .try_rehash_on_login(&record, sha_pass_hash, &RehashZeroSalts)
+            .unwrap()
+            .unwrap();
+        assert!(login(&change.new, "PASSWORD").is_some());
+        assert_eq!(
+            server.try_rehash_on_login(&record, wrong, &RehashZeroSalts),
+            Err(PasswordChangeError::CredentialsHashMismatch)
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use crate::error::SealedVerifierError;
+use crate::key::fill_random;
+use crate::normalized_string::NormalizedString;
+use crate::server::SrpVerifier;
+use crate::{RngSource, PASSWORD_VERIFIER_LENGTH, SALT_LENGTH};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use std::convert::TryInto;
+use std::fmt::{Debug, Formatter};
+
+/// Version of the binary encoding, always the first byte.
+const VERSION: u8 = 1;
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const PLAINTEXT_LENGTH: usize = PASSWORD_VERIFIER_LENGTH as usize + SALT_LENGTH as usize;
+
+/// Length of [`SealedVerifier::to_bytes`].
+pub const SEALED_VERIFIER_LENGTH: usize = 1 + 4 + NONCE_LENGTH + PLAINTEXT_LENGTH + TAG_LENGTH;
+
+/// Server held key for [`SealedVerifier`]s.
+///
+/// The id is stored in every sealed verifier so that the correct key can be found
+/// while rotating keys. Ids must be unique.
+///
+/// The key is not printed by [`Debug`].
+#[derive(Clone)]
+pub struct SealingKey {
+    id: u32,
+    key: [u8; 32],
+}
+
+impl SealingKey {
+    /// Creates a key from 32 random bytes.
+    #[must_use]
+    pub const fn new(id: u32, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    /// Creates a key from the system RNG.
+    ///
+    /// The key is checked by the [RNG health checks](crate::enable_rng_health_checks)
+    /// if they are enabled, but a failure is only returned by [`rng_health`](crate::rng_health).
+    #[must_use]
+    pub fn randomized(id: u32) -> Self {
+        let mut key = [0_u8; 32];
+        // Failures are still recorded for `rng_health`
+        let _ = fill_random(RngSource::SealingKey, &mut key);
+
+        Self::new(id, key)
+    }
+
+    /// Id of the key.
+    #[must_use]
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+}
+
+impl Debug for SealingKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SealingKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Password verifier and salt encrypted with ChaCha20-Poly1305 for storing in a database.
+///
+/// A leaked verifier allows offline dictionary attacks against the password,
+/// so the verifier and salt are only decrypted in memory by [`SealedVerifier::unseal`].
+///
+/// The username is authenticated but not encrypted, so a sealed verifier can not be moved to
+/// a different account.
+/// Each seal uses a new random nonce.
+///
+/// Keys are rotated by adding a new [`SealingKey`] with a new id, unsealing with both keys
+/// while [`SealedVerifier::reseal_all`] moves the records to the new key,
+/// and then removing the old key.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct SealedVerifier {
+    key_id: u32,
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: [u8; PLAINTEXT_LENGTH],
+    tag: [u8; TAG_LENGTH],
+}
+
+impl SealedVerifier {
+    /// Encrypts the password verifier and salt of `verifier` with `key`.
+    #[must_use]
+    pub fn seal(verifier: &SrpVerifier, key: &SealingKey) -> Self {
+        let mut nonce = [0_u8; NONCE_LENGTH];
+        // Failures are still recorded for `rng_health`
+        let _ = fill_random(RngSource::Nonce, &mut nonce);
+
+        let mut ciphertext = [0_u8; PLAINTEXT_LENGTH];
+        ciphertext[..PASSWORD_VERIFIER_LENGTH as usize]
+            .copy_from_slice(verifier.password_verifier());
+        ciphertext[PASSWORD_VERIFIER_LENGTH as usize..].copy_from_slice(verifier.salt());
+
+        let tag = encrypt(
+            key,
+            &nonce,
+            &associated_data(verifier.username(), key.id),
+            &mut ciphertext,
+        );
+
+        Self {
+            key_id: key.id,
+            nonce,
+            ciphertext,
+            tag,
+        }
+    }
+
+    /// Id of the [`SealingKey`] used to seal the verifier.
+    #[must_use]
+    pub const fn key_id(&self) -> u32 {
+        self.key_id
+    }
+
+    /// Decrypts the verifier for `username` with the key from `keys` that has the correct id,
+    /// and creates an [`SrpVerifier`] through
+    /// [`SrpVerifier::from_database_values`].
+    ///
+    /// # Errors
+    ///
+    /// If none of `keys` has the id of the sealed verifier, or if the verifier can not be
+    /// decrypted because the key, the username, or the data is wrong.
+    pub fn unseal(
+        &self,
+        username: NormalizedString,
+        keys: &[SealingKey],
+    ) -> Result<SrpVerifier, SealedVerifierError> {
+        let key = keys
+            .iter()
+            .find(|k| k.id == self.key_id)
+            .ok_or(SealedVerifierError::UnknownKeyId(self.key_id))?;
+
+        let mut plaintext = self.ciphertext;
+        key.cipher()
+            .decrypt_in_place_detached(
+                Nonce::from_slice(&self.nonce),
+                &associated_data(username.as_ref(), key.id),
+                &mut plaintext,
+                Tag::from_slice(&self.tag),
+            )
+            .map_err(|_| SealedVerifierError::DecryptionFailed)?;
+
+        let mut password_verifier = [0_u8; PASSWORD_VERIFIER_LENGTH as usize];
+        let mut salt = [0_u8; SALT_LENGTH as usize];
+        password_verifier.copy_from_slice(&plaintext[..PASSWORD_VERIFIER_LENGTH as usize]);
+        salt.copy_from_slice(&plaintext[PASSWORD_VERIFIER_LENGTH as usize..]);
+
+        Ok(SrpVerifier::from_database_values(
+            username,
+            password_verifier,
+            salt,
+        ))
+    }
+
+    /// Unseals the verifier with `keys` and seals it again with `new_key`.
+    ///
+    /// # Errors
+    ///
+    /// If the verifier can not be unsealed, see [`SealedVerifier::unseal`].
+    pub fn reseal(
+        &self,
+        username: NormalizedString,
+        keys: &[SealingKey],
+        new_key: &SealingKey,
+    ) -> Result<Self, SealedVerifierError> {
+        let verifier = self.unseal(username, keys)?;
+
+        Ok(Self::seal(&verifier, new_key))
+    }
+
+    /// Reseals every verifier in `records` that is not already sealed with `new_key`.
+    ///
+    /// `keys` must contain the keys for all records.
+    /// Records are only changed if all of them can be unsealed,
+    /// so a missing key does not leave them partially rotated.
+    ///
+    /// Returns the amount of records that were resealed.
+    ///
+    /// # Errors
+    ///
+    /// If any verifier can not be unsealed, see [`SealedVerifier::unseal`].
+    pub fn reseal_all(
+        records: &mut [(NormalizedString, SealedVerifier)],
+        keys: &[SealingKey],
+        new_key: &SealingKey,
+    ) -> Result<usize, SealedVerifierError> {
+        let mut resealed = Vec::new();
+        for (i, (username, sealed)) in records.iter().enumerate() {
+            if sealed.key_id != new_key.id {
+                resealed.push((i, sealed.reseal(username.clone(), keys, new_key)?));
+            }
+        }
+
+        let amount = resealed.len();
+        for (i, sealed) in resealed {
+            records[i].1 = sealed;
+        }
+
+        Ok(amount)
+    }
+
+    /// Binary encoding for storage, always [`SEALED_VERIFIER_LENGTH`] bytes.
+    ///
+    /// The layout is a version byte, the key id as a **little endian** integer, the nonce,
+    /// the encrypted verifier and salt, and the authentication tag.
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; SEALED_VERIFIER_LENGTH] {
+        let mut b = [0_u8; SEALED_VERIFIER_LENGTH];
+        b[0] = VERSION;
+        b[1..5].copy_from_slice(&self.key_id.to_le_bytes());
+        b[5..17].copy_from_slice(&self.nonce);
+        b[17..17 + PLAINTEXT_LENGTH].copy_from_slice(&self.ciphertext);
+        b[17 + PLAINTEXT_LENGTH..].copy_from_slice(&self.tag);
+
+        b
+    }
+
+    /// Parses the encoding from [`SealedVerifier::to_bytes`].
+    ///
+    /// The data is not authenticated until [`SealedVerifier::unseal`].
+    ///
+    /// # Errors
+    ///
+    /// If the length or version is wrong.
+    pub fn from_bytes(b: &[u8]) -> Result<Self, SealedVerifierError> {
+        let b: &[u8; SEALED_VERIFIER_LENGTH] = b
+            .try_into()
+            .map_err(|_| SealedVerifierError::InvalidLength { length: b.len() })?;
+        if b[0] != VERSION {
+            return Err(SealedVerifierError::UnsupportedVersion(b[0]));
+        }
+
+        let mut s = Self {
+            key_id: u32::from_le_bytes([b[1], b[2], b[3], b[4]]),
+            nonce: [0; NONCE_LENGTH],
+            ciphertext: [0; PLAINTEXT_LENGTH],
+            tag: [0; TAG_LENGTH],
+        };
+        s.nonce.copy_from_slice(&b[5..17]);
+        s.ciphertext.copy_from_slice(&b[17..17 + PLAINTEXT_LENGTH]);
+        s.tag.copy_from_slice(&b[17 + PLAINTEXT_LENGTH..]);
+
+        Ok(s)
+    }
+}
+
+fn associated_data(username: &str, key_id: u32) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(4 + username.len());
+    aad.extend_from_slice(&key_id.to_le_bytes());
+    aad.extend_from_slice(username.as_bytes());
+
+    aad
+}
+
+fn encrypt(
+    key: &SealingKey,
+    nonce: &[u8; NONCE_LENGTH],
+    associated_data: &[u8],
+    buffer: &mut [u8; PLAINTEXT_LENGTH],
+) -> [u8; TAG_LENGTH] {
+    // Only fails for messages larger than 256 GiB
+    key.cipher()
+        .encrypt_in_place_detached(Nonce::from_slice(nonce), associated_data, buffer)
+        .unwrap()
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SealedVerifierError;
+    use crate::normalized_string::NormalizedString;
+    use crate::server::{SealedVerifier, SealingKey, SrpVerifier, SEALED_VERIFIER_LENGTH};
+
+    fn n(s: &str) -> NormalizedString {
+        NormalizedString::new(s).unwrap()
+    }
+
+    #[test]
+    fn verify_seal() {
+        let keys = [SealingKey::new(1, [7; 32])];
+        let key = &keys[0];
+        let verifier = SrpVerifier::from_username_and_password(n("A"), n("A"));
+
+        let sealed = SealedVerifier::seal(&verifier, key);
+        assert_eq!(sealed.key_id(), 1);
+        assert_eq!(sealed.unseal(n("A"), &keys).unwrap(), verifier);
+
+        // Nonces are random
+        assert_ne!(SealedVerifier::seal(&verifier, key), sealed);
+
+        let b = sealed.to_bytes();
+        assert_eq!(b.len(), SEALED_VERIFIER_LENGTH);
+        assert_eq!(SealedVerifier::from_bytes(&b).unwrap(), sealed);
+        assert!(!b
+            .windows(32)
+            .any(|w| w == verifier.password_verifier() || w == verifier.salt()));
+
+        assert_eq!(
+            sealed.unseal(n("B"), &keys),
+            Err(SealedVerifierError::DecryptionFailed)
+        );
+        assert_eq!(
+            sealed.unseal(n("A"), &[SealingKey::new(1, [8; 32])]),
+            Err(SealedVerifierError::DecryptionFailed)
+        );
+        assert_eq!(
+            sealed.unseal(n("A"), &[SealingKey::new(2, [7; 32])]),
+            Err(SealedVerifierError::UnknownKeyId(1))
+        );
+
+        let mut tampered = b;
+        tampered[20] ^= 1;
+        let tampered = SealedVerifier::from_bytes(&tampered).unwrap();
+        assert_eq!(
+            tampered.unseal(n("A"), &keys),
+            Err(SealedVerifierError::DecryptionFailed)
+        );
+
+        // Changing the key id also changes the associated data
+        let mut tampered = b;
+        tampered[1] = 2;
+        let tampered = SealedVerifier::from_bytes(&tampered).unwrap();
+        assert_eq!(
+            tampered.unseal(n("A"), &[SealingKey::new(2, [7; 32])]),
+            Err(SealedVerifierError::DecryptionFailed)
+        );
+
+        assert_eq!(
+            SealedVerifier::from_bytes(&b[1..]),
+            Err(SealedVerifierError::InvalidLength {
+                length: SEALED_VERIFIER_LENGTH - 1
+            })
+        );
+        let mut invalid = b;
+        invalid[0] = 0;
+        assert_eq!(
+            SealedVerifier::from_bytes(&invalid),
+            Err(SealedVerifierError::UnsupportedVersion(0))
+        );
+
+        assert!(!format!("{:?}", key).contains('7'));
+    }
+
+    #[test]
+    fn verify_reseal_all() {
+        let old = SealingKey::randomized(1);
+        let new = SealingKey::randomized(2);
+
+        let verifiers: Vec<SrpVerifier> = ["A", "B", "C"]
+            .iter()
+            .map(|s| SrpVerifier::from_username_and_password(n(s), n(s)))
+            .collect();
+
+        let mut records: Vec<(NormalizedString, SealedVerifier)> = verifiers
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let key = if i == 0 { &new } else { &old };
+                (n(v.username()), SealedVerifier::seal(v, key))
+            })
+            .collect();
+
+        // Missing keys leave everything unchanged
+        let before = records.clone();
+        assert_eq!(
+            SealedVerifier::reseal_all(&mut records, std::slice::from_ref(&new), &new),
+            Err(SealedVerifierError::UnknownKeyId(1))
+        );
+        assert_eq!(records, before);
+
+        let keys = [old, new.clone()];
+        assert_eq!(SealedVerifier::reseal_all(&mut records, &keys, &new), Ok(2));
+        assert_eq!(records[0], before[0]);
+        for ((username, sealed), verifier) in records.iter().zip(&verifiers) {
+            assert_eq!(sealed.key_id(), 2);
+            assert_eq!(
+                &sealed
+                    .unseal(username.clone(), std::slice::from_ref(&new))
+                    .unwrap(),
+                verifier
+            );
+        }
+
+        assert_eq!(SealedVerifier::reseal_all(&mut records, &keys, &new), Ok(0));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::clock::{Clock, SystemClock};
+use crate::normalized_string::NormalizedString;
+use crate::server::SrpServer;
+use crate::{PROOF_LENGTH, RECONNECT_CHALLENGE_DATA_LENGTH, SESSION_KEY_LENGTH};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Result of [`SessionStore::verify_reconnection_attempt`].
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum ReconnectOutcome {
+    /// The client has proven that it knows the session key and should be allowed to reconnect.
+    ///
+    /// The failed attempts of the session have been reset.
+    Success {
+        /// Session key of the session.
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+    },
+    /// The proofs do not match.
+    ///
+    /// The session is kept since anybody knowing the username can get this far.
+    ProofMismatch {
+        /// Failed attempts allowed before the session is removed.
+        ///
+        /// [`None`] if failed attempts never remove the session.
+        attempts_remaining: Option<u8>,
+    },
+    /// The proofs do not match and the session has been removed because of too many failed attempts.
+    ///
+    /// The client must log in with the password again.
+    TooManyAttempts,
+    /// The session is older than the time to live and has been removed.
+    Expired,
+    /// There is no session for the username.
+    NoSession,
+}
+
+/// Storage of [`SrpServer`]s for reconnection.
+///
+/// All functions take `&self` so that the store can be shared between connections,
+/// implementations must handle synchronization internally.
+///
+/// [`InMemorySessionStore`] is the default implementation.
+pub trait SessionStore: Send + Sync {
+    /// Stores `server` under its username.
+    ///
+    /// Any previous session for the username is replaced, since it has a different session key.
+    /// Returns `true` if a session was replaced.
+    fn insert(&self, server: SrpServer) -> bool;
+
+    /// Removes the session for `username`.
+    ///
+    /// Returns `true` if a session was removed.
+    fn remove(&self, username: &NormalizedString) -> bool;
+
+    /// Session key for `username`, for example for the world server.
+    ///
+    /// Returns [`None`] if there is no session or if it has expired.
+    fn session_key(&self, username: &NormalizedString)
+        -> Option<[u8; SESSION_KEY_LENGTH as usize]>;
+
+    /// Server data to send in the reconnect challenge, see
+    /// [`SrpServer::reconnect_challenge_data`].
+    ///
+    /// Returns [`None`] if there is no session or if it has expired.
+    fn reconnect_challenge_data(
+        &self,
+        username: &NormalizedString,
+    ) -> Option<[u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize]>;
+
+    /// Verifies the reconnect proof of the client, see
+    /// [`SrpServer::verify_reconnection_attempt`].
+    fn verify_reconnection_attempt(
+        &self,
+        username: &NormalizedString,
+        client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+    ) -> ReconnectOutcome;
+
+    /// Removes all expired sessions.
+    ///
+    /// Returns the amount of removed sessions.
+    fn remove_expired(&self) -> usize;
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    server: SrpServer,
+    /// Time of the insert or the last successful reconnect.
+    last_authenticated: Instant,
+    failed_attempts: u8,
+}
+
+/// [`SessionStore`] that keeps sessions in a [`HashMap`] behind a [`Mutex`].
+///
+/// Sessions expire a time to live after they were inserted or last successfully reconnected.
+/// Expired sessions are removed when they are accessed, or through
+/// [`SessionStore::remove_expired`].
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wow_srp::normalized_string::NormalizedString;
+/// use wow_srp::server::{InMemorySessionStore, ReconnectOutcome, SessionStore, SrpServer};
+/// use wow_srp::{PROOF_LENGTH, RECONNECT_CHALLENGE_DATA_LENGTH};
+///
+/// fn logged_in(store: &impl SessionStore, server: SrpServer) {
+///     store.insert(server);
+/// }
+///
+/// fn reconnect_proof_received(
+///     store: &impl SessionStore,
+///     username: &NormalizedString,
+///     client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
+///     client_proof: [u8; PROOF_LENGTH as usize],
+/// ) {
+///     match store.verify_reconnection_attempt(username, client_data, client_proof) {
+///         ReconnectOutcome::Success { session_key } => { /* Continue with the connection */ }
+///         _ => { /* Send an error back */ }
+///     }
+/// }
+///
+/// // Sessions last one hour and are removed after 5 failed reconnects
+/// let store = InMemorySessionStore::new(Duration::from_secs(60 * 60), 5);
+/// ```
+#[derive(Debug)]
+pub struct InMemorySessionStore<C: Clock = SystemClock> {
+    sessions: Mutex<HashMap<NormalizedString, Session>>,
+    time_to_live: Duration,
+    maximum_failed_attempts: u8,
+    clock: C,
+}
+
+impl InMemorySessionStore<SystemClock> {
+    /// Creates a store where sessions expire after `time_to_live` and are removed after
+    /// `maximum_failed_attempts` failed reconnects.
+    ///
+    /// A `maximum_failed_attempts` of 0 never removes sessions because of failed reconnects.
+    #[must_use]
+    pub fn new(time_to_live: Duration, maximum_failed_attempts: u8) -> Self {
+        Self::with_clock(time_to_live, maximum_failed_attempts, SystemClock)
+    }
+}
+
+impl<C: Clock> InMemorySessionStore<C> {
+    /// Same as [`InMemorySessionStore::new`] but with a specific [`Clock`].
+    #[must_use]
+    pub fn with_clock(time_to_live: Duration, maximum_failed_attempts: u8, clock: C) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            time_to_live,
+            maximum_failed_attempts,
+            clock,
+        }
+    }
+
+    /// Amount of stored sessions, including expired sessions that have not been removed yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no sessions are stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<NormalizedString, Session>> {
+        // The map is never left in an inconsistent state, so a panic in another thread is not a problem
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_expired(&self, session: &Session, now: Instant) -> bool {
+        now.saturating_duration_since(session.last_authenticated) >= self.time_to_live
+    }
+
+    /// Runs `f` on the session for `username` if it exists and has not expired.
+    fn with_session<T>(
+        &self,
+        username: &NormalizedString,
+        f: impl FnOnce(&Session) -> T,
+    ) -> Option<T> {
+        let now = self.clock.now();
+        let mut sessions = self.lock();
+
+        let session = sessions.get_mut(username)?;
+        if self.is_expired(session, now) {
+            sessions.remove(username);
+            return None;
+        }
+
+        Some(f(session))
+    }
+}
+
+impl<C: Clock> SessionStore for InMemorySessionStore<C> {
+    fn insert(&self, server: SrpServer) -> bool {
+        let session = Session {
+            last_authenticated: self.clock.now(),
+            failed_attempts: 0,
+            server,
+        };
+
+        self.lock()
+            .insert(session.server.username.clone(), session)
+            .is_some()
+    }
+
+    fn remove(&self, username: &NormalizedString) -> bool {
+        self.lock().remove(username).is_some()
+    }
+
+    fn session_key(
+        &self,
+        username: &NormalizedString,
+    ) -> Option<[u8; SESSION_KEY_LENGTH as usize]> {
+        self.with_session(username, |session| *session.server.session_key())
+    }
+
+    fn reconnect_challenge_data(
+        &self,
+        username: &NormalizedString,
+    ) -> Option<[u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize]> {
+        self.with_session(username, |session| {
+            *session.server.reconnect_challenge_data()
+        })
+    }
+
+    fn verify_reconnection_attempt(
+        &self,
+        username: &NormalizedString,
+        client_data: [u8; RECONNECT_CHALLENGE_DATA_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+    ) -> ReconnectOutcome {
+        let now = self.clock.now();
+        let mut sessions = self.lock();
+
+        let session = match sessions.get_mut(username) {
+            Some(session) => session,
+            None => return ReconnectOutcome::NoSession,
+        };
+
+        if self.is_expired(session, now) {
+            sessions.remove(username);
+            return ReconnectOutcome::Expired;
+        }
+
+        if session
+            .server
+            .verify_reconnection_attempt(client_data, client_proof)
+        {
+            session.last_authenticated = now;
+            session.failed_attempts = 0;
+
+            return ReconnectOutcome::Success {
+                session_key: *session.server.session_key(),
+            };
+        }
+
+        if self.maximum_failed_attempts == 0 {
+            return ReconnectOutcome::ProofMismatch {
+                attempts_remaining: None,
+            };
+        }
+
+        session.failed_attempts = session.failed_attempts.saturating_add(1);
+        if session.failed_attempts >= self.maximum_failed_attempts {
+            sessions.remove(username);
+            return ReconnectOutcome::TooManyAttempts;
+        }
+
+        ReconnectOutcome::ProofMismatch {
+            attempts_remaining: Some(self.maximum_failed_attempts - session.failed_attempts),
+        }
+    }
+
+    fn remove_expired(&self) -> usize {
+        let now = self.clock.now();
+        let mut sessions = self.lock();
+
+        let before = sessions.len();
+        sessions.retain(|_, session| !self.is_expired(session, now));
+
+        before - sessions.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::{SrpClient, SrpClientUser};
+    use crate::clock::ManualClock;
+    use crate::normalized_string::NormalizedString;
+    use crate::server::{
+        InMemorySessionStore, ReconnectOutcome, SessionStore, SrpServer, SrpVerifier,
+    };
+    use crate::{PublicKey, GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
+    use std::time::Duration;
+
+    fn authenticate(username: &str) -> (SrpClient, SrpServer) {
+        let n = || NormalizedString::new(username).unwrap();
+        let client = SrpClientUser::new(n(), n());
+        let proof = SrpVerifier::from_username_and_password(n(), n()).into_proof();
+
+        let client = client.into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*proof.server_public_key()).unwrap(),
+            *proof.salt(),
+        );
+        let (server, server_proof) = proof
+            .into_server(
+                PublicKey::from_le_bytes(*client.client_public_key()).unwrap(),
+                *client.client_proof(),
+            )
+            .unwrap();
+
+        (client.verify_server_proof(server_proof).unwrap(), server)
+    }
+
+    fn reconnect(
+        store: &impl SessionStore,
+        client: &SrpClient,
+        username: &NormalizedString,
+    ) -> ReconnectOutcome {
+        let challenge = store.reconnect_challenge_data(username).unwrap();
+        let values = client.calculate_reconnect_values(challenge);
+        store.verify_reconnection_attempt(username, values.challenge_data, values.proof)
+    }
+
+    #[test]
+    fn verify_reconnect() {
+        let store = InMemorySessionStore::new(Duration::from_secs(60), 3);
+        let username = NormalizedString::new("A").unwrap();
+        let (client, server) = authenticate("A");
+        let session_key = *server.session_key();
+
+        assert_eq!(
+            store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+            ReconnectOutcome::NoSession
+        );
+        assert!(!store.insert(server));
+        assert_eq!(store.session_key(&username), Some(session_key));
+
+        assert_eq!(
+            reconnect(&store, &client, &username),
+            ReconnectOutcome::Success { session_key }
+        );
+
+        assert_eq!(
+            store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+            ReconnectOutcome::ProofMismatch {
+                attempts_remaining: Some(2)
+            }
+        );
+        assert_eq!(
+            store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+            ReconnectOutcome::ProofMismatch {
+                attempts_remaining: Some(1)
+            }
+        );
+
+        // Success resets the failed attempts
+        assert_eq!(
+            reconnect(&store, &client, &username),
+            ReconnectOutcome::Success { session_key }
+        );
+        for attempts_remaining in [Some(2), Some(1)] {
+            assert_eq!(
+                store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+                ReconnectOutcome::ProofMismatch { attempts_remaining }
+            );
+        }
+        assert_eq!(
+            store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+            ReconnectOutcome::TooManyAttempts
+        );
+        assert!(store.is_empty());
+        assert_eq!(store.reconnect_challenge_data(&username), None);
+    }
+
+    #[test]
+    fn verify_replacement_and_expiry() {
+        let clock = ManualClock::new();
+        let store = InMemorySessionStore::with_clock(Duration::from_secs(60), 0, clock.clone());
+        let username = NormalizedString::new("A").unwrap();
+
+        let (old_client, old_server) = authenticate("A");
+        let (client, server) = authenticate("A");
+        let session_key = *server.session_key();
+        assert!(!store.insert(old_server));
+        assert!(store.insert(server));
+        assert_eq!(store.len(), 1);
+
+        // The old session key no longer works
+        assert_eq!(
+            reconnect(&store, &old_client, &username),
+            ReconnectOutcome::ProofMismatch {
+                attempts_remaining: None
+            }
+        );
+
+        // Successful reconnects refresh the session
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(
+            reconnect(&store, &client, &username),
+            ReconnectOutcome::Success { session_key }
+        );
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(store.session_key(&username), Some(session_key));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            store.verify_reconnection_attempt(&username, [0; 16], [0; 20]),
+            ReconnectOutcome::Expired
+        );
+        assert!(store.is_empty());
+
+        let (_, other) = authenticate("B");
+        store.insert(other);
+        assert!(store.remove(&NormalizedString::new("B").unwrap()));
+
+        let (_, other) = authenticate("B");
+        store.insert(other);
+        assert_eq!(store.remove_expired(), 0);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(store.remove_expired(), 1);
+    }
+}
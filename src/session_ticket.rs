@@ -0,0 +1,471 @@
+//! Handing the session key from the auth server to the world servers.
+//!
+//! After a successful login the auth server stores the session key, and the world server
+//! reads it back when the client sends [`CMSG_AUTH_SESSION`].
+//! A [`SessionTicket`] carries the session key together with the username, the time it was
+//! issued, the realm, and the [`Expansion`] of the client.
+//!
+//! The ticket has a canonical binary encoding through [`SessionTicket::to_bytes`] and
+//! [`SessionTicket::from_bytes`], and the same encoding as uppercase hex through
+//! [`SessionTicket::to_hex`] and [`SessionTicket::from_hex`].
+//! The `_signed` variants append an HMAC-SHA1 of the ticket with a key shared by the servers,
+//! so that a world server can detect tickets that have been changed in the database.
+//! Combined with [`SessionTicket::is_expired`] this also prevents old tickets from being used
+//! with a newer issue time.
+//!
+//! Other cores store the session key on its own in the `account.sessionkey` column as
+//! **big endian** hex, see [`SessionTicket::session_key_big_endian_hex`].
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use wow_srp::normalized_string::NormalizedString;
+//! use wow_srp::session_ticket::SessionTicket;
+//! use wow_srp::Expansion;
+//!
+//! let key = b"shared between auth and world";
+//! let issued_at = 1_700_000_000;
+//!
+//! // Auth server
+//! let ticket = SessionTicket::new(
+//!     NormalizedString::new("A").unwrap(),
+//!     [0xAB; 40],
+//!     issued_at,
+//!     1,
+//!     Expansion::Wrath,
+//! );
+//! let column = ticket.to_signed_hex(key);
+//!
+//! // World server
+//! let ticket = SessionTicket::from_signed_hex(&column, key).unwrap();
+//! assert!(!ticket.is_expired(issued_at + 30, Duration::from_secs(60)));
+//! ```
+//!
+//! [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+
+use crate::error::SessionTicketError;
+use crate::hex::{hex_encode_upper, try_hex_decode, try_hex_decode_big_endian, HexError};
+use crate::normalized_string::NormalizedString;
+use crate::{Expansion, SESSION_KEY_LENGTH, SHA1_HASH_LENGTH};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::convert::TryInto;
+use std::time::Duration;
+
+/// Version of the binary encoding, always the first byte.
+const VERSION: u8 = 1;
+
+/// Version, expansion, realm id, issue time, and session key.
+const FIXED_LENGTH: usize = 1 + 1 + 4 + 8 + SESSION_KEY_LENGTH as usize;
+
+/// Session key and account information shared from the auth server to the world servers.
+///
+/// Created through [`SessionTicket::new`] or
+/// [`SrpServer::session_ticket`](crate::server::SrpServer::session_ticket).
+///
+/// The session key is **little endian**, the same as [`SrpServer::session_key`](crate::server::SrpServer::session_key).
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct SessionTicket {
+    username: NormalizedString,
+    session_key: [u8; SESSION_KEY_LENGTH as usize],
+    issued_at: u64,
+    realm_id: u32,
+    expansion: Expansion,
+}
+
+impl SessionTicket {
+    /// Creates a ticket. `issued_at` is seconds since the Unix epoch.
+    #[must_use]
+    pub const fn new(
+        username: NormalizedString,
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+        issued_at: u64,
+        realm_id: u32,
+        expansion: Expansion,
+    ) -> Self {
+        Self {
+            username,
+            session_key,
+            issued_at,
+            realm_id,
+            expansion,
+        }
+    }
+
+    /// Username of the account.
+    #[must_use]
+    pub const fn username(&self) -> &NormalizedString {
+        &self.username
+    }
+
+    /// The **little endian** session key.
+    #[must_use]
+    pub const fn session_key(&self) -> &[u8; SESSION_KEY_LENGTH as usize] {
+        &self.session_key
+    }
+
+    /// Seconds since the Unix epoch when the ticket was issued.
+    #[must_use]
+    pub const fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Id of the realm the client chose.
+    #[must_use]
+    pub const fn realm_id(&self) -> u32 {
+        self.realm_id
+    }
+
+    /// Expansion of the client.
+    #[must_use]
+    pub const fn expansion(&self) -> Expansion {
+        self.expansion
+    }
+
+    /// Returns `true` if `now` is more than `max_age` after the ticket was issued.
+    ///
+    /// `now` is seconds since the Unix epoch.
+    /// Tickets issued in the future are not expired.
+    #[must_use]
+    pub const fn is_expired(&self, now: u64, max_age: Duration) -> bool {
+        now.saturating_sub(self.issued_at) > max_age.as_secs()
+    }
+
+    /// Canonical binary encoding.
+    ///
+    /// The layout is a version byte, the expansion, the realm id and issue time as
+    /// **little endian** integers, the session key, and the username prefixed by its length.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let username = self.username.as_ref().as_bytes();
+
+        let mut b = Vec::with_capacity(FIXED_LENGTH + 1 + username.len());
+        b.push(VERSION);
+        b.push(self.expansion.as_int());
+        b.extend_from_slice(&self.realm_id.to_le_bytes());
+        b.extend_from_slice(&self.issued_at.to_le_bytes());
+        b.extend_from_slice(&self.session_key);
+        // Normalized strings are at most 16 bytes
+        b.push(username.len() as u8);
+        b.extend_from_slice(username);
+
+        b
+    }
+
+    /// Parses the encoding from [`SessionTicket::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// If the encoding is invalid.
+    pub fn from_bytes(b: &[u8]) -> Result<Self, SessionTicketError> {
+        let invalid_length = || SessionTicketError::InvalidLength { length: b.len() };
+
+        if b.len() < FIXED_LENGTH + 1 {
+            return Err(invalid_length());
+        }
+        if b[0] != VERSION {
+            return Err(SessionTicketError::UnsupportedVersion(b[0]));
+        }
+
+        let expansion =
+            Expansion::from_int(b[1]).ok_or(SessionTicketError::InvalidExpansion(b[1]))?;
+        let realm_id = u32::from_le_bytes(b[2..6].try_into().map_err(|_| invalid_length())?);
+        let issued_at = u64::from_le_bytes(b[6..14].try_into().map_err(|_| invalid_length())?);
+        let session_key = b[14..FIXED_LENGTH]
+            .try_into()
+            .map_err(|_| invalid_length())?;
+
+        let username_length = b[FIXED_LENGTH] as usize;
+        let username = &b[FIXED_LENGTH + 1..];
+        if username.len() != username_length {
+            return Err(invalid_length());
+        }
+        let username = std::str::from_utf8(username)
+            .ok()
+            .and_then(|s| NormalizedString::new(s).ok())
+            .ok_or(SessionTicketError::InvalidUsername)?;
+
+        Ok(Self {
+            username,
+            session_key,
+            issued_at,
+            realm_id,
+            expansion,
+        })
+    }
+
+    /// [`SessionTicket::to_bytes`] followed by an HMAC-SHA1 of the bytes using `key`.
+    #[must_use]
+    pub fn to_signed_bytes(&self, key: &[u8]) -> Vec<u8> {
+        let mut b = self.to_bytes();
+        let mac = new_mac(key).chain_update(&b).finalize().into_bytes();
+        b.extend_from_slice(&mac);
+
+        b
+    }
+
+    /// Verifies the HMAC and parses the encoding from [`SessionTicket::to_signed_bytes`].
+    ///
+    /// The HMAC is compared in constant time.
+    ///
+    /// # Errors
+    ///
+    /// If the HMAC does not match `key` or the encoding is invalid.
+    pub fn from_signed_bytes(b: &[u8], key: &[u8]) -> Result<Self, SessionTicketError> {
+        let ticket_length = b
+            .len()
+            .checked_sub(SHA1_HASH_LENGTH as usize)
+            .ok_or(SessionTicketError::InvalidLength { length: b.len() })?;
+        let (ticket, mac) = b.split_at(ticket_length);
+
+        new_mac(key)
+            .chain_update(ticket)
+            .verify_slice(mac)
+            .map_err(|_| SessionTicketError::InvalidSignature)?;
+
+        Self::from_bytes(ticket)
+    }
+
+    /// [`SessionTicket::to_bytes`] as uppercase hex.
+    #[must_use]
+    pub fn to_hex(&self) -> String {
+        hex_encode_upper(&self.to_bytes())
+    }
+
+    /// Parses the encoding from [`SessionTicket::to_hex`]. Lowercase hex is also accepted.
+    ///
+    /// # Errors
+    ///
+    /// If the string is not hex or the encoding is invalid.
+    pub fn from_hex(s: &str) -> Result<Self, SessionTicketError> {
+        Self::from_bytes(&hex_decode(s)?)
+    }
+
+    /// [`SessionTicket::to_signed_bytes`] as uppercase hex.
+    #[must_use]
+    pub fn to_signed_hex(&self, key: &[u8]) -> String {
+        hex_encode_upper(&self.to_signed_bytes(key))
+    }
+
+    /// Parses the encoding from [`SessionTicket::to_signed_hex`]. Lowercase hex is also accepted.
+    ///
+    /// # Errors
+    ///
+    /// If the string is not hex, the HMAC does not match `key`, or the encoding is invalid.
+    pub fn from_signed_hex(s: &str, key: &[u8]) -> Result<Self, SessionTicketError> {
+        Self::from_signed_bytes(&hex_decode(s)?, key)
+    }
+
+    /// The session key as **big endian** uppercase hex, as stored in the `account.sessionkey`
+    /// column of `MaNGOS` and older `TrinityCore` versions.
+    ///
+    /// This is always 80 characters including leading zeros.
+    #[must_use]
+    pub fn session_key_big_endian_hex(&self) -> String {
+        let mut key = self.session_key;
+        key.reverse();
+
+        hex_encode_upper(&key)
+    }
+
+    /// Parses a **big endian** hex session key into the **little endian** array used by the crate.
+    ///
+    /// Leading zeros may be left out, since some cores do not write them.
+    ///
+    /// # Errors
+    ///
+    /// If the string is empty, is not hex, has more than 80 significant digits,
+    /// or if the session key is zero.
+    pub fn session_key_from_big_endian_hex(
+        s: &str,
+    ) -> Result<[u8; SESSION_KEY_LENGTH as usize], SessionTicketError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(SessionTicketError::InvalidHex);
+        }
+
+        let mut key = [0_u8; SESSION_KEY_LENGTH as usize];
+        try_hex_decode_big_endian(s, &mut key).map_err(|e| match e {
+            HexError::InvalidHex => SessionTicketError::InvalidHex,
+            HexError::TooLong { digits } => SessionTicketError::InvalidLength {
+                length: (digits + 1) / 2,
+            },
+        })?;
+
+        if key.iter().all(|b| *b == 0) {
+            return Err(SessionTicketError::SessionKeyIsZero);
+        }
+
+        Ok(key)
+    }
+}
+
+fn new_mac(key: &[u8]) -> Hmac<Sha1> {
+    // HMAC accepts keys of any length
+    Hmac::new_from_slice(key).unwrap()
+}
+
+fn hex_decode(s: &str) -> Result<Vec<u8>, SessionTicketError> {
+    try_hex_decode(s.trim()).map_err(|_| SessionTicketError::InvalidHex)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SessionTicketError;
+    use crate::normalized_string::NormalizedString;
+    use crate::session_ticket::SessionTicket;
+    use crate::Expansion;
+    use std::time::Duration;
+
+    const KEY: &[u8] = b"secret";
+
+    fn ticket() -> SessionTicket {
+        let mut session_key = [0_u8; 40];
+        for (i, b) in session_key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        SessionTicket::new(
+            NormalizedString::new("Alice").unwrap(),
+            session_key,
+            1_700_000_000,
+            0x0102_0304,
+            Expansion::Tbc,
+        )
+    }
+
+    #[test]
+    fn verify_encoding() {
+        let ticket = ticket();
+
+        let b = ticket.to_bytes();
+        assert_eq!(b.len(), 54 + 1 + 5);
+        assert_eq!(&b[..6], &[1, 1, 4, 3, 2, 1]);
+        assert_eq!(&b[55..], b"ALICE");
+        assert_eq!(SessionTicket::from_bytes(&b).unwrap(), ticket);
+
+        let hex = ticket.to_hex();
+        assert!(hex.starts_with("010104030201"));
+        assert_eq!(SessionTicket::from_hex(&hex).unwrap(), ticket);
+        assert_eq!(
+            SessionTicket::from_hex(&hex.to_lowercase()).unwrap(),
+            ticket
+        );
+
+        assert_eq!(
+            SessionTicket::from_bytes(&b[..b.len() - 1]),
+            Err(SessionTicketError::InvalidLength { length: 59 })
+        );
+        let mut invalid = b.clone();
+        invalid[0] = 2;
+        assert_eq!(
+            SessionTicket::from_bytes(&invalid),
+            Err(SessionTicketError::UnsupportedVersion(2))
+        );
+        let mut invalid = b.clone();
+        invalid[1] = 3;
+        assert_eq!(
+            SessionTicket::from_bytes(&invalid),
+            Err(SessionTicketError::InvalidExpansion(3))
+        );
+        let mut invalid = b;
+        invalid[55] = b'\n';
+        assert_eq!(
+            SessionTicket::from_bytes(&invalid),
+            Err(SessionTicketError::InvalidUsername)
+        );
+        assert_eq!(
+            SessionTicket::from_hex("0G"),
+            Err(SessionTicketError::InvalidHex)
+        );
+        assert_eq!(
+            SessionTicket::from_hex(&format!("+{}", &hex[1..])),
+            Err(SessionTicketError::InvalidHex)
+        );
+    }
+
+    #[test]
+    fn verify_signature() {
+        let ticket = ticket();
+
+        let b = ticket.to_signed_bytes(KEY);
+        assert_eq!(b.len(), ticket.to_bytes().len() + 20);
+        assert_eq!(SessionTicket::from_signed_bytes(&b, KEY).unwrap(), ticket);
+        assert_eq!(
+            SessionTicket::from_signed_hex(&ticket.to_signed_hex(KEY), KEY).unwrap(),
+            ticket
+        );
+
+        assert_eq!(
+            SessionTicket::from_signed_bytes(&b, b"other"),
+            Err(SessionTicketError::InvalidSignature)
+        );
+
+        // Moving the issue time forward invalidates the signature
+        let mut tampered = b.clone();
+        tampered[13] = 0xFF;
+        assert_eq!(
+            SessionTicket::from_signed_bytes(&tampered, KEY),
+            Err(SessionTicketError::InvalidSignature)
+        );
+
+        assert_eq!(
+            SessionTicket::from_signed_bytes(&b[..25], KEY),
+            Err(SessionTicketError::InvalidSignature)
+        );
+        assert_eq!(
+            SessionTicket::from_signed_bytes(&b[..19], KEY),
+            Err(SessionTicketError::InvalidLength { length: 19 })
+        );
+    }
+
+    #[test]
+    fn verify_expiry() {
+        let ticket = ticket();
+        let max_age = Duration::from_secs(60);
+
+        assert!(!ticket.is_expired(1_700_000_000, max_age));
+        assert!(!ticket.is_expired(1_700_000_060, max_age));
+        assert!(ticket.is_expired(1_700_000_061, max_age));
+        assert!(!ticket.is_expired(0, max_age));
+    }
+
+    #[test]
+    fn verify_big_endian_hex() {
+        let ticket = ticket();
+
+        let hex = ticket.session_key_big_endian_hex();
+        assert_eq!(hex.len(), 80);
+        assert!(hex.starts_with("2726252423"));
+        assert!(hex.ends_with("0403020100"));
+        assert_eq!(
+            &SessionTicket::session_key_from_big_endian_hex(&hex).unwrap(),
+            ticket.session_key()
+        );
+
+        let key = SessionTicket::session_key_from_big_endian_hex("abc").unwrap();
+        assert_eq!(&key[..3], &[0xBC, 0x0A, 0]);
+
+        assert_eq!(
+            SessionTicket::session_key_from_big_endian_hex(&format!("1{}", hex)),
+            Err(SessionTicketError::InvalidLength { length: 41 })
+        );
+        assert_eq!(
+            SessionTicket::session_key_from_big_endian_hex("XY"),
+            Err(SessionTicketError::InvalidHex)
+        );
+        assert_eq!(
+            SessionTicket::session_key_from_big_endian_hex("+A"),
+            Err(SessionTicketError::InvalidHex)
+        );
+        assert_eq!(
+            SessionTicket::session_key_from_big_endian_hex(""),
+            Err(SessionTicketError::InvalidHex)
+        );
+        assert_eq!(
+            SessionTicket::session_key_from_big_endian_hex("0000"),
+            Err(SessionTicketError::SessionKeyIsZero)
+        );
+    }
+}
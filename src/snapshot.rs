@@ -0,0 +1,217 @@
+//! Snapshots of header crypto state for handing a live connection to another process.
+//!
+//! Every crypto half in [`vanilla_header`](crate::vanilla_header), `tbc_header`, and `wrath_header`
+//! has a `snapshot` function that returns a [`CryptoSnapshot`] of the exact position of the cipher,
+//! and a `restore` function that creates a half that continues from that position.
+//!
+//! The snapshot can be converted to bytes through [`CryptoSnapshot::as_bytes`] and back through
+//! [`CryptoSnapshot::from_bytes`] in order to be sent alongside the socket.
+//! The format is versioned, so snapshots created by one version of this library
+//! will either be restored correctly or rejected by another.
+//!
+//! Snapshots contain key material that allows for decrypting the connection
+//! and should be treated as confidential as the session key.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::snapshot::CryptoSnapshot;
+//! use wow_srp::vanilla_header::EncrypterHalf;
+//!
+//! fn hand_off(encrypter: &EncrypterHalf) -> Vec<u8> {
+//!     // Send these bytes to the other process together with the socket
+//!     encrypter.snapshot().as_bytes().to_vec()
+//! }
+//!
+//! fn take_over(bytes: &[u8]) -> EncrypterHalf {
+//!     let snapshot = CryptoSnapshot::from_bytes(bytes).unwrap();
+//!     EncrypterHalf::restore(&snapshot).unwrap()
+//! }
+//! ```
+
+use crate::error::SnapshotError;
+
+/// Current version of the snapshot format.
+const VERSION: u8 = 1;
+
+/// Version and kind bytes.
+const PREFIX_LENGTH: usize = 2;
+
+/// Opaque state of a single crypto half.
+///
+/// Created through the `snapshot` function on the crypto halves and consumed through `restore`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct CryptoSnapshot {
+    bytes: Vec<u8>,
+}
+
+impl CryptoSnapshot {
+    /// Serialized form of the snapshot.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Parses bytes previously gotten from [`CryptoSnapshot::as_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// If the version is not supported by this version of the library or if the
+    /// bytes are not a valid snapshot.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let (version, kind) = match bytes {
+            [version, kind, ..] => (*version, *kind),
+            _ => return Err(SnapshotError::InvalidLength),
+        };
+
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion { version });
+        }
+
+        let kind = SnapshotKind::from_u8(kind).ok_or(SnapshotError::InvalidKind)?;
+        if bytes.len() != PREFIX_LENGTH + kind.payload_length() {
+            return Err(SnapshotError::InvalidLength);
+        }
+
+        Ok(Self {
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub(crate) fn new(kind: SnapshotKind, payload: &[&[u8]]) -> Self {
+        let mut bytes = Vec::with_capacity(PREFIX_LENGTH + kind.payload_length());
+        bytes.push(VERSION);
+        bytes.push(kind as u8);
+
+        for part in payload {
+            bytes.extend_from_slice(part);
+        }
+        debug_assert_eq!(bytes.len(), PREFIX_LENGTH + kind.payload_length());
+
+        Self { bytes }
+    }
+
+    /// Payload of the snapshot regardless of the kind of half that created it.
+    pub(crate) fn state(&self) -> &[u8] {
+        &self.bytes[PREFIX_LENGTH..]
+    }
+
+    /// Payload of the snapshot if it was created by the same kind of half.
+    pub(crate) fn payload(&self, kind: SnapshotKind) -> Result<&[u8], SnapshotError> {
+        if self.bytes[1] != kind as u8 {
+            return Err(SnapshotError::InvalidKind);
+        }
+
+        Ok(&self.bytes[PREFIX_LENGTH..])
+    }
+}
+
+/// Key, index, and previous value shared by Vanilla and TBC.
+///
+/// Returns an error if the index is outside of the key.
+pub(crate) fn restore_key_state(
+    snapshot: &CryptoSnapshot,
+    kind: SnapshotKind,
+) -> Result<(&[u8], u8, u8), SnapshotError> {
+    let payload = snapshot.payload(kind)?;
+    let (key, state) = payload.split_at(payload.len() - 2);
+    let (index, previous_value) = (state[0], state[1]);
+
+    if usize::from(index) >= key.len() {
+        return Err(SnapshotError::InvalidState);
+    }
+
+    Ok((key, index, previous_value))
+}
+
+/// The type of half that created the snapshot.
+///
+/// Values must never be reused since they are part of the serialized format.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub(crate) enum SnapshotKind {
+    VanillaEncrypter = 1,
+    VanillaDecrypter = 2,
+    TbcEncrypter = 3,
+    TbcDecrypter = 4,
+    WrathServerEncrypter = 5,
+    WrathServerDecrypter = 6,
+    WrathClientEncrypter = 7,
+    WrathClientDecrypter = 8,
+}
+
+impl SnapshotKind {
+    const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::VanillaEncrypter,
+            2 => Self::VanillaDecrypter,
+            3 => Self::TbcEncrypter,
+            4 => Self::TbcDecrypter,
+            5 => Self::WrathServerEncrypter,
+            6 => Self::WrathServerDecrypter,
+            7 => Self::WrathClientEncrypter,
+            8 => Self::WrathClientDecrypter,
+            _ => return None,
+        })
+    }
+
+    /// Length of the state without the version and kind.
+    const fn payload_length(self) -> usize {
+        match self {
+            // Session key, index, and previous value
+            Self::VanillaEncrypter | Self::VanillaDecrypter => 40 + 2,
+            // HMAC key, index, and previous value
+            Self::TbcEncrypter | Self::TbcDecrypter => 20 + 2,
+            // RC4 state, i, and j
+            Self::WrathServerEncrypter
+            | Self::WrathServerDecrypter
+            | Self::WrathClientEncrypter
+            | Self::WrathClientDecrypter => 256 + 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SnapshotError;
+    use crate::snapshot::{CryptoSnapshot, SnapshotKind, VERSION};
+
+    #[test]
+    fn verify_from_bytes() {
+        let snapshot = CryptoSnapshot::new(SnapshotKind::TbcDecrypter, &[&[0xAA; 20], &[3, 4]]);
+        assert_eq!(
+            CryptoSnapshot::from_bytes(snapshot.as_bytes()).unwrap(),
+            snapshot
+        );
+
+        let mut bytes = snapshot.as_bytes().to_vec();
+        bytes[0] = VERSION + 1;
+        assert_eq!(
+            CryptoSnapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion {
+                version: VERSION + 1
+            })
+        );
+
+        let mut bytes = snapshot.as_bytes().to_vec();
+        bytes[1] = 0;
+        assert_eq!(
+            CryptoSnapshot::from_bytes(&bytes),
+            Err(SnapshotError::InvalidKind)
+        );
+
+        let bytes = snapshot.as_bytes();
+        assert_eq!(
+            CryptoSnapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::InvalidLength)
+        );
+        assert_eq!(
+            CryptoSnapshot::from_bytes(&[]),
+            Err(SnapshotError::InvalidLength)
+        );
+
+        assert_eq!(
+            snapshot.payload(SnapshotKind::TbcEncrypter),
+            Err(SnapshotError::InvalidKind)
+        );
+    }
+}
@@ -42,7 +42,7 @@ const PRECALCULATED_XOR_HASH: [u8; SHA1_HASH_LENGTH as usize] = [
 /// * `username` (`U` in [RFC2945], `I` in [RFC5054]) is an **uppercase** UTF-8 encoded strings.
 /// * `password` (`p` in [RFC2945], `P` in [RFC5054]) is an **uppercase** UTF-8 encoded strings.
 /// * `salt` (`s` in [RFC2945] and [RFC5054]) is a **little endian** [32][`SALT_LENGTH_IN_BYTES`] byte array of random values.
-/// The client will not reject an authentication attempt with a salt of all zeros.
+///   The client will not reject an authentication attempt with a salt of all zeros.
 ///
 /// # Different Implementations
 ///
@@ -50,20 +50,42 @@ const PRECALCULATED_XOR_HASH: [u8; SHA1_HASH_LENGTH as usize] = [
 ///
 /// [RFC2945]: https://tools.ietf.org/html/rfc2945
 /// [RFC5054]: https://tools.ietf.org/html/rfc5054
+#[cfg(test)] // Only used for test vectors, the client uses the credentials hash directly
 pub fn calculate_x(
     username: &NormalizedString,
     password: &NormalizedString,
     salt: &Salt,
 ) -> Sha1Hash {
-    let p = Sha1::new()
+    let p = calculate_credentials_hash(username, password);
+
+    calculate_x_from_credentials_hash(&p, salt)
+}
+
+/// Calculate the intermediate `p = H( upper( username | : |  password ) )` value of [`calculate_x`].
+///
+/// This is the same as the `sha_pass_hash` column used by some other server implementations.
+/// The array is in the byte order of the SHA-1 output.
+pub fn calculate_credentials_hash(
+    username: &NormalizedString,
+    password: &NormalizedString,
+) -> [u8; SHA1_HASH_LENGTH as usize] {
+    Sha1::new()
         .chain_update(username.as_ref())
         .chain_update(":")
         .chain_update(password.as_ref())
-        .finalize();
+        .finalize()
+        .into()
+}
 
+/// Calculate `x` from the intermediate `p` value, see [`calculate_x`] and
+/// [`calculate_credentials_hash`].
+pub fn calculate_x_from_credentials_hash(
+    credentials_hash: &[u8; SHA1_HASH_LENGTH as usize],
+    salt: &Salt,
+) -> Sha1Hash {
     let x = Sha1::new()
         .chain_update(salt.as_le_bytes())
-        .chain_update(p)
+        .chain_update(credentials_hash)
         .finalize();
 
     Sha1Hash::from_le_bytes(x.into())
@@ -80,7 +102,7 @@ pub fn calculate_x(
 /// * `username` (`U` in [RFC2945], `I` in [RFC5054]) is an **uppercase** UTF-8 encoded strings.
 /// * `password` (`p` in [RFC2945], `P` in [RFC5054]) is an **uppercase** UTF-8 encoded strings.
 /// * `salt` (`s` in [RFC2945] and [RFC5054]) is a **little endian** [32 byte][`SALT_LENGTH_IN_BYTES`] array of random values.
-/// The client will not reject an authentication attempt with a salt of all zeros.
+///   The client will not reject an authentication attempt with a salt of all zeros.
 ///
 /// # Return value
 ///
@@ -98,7 +120,19 @@ pub fn calculate_password_verifier(
     salt: &Salt,
     // Return an array instead of Verifier because this is never directly used to create a Verifier
 ) -> [u8; PASSWORD_VERIFIER_LENGTH as usize] {
-    let x = calculate_x(username, password, salt).as_bigint();
+    calculate_password_verifier_from_credentials_hash(
+        &calculate_credentials_hash(username, password),
+        salt,
+    )
+}
+
+/// Calculate the password verifier `v` from the intermediate `p` value,
+/// see [`calculate_password_verifier`] and [`calculate_credentials_hash`].
+pub fn calculate_password_verifier_from_credentials_hash(
+    credentials_hash: &[u8; SHA1_HASH_LENGTH as usize],
+    salt: &Salt,
+) -> [u8; PASSWORD_VERIFIER_LENGTH as usize] {
+    let x = calculate_x_from_credentials_hash(credentials_hash, salt).as_bigint();
 
     let generator = Generator::default().to_bigint();
     let large_safe_prime = LargeSafePrime::default().to_bigint();
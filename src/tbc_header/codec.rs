@@ -0,0 +1,169 @@
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::tbc_header::{ClientFramer, DecrypterHalf, EncrypterHalf, ServerFramer};
+use crate::vanilla_header::codec::end_of_stream;
+use bytes::{Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// [`Decoder`] for servers that yields `(opcode, body)` frames sent by the client.
+///
+/// Wraps a [`ServerFramer`], so headers are only decrypted once the full header
+/// has been received.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ServerDecoder {
+    framer: ServerFramer,
+}
+
+impl ServerDecoder {
+    /// Creates a new decoder that rejects bodies larger than `maximum_body_size` bytes.
+    ///
+    /// The body size does not include the opcode.
+    #[must_use]
+    pub const fn new(decrypter: DecrypterHalf, maximum_body_size: u32) -> Self {
+        Self {
+            framer: ServerFramer::new(decrypter, maximum_body_size),
+        }
+    }
+}
+
+impl Decoder for ServerDecoder {
+    type Item = (u32, Bytes);
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.push(src);
+        src.clear();
+
+        let frame = self.framer.next_frame()?;
+        Ok(frame.map(|(opcode, body)| (opcode, body.into())))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame = self.decode(src)?;
+        end_of_stream(frame, self.framer.buffered_len())
+    }
+}
+
+/// [`Decoder`] for clients that yields `(opcode, body)` frames sent by the server.
+///
+/// Wraps a [`ClientFramer`], so headers are only decrypted once the full header
+/// has been received.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ClientDecoder {
+    framer: ClientFramer,
+}
+
+impl ClientDecoder {
+    /// Creates a new decoder that rejects bodies larger than `maximum_body_size` bytes.
+    ///
+    /// The body size does not include the opcode.
+    #[must_use]
+    pub const fn new(decrypter: DecrypterHalf, maximum_body_size: u32) -> Self {
+        Self {
+            framer: ClientFramer::new(decrypter, maximum_body_size),
+        }
+    }
+
+    /// Decompresses the bodies of messages that are compressed according to `compression`.
+    ///
+    /// See the [`compression`](crate::compression) module for more information.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.framer = self.framer.with_compression(compression);
+        self
+    }
+}
+
+impl Decoder for ClientDecoder {
+    type Item = (u16, Bytes);
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.push(src);
+        src.clear();
+
+        let frame = self.framer.next_frame()?;
+        Ok(frame.map(|(opcode, body)| (opcode, body.into())))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame = self.decode(src)?;
+        end_of_stream(frame, self.framer.buffered_len())
+    }
+}
+
+/// [`Encoder`] for servers that accepts `(opcode, body)` frames to send to the client.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ServerEncoder {
+    encrypter: EncrypterHalf,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
+}
+
+impl ServerEncoder {
+    /// Creates a new encoder.
+    #[must_use]
+    pub const fn new(encrypter: EncrypterHalf) -> Self {
+        Self {
+            encrypter,
+            #[cfg(feature = "compression")]
+            compression: None,
+        }
+    }
+
+    /// Compresses the bodies of messages that are compressed according to `compression`.
+    ///
+    /// See the [`compression`](crate::compression) module for more information.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+}
+
+impl Encoder<(u16, Bytes)> for ServerEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: (u16, Bytes), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (opcode, body) = item;
+
+        #[cfg(feature = "compression")]
+        if let Some(compression) = &self.compression {
+            self.encrypter
+                .put_compressed_server_packet(dst, opcode, &[&body], compression)?;
+            return Ok(());
+        }
+
+        self.encrypter.put_server_packet(dst, opcode, &[&body])?;
+
+        Ok(())
+    }
+}
+
+/// [`Encoder`] for clients that accepts `(opcode, body)` frames to send to the server.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ClientEncoder {
+    encrypter: EncrypterHalf,
+}
+
+impl ClientEncoder {
+    /// Creates a new encoder.
+    #[must_use]
+    pub const fn new(encrypter: EncrypterHalf) -> Self {
+        Self { encrypter }
+    }
+}
+
+impl Encoder<(u32, Bytes)> for ClientEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: (u32, Bytes), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (opcode, body) = item;
+        self.encrypter.put_client_packet(dst, opcode, &[&body])?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,11 @@
+use crate::desync::CipherFingerprint;
+use crate::desync::HeaderLimits;
+use crate::error::DesyncError;
+use crate::error::SnapshotError;
+use crate::framing::{CLIENT_OPCODE_LENGTH, SERVER_OPCODE_LENGTH};
+use crate::snapshot::{restore_key_state, CryptoSnapshot, SnapshotKind};
 use crate::tbc_header::{ClientHeader, ServerHeader, CLIENT_HEADER_LENGTH, SERVER_HEADER_LENGTH};
+use crate::vanilla_header::decrypt::plausible_at_offset;
 use crate::{PROOF_LENGTH, SESSION_KEY_LENGTH};
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
@@ -85,6 +92,109 @@ impl DecrypterHalf {
         ClientHeader { size, opcode }
     }
 
+    /// [`DecrypterHalf::decrypt_server_header`] that returns an error for headers outside of `limits`.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the decrypted header is implausible according to `limits`.
+    /// The decryption state is advanced past the header regardless.
+    pub fn decrypt_server_header_checked(
+        &mut self,
+        data: [u8; SERVER_HEADER_LENGTH as usize],
+        limits: &HeaderLimits,
+    ) -> Result<ServerHeader, DesyncError> {
+        let before = self.clone();
+        let header = self.decrypt_server_header(data);
+
+        let opcode_length = SERVER_OPCODE_LENGTH.into();
+        limits.check_or_search(
+            header.size.into(),
+            header.opcode.into(),
+            opcode_length,
+            false,
+            |offset| {
+                let header = before.at_offset(offset).decrypt_server_header(data);
+                plausible_at_offset(limits, header.size, header.opcode.into(), opcode_length)
+            },
+        )?;
+
+        Ok(header)
+    }
+
+    /// [`DecrypterHalf::decrypt_client_header`] that returns an error for headers outside of `limits`.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the decrypted header is implausible according to `limits`.
+    /// The decryption state is advanced past the header regardless.
+    pub fn decrypt_client_header_checked(
+        &mut self,
+        data: [u8; CLIENT_HEADER_LENGTH as usize],
+        limits: &HeaderLimits,
+    ) -> Result<ClientHeader, DesyncError> {
+        let before = self.clone();
+        let header = self.decrypt_client_header(data);
+
+        let opcode_length = CLIENT_OPCODE_LENGTH.into();
+        limits.check_or_search(
+            header.size.into(),
+            header.opcode,
+            opcode_length,
+            false,
+            |offset| {
+                let header = before.at_offset(offset).decrypt_client_header(data);
+                plausible_at_offset(limits, header.size, header.opcode, opcode_length)
+            },
+        )?;
+
+        Ok(header)
+    }
+
+    /// Copy of the half with the position in the key moved by `offset`.
+    fn at_offset(&self, offset: i32) -> Self {
+        let mut half = self.clone();
+        half.index = (i32::from(self.index) + offset).rem_euclid(PROOF_LENGTH.into()) as u8;
+        half
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        CryptoSnapshot::new(
+            SnapshotKind::TbcDecrypter,
+            &[&self.key, &[self.index, self.previous_value]],
+        )
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`DecrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        let (key, index, previous_value) = restore_key_state(snapshot, SnapshotKind::TbcDecrypter)?;
+
+        Ok(Self {
+            key: key.try_into().map_err(|_| SnapshotError::InvalidLength)?,
+            index,
+            previous_value,
+        })
+    }
+
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         const SEED_KEY_SIZE: usize = 16;
         let s: [u8; SEED_KEY_SIZE] = [
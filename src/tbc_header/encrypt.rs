@@ -1,4 +1,16 @@
-use crate::tbc_header::{CLIENT_HEADER_LENGTH, SERVER_HEADER_LENGTH};
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::desync::CipherFingerprint;
+use crate::error::{PacketSizeError, SnapshotError};
+use crate::snapshot::{restore_key_state, CryptoSnapshot, SnapshotKind};
+use crate::tbc_header::{
+    CLIENT_BODY_MAXIMUM_LENGTH, CLIENT_HEADER_LENGTH, SERVER_BODY_MAXIMUM_LENGTH,
+    SERVER_HEADER_LENGTH,
+};
+use crate::vanilla_header::encrypt::{client_size, server_size};
+#[cfg(feature = "tokio")]
+use crate::vanilla_header::packet::put_packet;
+use crate::vanilla_header::packet::{body_length, packet_buffer, write_packet};
 use crate::{PROOF_LENGTH, SESSION_KEY_LENGTH};
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
@@ -100,6 +112,178 @@ impl EncrypterHalf {
         header
     }
 
+    /// Writes an encrypted server header followed by `body` into the start of `buf`.
+    ///
+    /// The size field is calculated from the opcode and every part of `body`,
+    /// which is written in order.
+    /// Returns the amount of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`] or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    pub fn write_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        let length = body_length(body, SERVER_BODY_MAXIMUM_LENGTH)?;
+        let buf = packet_buffer(buf, SERVER_HEADER_LENGTH as usize + length)?;
+
+        let header = self.encrypt_server_header(server_size(length), opcode);
+        write_packet(buf, &header, body);
+
+        Ok(buf.len())
+    }
+
+    /// Writes an encrypted client header followed by `body` into the start of `buf`.
+    ///
+    /// The size field is calculated from the opcode and every part of `body`,
+    /// which is written in order.
+    /// Returns the amount of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`CLIENT_BODY_MAXIMUM_LENGTH`] or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    pub fn write_client_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        let length = body_length(body, CLIENT_BODY_MAXIMUM_LENGTH)?;
+        let buf = packet_buffer(buf, CLIENT_HEADER_LENGTH as usize + length)?;
+
+        let header = self.encrypt_client_header(client_size(length), opcode);
+        write_packet(buf, &header, body);
+
+        Ok(buf.len())
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "tokio")]
+    pub fn put_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        let length = body_length(body, SERVER_BODY_MAXIMUM_LENGTH)?;
+
+        let header = self.encrypt_server_header(server_size(length), opcode);
+        put_packet(dst, &header, body);
+
+        Ok(())
+    }
+
+    /// Same as [`Self::write_server_packet`] but first compresses `body` if `opcode` is compressed
+    /// according to `compression`.
+    ///
+    /// The size field is calculated from the compressed body.
+    /// See the [`compression`](crate::compression) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than the [maximum decompressed size](Compression::maximum_decompressed_size),
+    /// if the compressed body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`], or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "compression")]
+    pub fn write_compressed_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<usize, PacketSizeError> {
+        match compression.compress_packet_body(opcode, body)? {
+            Some(compressed) => self.write_server_packet(buf, opcode, &[&compressed]),
+            None => self.write_server_packet(buf, opcode, body),
+        }
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than the [maximum decompressed size](Compression::maximum_decompressed_size)
+    /// or if the compressed body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    pub fn put_compressed_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<(), PacketSizeError> {
+        match compression.compress_packet_body(opcode, body)? {
+            Some(compressed) => self.put_server_packet(dst, opcode, &[&compressed]),
+            None => self.put_server_packet(dst, opcode, body),
+        }
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`CLIENT_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "tokio")]
+    pub fn put_client_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        let length = body_length(body, CLIENT_BODY_MAXIMUM_LENGTH)?;
+
+        let header = self.encrypt_client_header(client_size(length), opcode);
+        put_packet(dst, &header, body);
+
+        Ok(())
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        CryptoSnapshot::new(
+            SnapshotKind::TbcEncrypter,
+            &[&self.key, &[self.index, self.previous_value]],
+        )
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`EncrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        let (key, index, previous_value) = restore_key_state(snapshot, SnapshotKind::TbcEncrypter)?;
+
+        Ok(Self {
+            key: key.try_into().map_err(|_| SnapshotError::InvalidLength)?,
+            index,
+            previous_value,
+        })
+    }
+
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         const SEED_KEY_SIZE: usize = 16;
         let s: [u8; SEED_KEY_SIZE] = [
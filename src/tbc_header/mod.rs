@@ -28,7 +28,7 @@
 //! 4. Retrieve the session key from the login server.
 //! 5. Create the [`HeaderCrypto`] struct through [`ProofSeed::into_server_header_crypto`].
 //! 6. Optionally, split the [`HeaderCrypto`] into [`EncrypterHalf`] and [`DecrypterHalf`] through
-//! [`HeaderCrypto::split`].
+//!    [`HeaderCrypto::split`].
 //!
 //! In a diagram this would look like:
 //! ```text
@@ -105,6 +105,16 @@
 //!
 //! ```
 //!
+//! # Framing
+//!
+//! Servers and clients that do not use blocking [`Read`](std::io::Read) can use the
+//! [`ServerFramer`] and [`ClientFramer`] instead. These take bytes in whatever chunks
+//! they arrive in from the socket and return complete `(opcode, body)` frames.
+//!
+//! With the `tokio` feature enabled, `ServerDecoder`, `ServerEncoder`, `ClientDecoder`, and
+//! `ClientEncoder` implement the [`tokio-util`](https://docs.rs/tokio-util) codec traits
+//! for use with `FramedRead` and `FramedWrite` on the halves of a split connection.
+//!
 //! [World Packet]: https://wowdev.wiki/World_Packet
 //! [Login Packets]: https://wowdev.wiki/Login_Packet
 //! [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
@@ -112,17 +122,26 @@
 
 use std::io::{Read, Write};
 
+#[cfg(feature = "tokio")]
+pub use codec::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
 pub use decrypt::DecrypterHalf;
 pub use encrypt::EncrypterHalf;
+pub use framer::ClientFramer;
+pub use framer::ServerFramer;
 
-use crate::error::MatchProofsError;
-use crate::key::{Proof, SessionKey};
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::{MatchProofsError, PacketSizeError, RngHealthError};
+use crate::key::{random_proof_seed, try_random_proof_seed, Proof, SessionKey};
 use crate::normalized_string::NormalizedString;
+use crate::trace::{enter_span, proof_mismatch};
 use crate::{PROOF_LENGTH, SESSION_KEY_LENGTH};
-use rand::{thread_rng, RngCore};
 
+#[cfg(feature = "tokio")]
+mod codec;
 pub(crate) mod decrypt;
 pub(crate) mod encrypt;
+mod framer;
 
 use crate::vanilla_header::calculate_world_server_proof;
 
@@ -141,6 +160,15 @@ pub const CLIENT_HEADER_LENGTH: u8 =
 pub const SERVER_HEADER_LENGTH: u8 =
     (std::mem::size_of::<u16>() + std::mem::size_of::<u16>()) as u8;
 
+/// Largest body in bytes that fits in a server header.
+///
+/// The size field is 2 bytes and includes the 2 byte opcode.
+pub const SERVER_BODY_MAXIMUM_LENGTH: u32 = u16::MAX as u32 - 2;
+/// Largest body in bytes that fits in a client header.
+///
+/// The size field is 2 bytes and includes the 4 byte opcode.
+pub const CLIENT_BODY_MAXIMUM_LENGTH: u32 = u16::MAX as u32 - 4;
+
 /// Decrypted values from a server.
 ///
 /// Gotten from either
@@ -257,6 +285,98 @@ impl HeaderCrypto {
         self.encrypt.encrypt_client_header(size, opcode)
     }
 
+    /// Convenience wrapper for [`EncrypterHalf::write_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_server_packet`].
+    pub fn write_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_server_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_server_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_server_packet(dst, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::write_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_compressed_server_packet`].
+    #[cfg(feature = "compression")]
+    pub fn write_compressed_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt
+            .write_compressed_server_packet(buf, opcode, body, compression)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_compressed_server_packet`].
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    pub fn put_compressed_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt
+            .put_compressed_server_packet(dst, opcode, body, compression)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::write_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_client_packet`].
+    pub fn write_client_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_client_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_client_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_client_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_client_packet(dst, opcode, body)
+    }
+
     /// Use either [the client](Self::read_and_decrypt_client_header)
     /// or [the server](Self::read_and_decrypt_server_header)
     /// [`Read`](std::io::Read) functions, or
@@ -360,8 +480,27 @@ impl ProofSeed {
         Self::default()
     }
 
+    /// Same as [`ProofSeed::new`] but fails if the seed does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the seed fails them.
+    pub fn try_new() -> Result<Self, RngHealthError> {
+        Ok(Self {
+            seed: try_random_proof_seed()?,
+        })
+    }
+
+    /// Server seed issued by a [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard).
+    pub(crate) const fn from_issued(server_seed: crate::vanilla_header::ProofSeed) -> Self {
+        Self {
+            seed: server_seed.seed(),
+        }
+    }
+
     #[cfg(test)]
-    const fn from_specific_seed(server_seed: u32) -> Self {
+    pub(crate) const fn from_specific_seed(server_seed: u32) -> Self {
         Self { seed: server_seed }
     }
 
@@ -417,6 +556,14 @@ impl ProofSeed {
         client_proof: [u8; PROOF_LENGTH as _],
         client_seed: u32,
     ) -> Result<HeaderCrypto, MatchProofsError> {
+        enter_span!(
+            "verify_world_proof",
+            username = %username,
+            expansion = "tbc",
+            server_seed = self.seed,
+            client_seed
+        );
+
         let server_proof = calculate_world_server_proof(
             username,
             &SessionKey::from_le_bytes(session_key),
@@ -425,6 +572,7 @@ impl ProofSeed {
         );
 
         if server_proof != Proof::from_le_bytes(client_proof) {
+            proof_mismatch!(client_proof);
             return Err(MatchProofsError {
                 client_proof,
                 server_proof: *server_proof.as_le_bytes(),
@@ -438,7 +586,7 @@ impl ProofSeed {
 impl Default for ProofSeed {
     fn default() -> Self {
         Self {
-            seed: thread_rng().next_u32(),
+            seed: random_proof_seed(),
         }
     }
 }
@@ -447,10 +595,13 @@ impl Default for ProofSeed {
 mod test {
     use std::fs::read_to_string;
 
+    use crate::error::PacketSizeError;
     use crate::hex::*;
     use crate::key::SessionKey;
     use crate::normalized_string::NormalizedString;
-    use crate::tbc_header::{HeaderCrypto, ProofSeed};
+    use crate::tbc_header::{
+        ClientFramer, HeaderCrypto, ProofSeed, ServerFramer, SERVER_BODY_MAXIMUM_LENGTH,
+    };
     use crate::SESSION_KEY_LENGTH;
     use std::convert::TryInto;
 
@@ -781,4 +932,340 @@ mod test {
             u16::from_le_bytes(decrypt_data[18..20].try_into().unwrap())
         );
     }
+
+    #[test]
+    fn verify_framer() {
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (mut encrypter, _) = HeaderCrypto::new(session_key).split();
+
+        let mut client_stream = Vec::new();
+        encrypter
+            .write_encrypted_client_header(&mut client_stream, 4 + 3, 0x1DC)
+            .unwrap();
+        client_stream.extend_from_slice(&[0xAA; 3]);
+
+        let mut server_stream = Vec::new();
+        encrypter
+            .write_encrypted_server_header(&mut server_stream, 2 + 5, 0x1DD)
+            .unwrap();
+        server_stream.extend_from_slice(&[0xBB; 5]);
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut server_framer = ServerFramer::new(decrypter, 16);
+        for chunk in client_stream.chunks(4) {
+            assert_eq!(server_framer.next_frame().unwrap(), None);
+            server_framer.push(chunk);
+        }
+        assert_eq!(
+            server_framer.next_frame().unwrap(),
+            Some((0x1DC, vec![0xAA; 3]))
+        );
+
+        // The decryption state continues from the client header
+        let decrypter = {
+            let (_, mut decrypter) = HeaderCrypto::new(session_key).split();
+            decrypter.decrypt(&mut client_stream[..6]);
+            decrypter
+        };
+        let mut client_framer = ClientFramer::new(decrypter, 16);
+        client_framer.push(&server_stream);
+        assert_eq!(
+            client_framer.next_frame().unwrap(),
+            Some((0x1DD, vec![0xBB; 5]))
+        );
+        assert_eq!(client_framer.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn verify_codec() {
+        use crate::tbc_header::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
+        use bytes::{Bytes, BytesMut};
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut encoder = ClientEncoder::new(encrypter);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x1DC, Bytes::from_static(&[0xAA; 3])), &mut stream)
+            .unwrap();
+        encoder.encode((0x37, Bytes::new()), &mut stream).unwrap();
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut decoder = ServerDecoder::new(decrypter, 16);
+        let mut frames = Vec::new();
+        let mut buf = BytesMut::new();
+        for chunk in stream.chunks(4) {
+            buf.extend_from_slice(chunk);
+            while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (0x1DC, Bytes::from_static(&[0xAA; 3])),
+                (0x37, Bytes::new())
+            ]
+        );
+
+        let (encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut encoder = ServerEncoder::new(encrypter);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x1DD, Bytes::from_static(&[0xBB; 4])), &mut stream)
+            .unwrap();
+        assert!(encoder
+            .encode((0x1DD, Bytes::from(vec![0; 0xFFFF])), &mut stream)
+            .is_err());
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut decoder = ClientDecoder::new(decrypter, 16);
+        let mut partial = stream.split_to(stream.len() - 1);
+        assert_eq!(decoder.decode(&mut partial).unwrap(), None);
+        assert!(decoder.decode_eof(&mut BytesMut::new()).is_err());
+        assert_eq!(
+            decoder.decode_eof(&mut stream).unwrap(),
+            Some((0x1DD, Bytes::from_static(&[0xBB; 4])))
+        );
+    }
+
+    #[test]
+    fn verify_packet_writer() {
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (mut expected, _) = HeaderCrypto::new(session_key).split();
+        let mut expected_packet = expected.encrypt_server_header(2 + 5, 0x1DD).to_vec();
+        expected_packet.extend_from_slice(&[1, 2, 3, 4, 5]);
+        expected_packet.extend_from_slice(&expected.encrypt_client_header(4, 0x37));
+
+        let (mut encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut buf = [0_u8; 16];
+
+        assert_eq!(
+            encrypter.write_server_packet(&mut buf[..8], 0x1DD, &[&[1, 2], &[3, 4, 5]]),
+            Err(PacketSizeError::BufferTooSmall {
+                required: 9,
+                available: 8
+            })
+        );
+        let body = vec![0_u8; SERVER_BODY_MAXIMUM_LENGTH as usize];
+        assert_eq!(
+            encrypter.write_server_packet(&mut buf, 0x1DD, &[&body, &[0]]),
+            Err(PacketSizeError::BodyTooLarge {
+                size: SERVER_BODY_MAXIMUM_LENGTH as usize + 1,
+                maximum: SERVER_BODY_MAXIMUM_LENGTH as usize
+            })
+        );
+
+        let written = encrypter
+            .write_server_packet(&mut buf, 0x1DD, &[&[1, 2], &[3, 4, 5]])
+            .unwrap();
+        assert_eq!(written, 9);
+        let written = written
+            + encrypter
+                .write_client_packet(&mut buf[written..], 0x37, &[])
+                .unwrap();
+
+        assert_eq!(&buf[..written], expected_packet.as_slice());
+    }
+
+    #[test]
+    fn verify_snapshot() {
+        use crate::error::SnapshotError;
+        use crate::snapshot::CryptoSnapshot;
+        use crate::tbc_header::{DecrypterHalf, EncrypterHalf};
+
+        fn hand_off(snapshot: CryptoSnapshot) -> CryptoSnapshot {
+            CryptoSnapshot::from_bytes(snapshot.as_bytes()).unwrap()
+        }
+
+        let contents = read_to_string("tests/encryption/calculate_tbc_encrypt_values.txt").unwrap();
+
+        for line in contents.lines() {
+            let mut line = line.split_whitespace();
+
+            let session_key = SessionKey::from_be_hex_str(line.next().unwrap());
+            let mut data = hex_decode(line.next().unwrap());
+            let expected_client = hex_decode(line.next().unwrap());
+            let expected_server = hex_decode(line.next().unwrap());
+
+            let original_data = data.clone();
+            let middle = data.len() / 2;
+
+            let (mut client_enc, mut client_dec) =
+                HeaderCrypto::new(*session_key.as_le_bytes()).split();
+            let (mut server_enc, mut server_dec) =
+                HeaderCrypto::new(*session_key.as_le_bytes()).split();
+
+            client_enc.encrypt(&mut data[..middle]);
+            server_dec.decrypt(&mut data[..middle]);
+            let mut client_enc = EncrypterHalf::restore(&hand_off(client_enc.snapshot())).unwrap();
+            let mut server_dec = DecrypterHalf::restore(&hand_off(server_dec.snapshot())).unwrap();
+
+            client_enc.encrypt(&mut data[middle..]);
+            assert_eq!(data[middle..], expected_client[middle..]);
+            server_dec.decrypt(&mut data[middle..]);
+            assert_eq!(data, original_data);
+
+            server_enc.encrypt(&mut data[..middle]);
+            client_dec.decrypt(&mut data[..middle]);
+            let mut server_enc = EncrypterHalf::restore(&hand_off(server_enc.snapshot())).unwrap();
+            let mut client_dec = DecrypterHalf::restore(&hand_off(client_dec.snapshot())).unwrap();
+
+            server_enc.encrypt(&mut data[middle..]);
+            assert_eq!(data[middle..], expected_server[middle..]);
+            client_dec.decrypt(&mut data[middle..]);
+            assert_eq!(data, original_data);
+        }
+
+        let (enc, _) = HeaderCrypto::new([7; SESSION_KEY_LENGTH as usize]).split();
+        assert_eq!(
+            DecrypterHalf::restore(&enc.snapshot()),
+            Err(SnapshotError::InvalidKind)
+        );
+    }
+
+    #[test]
+    fn verify_desync() {
+        use crate::desync::HeaderLimits;
+        use crate::error::ImplausibleHeader;
+        use crate::tbc_header::ClientHeader;
+
+        let mut session_key = [0_u8; SESSION_KEY_LENGTH as usize];
+        for (i, b) in session_key.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(97).wrapping_add(13);
+        }
+        let limits = HeaderLimits::new(0x1000, 0, 0x500).with_search_distance(10);
+
+        let (mut client, _) = HeaderCrypto::new(session_key).split();
+        let (_, mut server) = HeaderCrypto::new(session_key).split();
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        let header = client.encrypt_client_header(8, 0x1ED);
+        assert_eq!(
+            server.decrypt_client_header_checked(header, &limits),
+            Ok(ClientHeader {
+                size: 8,
+                opcode: 0x1ED
+            })
+        );
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        // Header encrypted but never sent
+        let _ = client.encrypt_client_header(4, 0x37);
+        let header = client.encrypt_client_header(4, 0x38);
+        let error = server
+            .decrypt_client_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(6));
+        assert_ne!(client.fingerprint(), server.fingerprint());
+
+        // Header decrypted twice
+        let (mut server, mut client) = HeaderCrypto::new(session_key).split();
+        let header = server.encrypt_server_header(2, 0x1EC);
+        let _ = client.decrypt_server_header(header);
+        let _ = client.decrypt_server_header(header);
+        let header = server.encrypt_server_header(6, 0x1EE);
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(-4));
+
+        let limits = HeaderLimits::new(0x1000, 0x10, 0x20);
+        let (mut server, mut client) = HeaderCrypto::new(session_key).split();
+        let header = server.encrypt_server_header(2, 0x1EC);
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(
+            error.reason,
+            ImplausibleHeader::OpcodeOutOfRange {
+                minimum: 0x10,
+                maximum: 0x20
+            }
+        );
+        assert_eq!(error.offset, None);
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn verify_compression() {
+        use crate::compression::{Compression, SMSG_COMPRESSED_UPDATE_OBJECT};
+        use crate::error::{CompressionError, FrameError, PacketSizeError};
+
+        let session_key = [7_u8; crate::SESSION_KEY_LENGTH as usize];
+        let compression = Compression::new(0x10000);
+        let update_object: Vec<u8> = (0..0x9000_u32).map(|i| (i / 64) as u8).collect();
+
+        let (mut encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut stream = vec![0_u8; 0x1000];
+        assert_eq!(
+            encrypter.write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[&update_object],
+                &Compression::new(0x100),
+            ),
+            Err(PacketSizeError::BodyTooLarge {
+                size: 0x9000,
+                maximum: 0x100
+            })
+        );
+
+        let (first, second) = update_object.split_at(0x4000);
+        let mut length = encrypter
+            .write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[first, second],
+                &compression,
+            )
+            .unwrap();
+        length += encrypter
+            .write_compressed_server_packet(
+                &mut stream[length..],
+                0x1EE,
+                &[&[1, 2, 3]],
+                &compression,
+            )
+            .unwrap();
+        stream.truncate(length);
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(decrypter, 0x1000).with_compression(compression);
+        framer.push(&stream);
+        assert_eq!(
+            framer.next_frame().unwrap(),
+            Some((SMSG_COMPRESSED_UPDATE_OBJECT, update_object))
+        );
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1EE, vec![1, 2, 3])));
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut framer =
+            ClientFramer::new(decrypter, 0x1000).with_compression(Compression::new(0x100));
+        framer.push(&stream);
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::InvalidCompressedBody {
+                opcode: SMSG_COMPRESSED_UPDATE_OBJECT,
+                error: CompressionError::DecompressedTooLarge {
+                    size: 0x9000,
+                    maximum: 0x100
+                }
+            })
+        ));
+    }
 }
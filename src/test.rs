@@ -1,4 +1,4 @@
-use crate::client::SrpClientUser;
+use crate::client::{CredentialsHash, SrpClientUser};
 use crate::normalized_string::NormalizedString;
 use crate::server::SrpVerifier;
 use crate::{PublicKey, GENERATOR, LARGE_SAFE_PRIME_LITTLE_ENDIAN};
@@ -82,3 +82,113 @@ fn authenticate_with_self() {
 
     assert!(verified);
 }
+
+#[test]
+fn authenticate_with_credentials_hash() {
+    let username = NormalizedString::new("Alice").unwrap();
+    let password = NormalizedString::new("password123").unwrap();
+
+    let credentials_hash = CredentialsHash::new(&username, &password);
+    // SHA1("ALICE:PASSWORD123")
+    assert_eq!(
+        crate::hex::hex_encode_upper(credentials_hash.as_bytes()),
+        "A9580A42C739DA2DD0348F037915039CF6D0CA9F"
+    );
+
+    let stored = *credentials_hash.as_bytes();
+    let client =
+        SrpClientUser::from_credentials_hash(username.clone(), CredentialsHash::from_bytes(stored));
+    assert_eq!(client.credentials_hash(), &credentials_hash);
+
+    let server = SrpVerifier::from_username_and_password(username, password).into_proof();
+    let client = client.into_challenge(
+        GENERATOR,
+        LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+        PublicKey::from_le_bytes(*server.server_public_key()).unwrap(),
+        *server.salt(),
+    );
+
+    let (server, server_proof) = server
+        .into_server(
+            PublicKey::from_le_bytes(*client.client_public_key()).unwrap(),
+            *client.client_proof(),
+        )
+        .unwrap();
+    let client = client.verify_server_proof(server_proof).unwrap();
+
+    assert_eq!(*server.session_key(), client.session_key());
+}
+
+#[test]
+fn world_crypto_from_session() {
+    fn authenticate() -> (crate::client::SrpClient, crate::server::SrpServer) {
+        let username = NormalizedString::new("A").unwrap();
+        let client = SrpClientUser::new(username.clone(), username.clone());
+        let server =
+            SrpVerifier::from_username_and_password(username.clone(), username).into_proof();
+
+        let client = client.into_challenge(
+            GENERATOR,
+            LARGE_SAFE_PRIME_LITTLE_ENDIAN,
+            PublicKey::from_le_bytes(*server.server_public_key()).unwrap(),
+            *server.salt(),
+        );
+        let (server, server_proof) = server
+            .into_server(
+                PublicKey::from_le_bytes(*client.client_public_key()).unwrap(),
+                *client.client_proof(),
+            )
+            .unwrap();
+
+        (client.verify_server_proof(server_proof).unwrap(), server)
+    }
+
+    let (client, server) = authenticate();
+    let seed = crate::vanilla_header::ProofSeed::new();
+    let (client_seed, client_proof, mut client_crypto) =
+        client.into_vanilla_client_crypto(seed.seed());
+    let mut server_crypto = server
+        .into_vanilla_server_crypto(seed, client_proof, client_seed)
+        .unwrap();
+    let header = server_crypto.encrypt_server_header(10, 0x1EE);
+    let header = client_crypto.decrypt_server_header(header);
+    assert_eq!((header.size, header.opcode), (10, 0x1EE));
+
+    let (client, server) = authenticate();
+    let seed = crate::vanilla_header::ProofSeed::new();
+    let (client_seed, mut client_proof, _) = client.into_vanilla_client_crypto(seed.seed());
+    client_proof[0] ^= 1;
+    assert!(server
+        .into_vanilla_server_crypto(seed, client_proof, client_seed)
+        .is_err());
+
+    #[cfg(feature = "tbc-header")]
+    {
+        let (client, server) = authenticate();
+        let seed = crate::tbc_header::ProofSeed::new();
+        let (client_seed, client_proof, mut client_crypto) =
+            client.into_tbc_client_crypto(seed.seed());
+        let mut server_crypto = server
+            .into_tbc_server_crypto(seed, client_proof, client_seed)
+            .unwrap();
+        let header = server_crypto.encrypt_server_header(10, 0x1EE);
+        let header = client_crypto.decrypt_server_header(header);
+        assert_eq!((header.size, header.opcode), (10, 0x1EE));
+    }
+
+    #[cfg(feature = "wrath-header")]
+    {
+        let (client, server) = authenticate();
+        let seed = crate::wrath_header::ProofSeed::new();
+        let (client_seed, client_proof, mut client_crypto) =
+            client.into_wrath_client_crypto(seed.seed());
+        let mut server_crypto = server
+            .into_wrath_server_crypto(seed, client_proof, client_seed)
+            .unwrap();
+        let header = server_crypto.encrypt_server_header(10, 0x1EE).to_vec();
+        let (header, _) = client_crypto
+            .decrypt_server_header_from_buffer(&header)
+            .unwrap();
+        assert_eq!((header.size, header.opcode), (10, 0x1EE));
+    }
+}
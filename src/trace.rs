@@ -0,0 +1,73 @@
+//! Helpers for the optional `tracing` instrumentation.
+//!
+//! Everything expands to nothing when `tracing` is not enabled.
+//! Only usernames, public values, and timings are recorded, never keys, verifiers, or the
+//! proofs calculated by the server.
+
+/// Name of the bigint backend used for the SRP math.
+#[cfg(all(feature = "tracing", feature = "srp-fast-math"))]
+pub(crate) const BACKEND: &str = "rug";
+#[cfg(all(
+    feature = "tracing",
+    feature = "srp-default-math",
+    not(feature = "srp-fast-math")
+))]
+pub(crate) const BACKEND: &str = "num-bigint";
+
+/// Enters a `DEBUG` span for the rest of the enclosing scope.
+macro_rules! enter_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $($fields)*)?).entered();
+    };
+}
+pub(crate) use enter_span;
+
+/// Evaluates the bigint calculation `$e` and records how long it took.
+#[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
+macro_rules! timed_math {
+    ($operation:literal, $e:expr) => {{
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+
+        let value = $e;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            operation = $operation,
+            backend = crate::trace::BACKEND,
+            elapsed_us = start.elapsed().as_micros() as u64,
+            "bigint calculation"
+        );
+
+        value
+    }};
+}
+#[cfg(any(feature = "srp-default-math", feature = "srp-fast-math"))]
+pub(crate) use timed_math;
+
+/// Records a `WARN` event for proofs that do not match.
+///
+/// Only the proof received over the network is included since the locally calculated
+/// proof is derived from the session key.
+macro_rules! proof_mismatch {
+    ($received_proof:expr) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            received_proof = %crate::trace::Hex(&$received_proof),
+            "proofs do not match"
+        );
+    };
+}
+pub(crate) use proof_mismatch;
+
+/// Formats bytes as lowercase hex.
+#[cfg(feature = "tracing")]
+pub(crate) struct Hex<'a>(pub(crate) &'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&crate::hex::hex_encode(self.0))
+    }
+}
@@ -28,7 +28,7 @@
 //! 4. Retrieve the session key from the login server.
 //! 5. Create the [`HeaderCrypto`] struct through [`ProofSeed::into_server_header_crypto`].
 //! 6. Optionally, split the [`HeaderCrypto`] into [`EncrypterHalf`] and [`DecrypterHalf`] through
-//! [`HeaderCrypto::split`].
+//!    [`HeaderCrypto::split`].
 //! 7. Optionally, unsplit them through [`EncrypterHalf::unsplit`].
 //!
 //! In a diagram this would look like:
@@ -106,6 +106,16 @@
 //!
 //! ```
 //!
+//! # Framing
+//!
+//! Servers and clients that do not use blocking [`Read`](std::io::Read) can use the
+//! [`ServerFramer`] and [`ClientFramer`] instead. These take bytes in whatever chunks
+//! they arrive in from the socket and return complete `(opcode, body)` frames.
+//!
+//! With the `tokio` feature enabled, `ServerDecoder`, `ServerEncoder`, `ClientDecoder`, and
+//! `ClientEncoder` implement the [`tokio-util`](https://docs.rs/tokio-util) codec traits
+//! for use with `FramedRead` and `FramedWrite` on the halves of a split connection.
+//!
 //! [World Packet]: https://wowdev.wiki/World_Packet
 //! [Login Packets]: https://wowdev.wiki/Login_Packet
 //! [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
@@ -113,18 +123,28 @@
 
 use std::io::{Read, Write};
 
+#[cfg(feature = "tokio")]
+pub use codec::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
 pub use decrypt::DecrypterHalf;
 pub use encrypt::EncrypterHalf;
+pub use framer::ClientFramer;
+pub use framer::ServerFramer;
 
-use crate::error::MatchProofsError;
-use crate::key::{Proof, SessionKey};
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::{MatchProofsError, PacketSizeError, RngHealthError};
+use crate::key::{random_proof_seed, try_random_proof_seed, Proof, SessionKey};
 use crate::normalized_string::NormalizedString;
+use crate::trace::{enter_span, proof_mismatch};
 use crate::{PROOF_LENGTH, SESSION_KEY_LENGTH};
-use rand::{thread_rng, RngCore};
 
+#[cfg(feature = "tokio")]
+pub(crate) mod codec;
 pub(crate) mod decrypt;
 pub(crate) mod encrypt;
+pub(crate) mod framer;
 mod internal;
+pub(crate) mod packet;
 
 pub(crate) use internal::calculate_world_server_proof;
 
@@ -143,6 +163,15 @@ pub const CLIENT_HEADER_LENGTH: u8 =
 pub const SERVER_HEADER_LENGTH: u8 =
     (std::mem::size_of::<u16>() + std::mem::size_of::<u16>()) as u8;
 
+/// Largest body in bytes that fits in a server header.
+///
+/// The size field is 2 bytes and includes the 2 byte opcode.
+pub const SERVER_BODY_MAXIMUM_LENGTH: u32 = u16::MAX as u32 - 2;
+/// Largest body in bytes that fits in a client header.
+///
+/// The size field is 2 bytes and includes the 4 byte opcode.
+pub const CLIENT_BODY_MAXIMUM_LENGTH: u32 = u16::MAX as u32 - 4;
+
 /// Decrypted values from a server.
 ///
 /// Gotten from either
@@ -259,6 +288,98 @@ impl HeaderCrypto {
         self.encrypt.encrypt_client_header(size, opcode)
     }
 
+    /// Convenience wrapper for [`EncrypterHalf::write_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_server_packet`].
+    pub fn write_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_server_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_server_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_server_packet(dst, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::write_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_compressed_server_packet`].
+    #[cfg(feature = "compression")]
+    pub fn write_compressed_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt
+            .write_compressed_server_packet(buf, opcode, body, compression)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_compressed_server_packet`].
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    pub fn put_compressed_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt
+            .put_compressed_server_packet(dst, opcode, body, compression)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::write_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::write_client_packet`].
+    pub fn write_client_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_client_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`EncrypterHalf::put_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`EncrypterHalf::put_client_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_client_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_client_packet(dst, opcode, body)
+    }
+
     /// Use either [the client](Self::read_and_decrypt_client_header)
     /// or [the server](Self::read_and_decrypt_server_header)
     /// [`Read`](std::io::Read) functions, or
@@ -361,8 +482,20 @@ impl ProofSeed {
         Self::default()
     }
 
+    /// Same as [`ProofSeed::new`] but fails if the seed does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the seed fails them.
+    pub fn try_new() -> Result<Self, RngHealthError> {
+        Ok(Self {
+            seed: try_random_proof_seed()?,
+        })
+    }
+
     #[cfg(test)]
-    const fn from_specific_seed(server_seed: u32) -> Self {
+    pub(crate) const fn from_specific_seed(server_seed: u32) -> Self {
         Self { seed: server_seed }
     }
 
@@ -418,6 +551,14 @@ impl ProofSeed {
         client_proof: [u8; PROOF_LENGTH as _],
         client_seed: u32,
     ) -> Result<HeaderCrypto, MatchProofsError> {
+        enter_span!(
+            "verify_world_proof",
+            username = %username,
+            expansion = "vanilla",
+            server_seed = self.seed,
+            client_seed
+        );
+
         let server_proof = calculate_world_server_proof(
             username,
             &SessionKey::from_le_bytes(session_key),
@@ -426,6 +567,7 @@ impl ProofSeed {
         );
 
         if server_proof != Proof::from_le_bytes(client_proof) {
+            proof_mismatch!(client_proof);
             return Err(MatchProofsError {
                 client_proof,
                 server_proof: *server_proof.as_le_bytes(),
@@ -439,7 +581,7 @@ impl ProofSeed {
 impl Default for ProofSeed {
     fn default() -> Self {
         Self {
-            seed: thread_rng().next_u32(),
+            seed: random_proof_seed(),
         }
     }
 }
@@ -448,10 +590,13 @@ impl Default for ProofSeed {
 mod test {
     use std::fs::read_to_string;
 
+    use crate::error::{FrameError, PacketSizeError};
     use crate::hex::*;
     use crate::key::SessionKey;
     use crate::normalized_string::NormalizedString;
-    use crate::vanilla_header::{HeaderCrypto, ProofSeed};
+    use crate::vanilla_header::{
+        ClientFramer, HeaderCrypto, ProofSeed, ServerFramer, SERVER_BODY_MAXIMUM_LENGTH,
+    };
     use crate::SESSION_KEY_LENGTH;
     use std::convert::TryInto;
 
@@ -911,4 +1056,363 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn verify_framer() {
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (mut client, _) = HeaderCrypto::new(session_key).split();
+        let (_, server) = HeaderCrypto::new(session_key).split();
+        let mut framer = ServerFramer::new(server.clone(), 16);
+
+        let mut stream = Vec::new();
+        client
+            .write_encrypted_client_header(&mut stream, 4, 0x37)
+            .unwrap();
+        client
+            .write_encrypted_client_header(&mut stream, 4 + 12, 0x1DC)
+            .unwrap();
+        stream.extend_from_slice(&[0xAA; 12]);
+
+        // Pushing a single byte at a time must not advance the decryption on partial headers
+        let mut frames = Vec::new();
+        for b in &stream {
+            framer.push(&[*b]);
+            while let Some(frame) = framer.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![(0x37, vec![]), (0x1DC, vec![0xAA; 12])]);
+        assert_eq!(framer.buffered_len(), 0);
+
+        let mut framer = ServerFramer::new(server, 4);
+        framer.push(&stream);
+        assert_eq!(framer.next_frame().unwrap(), Some((0x37, vec![])));
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::BodyTooLarge {
+                size: 12,
+                maximum: 4
+            })
+        ));
+
+        let (mut client, _) = HeaderCrypto::new(session_key).split();
+        let mut stream = Vec::new();
+        client
+            .write_encrypted_server_header(&mut stream, 6, 0x1DD)
+            .unwrap();
+        stream.extend_from_slice(&[0xBB; 4]);
+        client
+            .write_encrypted_server_header(&mut stream, 1, 0x1DD)
+            .unwrap();
+
+        let (_, server) = HeaderCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(server, 16);
+        framer.push(&stream[..7]);
+        assert_eq!(framer.next_frame().unwrap(), None);
+        framer.push(&stream[7..]);
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1DD, vec![0xBB; 4])));
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::SizeSmallerThanOpcode { size: 1 })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn verify_codec() {
+        use crate::vanilla_header::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
+        use bytes::{Bytes, BytesMut};
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut encoder = ClientEncoder::new(encrypter);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x1DC, Bytes::from_static(&[0xAA; 3])), &mut stream)
+            .unwrap();
+        encoder.encode((0x37, Bytes::new()), &mut stream).unwrap();
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut decoder = ServerDecoder::new(decrypter, 16);
+        let mut frames = Vec::new();
+        let mut buf = BytesMut::new();
+        for chunk in stream.chunks(4) {
+            buf.extend_from_slice(chunk);
+            while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (0x1DC, Bytes::from_static(&[0xAA; 3])),
+                (0x37, Bytes::new())
+            ]
+        );
+
+        let (encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut encoder = ServerEncoder::new(encrypter);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x1DD, Bytes::from_static(&[0xBB; 4])), &mut stream)
+            .unwrap();
+        assert!(encoder
+            .encode((0x1DD, Bytes::from(vec![0; 0xFFFF])), &mut stream)
+            .is_err());
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut decoder = ClientDecoder::new(decrypter, 16);
+        let mut partial = stream.split_to(stream.len() - 1);
+        assert_eq!(decoder.decode(&mut partial).unwrap(), None);
+        assert!(decoder.decode_eof(&mut BytesMut::new()).is_err());
+        assert_eq!(
+            decoder.decode_eof(&mut stream).unwrap(),
+            Some((0x1DD, Bytes::from_static(&[0xBB; 4])))
+        );
+    }
+
+    #[test]
+    fn verify_packet_writer() {
+        let session_key = hex_decode(
+            "2EFEE7B0C177EBBDFF6676C56EFC2339BE9CAD14BF8B54BB5A86FBF81F6D424AA23CC9A3149FB175",
+        );
+        let session_key: [u8; SESSION_KEY_LENGTH as usize] = session_key.try_into().unwrap();
+
+        let (mut expected, _) = HeaderCrypto::new(session_key).split();
+        let mut expected_packet = expected.encrypt_server_header(2 + 5, 0x1DD).to_vec();
+        expected_packet.extend_from_slice(&[1, 2, 3, 4, 5]);
+        expected_packet.extend_from_slice(&expected.encrypt_client_header(4, 0x37));
+
+        let (mut encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut buf = [0_u8; 16];
+
+        assert_eq!(
+            encrypter.write_server_packet(&mut buf[..8], 0x1DD, &[&[1, 2], &[3, 4, 5]]),
+            Err(PacketSizeError::BufferTooSmall {
+                required: 9,
+                available: 8
+            })
+        );
+        let body = vec![0_u8; SERVER_BODY_MAXIMUM_LENGTH as usize];
+        assert_eq!(
+            encrypter.write_server_packet(&mut buf, 0x1DD, &[&body, &[0]]),
+            Err(PacketSizeError::BodyTooLarge {
+                size: SERVER_BODY_MAXIMUM_LENGTH as usize + 1,
+                maximum: SERVER_BODY_MAXIMUM_LENGTH as usize
+            })
+        );
+
+        let written = encrypter
+            .write_server_packet(&mut buf, 0x1DD, &[&[1, 2], &[3, 4, 5]])
+            .unwrap();
+        assert_eq!(written, 9);
+        let written = written
+            + encrypter
+                .write_client_packet(&mut buf[written..], 0x37, &[])
+                .unwrap();
+
+        assert_eq!(&buf[..written], expected_packet.as_slice());
+    }
+
+    #[test]
+    fn verify_snapshot() {
+        use crate::error::SnapshotError;
+        use crate::snapshot::CryptoSnapshot;
+        use crate::vanilla_header::{DecrypterHalf, EncrypterHalf};
+
+        fn hand_off(snapshot: CryptoSnapshot) -> CryptoSnapshot {
+            CryptoSnapshot::from_bytes(snapshot.as_bytes()).unwrap()
+        }
+
+        let contents = read_to_string("tests/encryption/calculate_encrypt_values.txt").unwrap();
+        for line in contents.lines() {
+            let mut line = line.split_whitespace();
+            let session_key = SessionKey::from_le_hex_str(line.next().unwrap());
+            let mut data = hex_decode(line.next().unwrap());
+            let expected = hex_decode(line.next().unwrap());
+
+            let (mut enc, _) = HeaderCrypto::new(*session_key.as_le_bytes()).split();
+            let middle = data.len() / 2;
+            let (first, second) = data.split_at_mut(middle);
+            enc.encrypt(first);
+
+            let mut enc = EncrypterHalf::restore(&hand_off(enc.snapshot())).unwrap();
+            enc.encrypt(second);
+
+            assert_eq!(data, expected);
+        }
+
+        let contents = read_to_string("tests/encryption/calculate_decrypt_values.txt").unwrap();
+        for line in contents.lines() {
+            let mut line = line.split_whitespace();
+            let session_key = SessionKey::from_le_hex_str(line.next().unwrap());
+            let mut data = hex_decode(line.next().unwrap());
+            let expected = hex_decode(line.next().unwrap());
+
+            let (_, mut dec) = HeaderCrypto::new(*session_key.as_le_bytes()).split();
+            let middle = data.len() / 3;
+            let (first, second) = data.split_at_mut(middle);
+            dec.decrypt(first);
+
+            let mut dec = DecrypterHalf::restore(&hand_off(dec.snapshot())).unwrap();
+            dec.decrypt(second);
+
+            assert_eq!(data, expected);
+        }
+
+        let (enc, dec) = HeaderCrypto::new([7; SESSION_KEY_LENGTH as usize]).split();
+        assert_eq!(
+            EncrypterHalf::restore(&dec.snapshot()),
+            Err(SnapshotError::InvalidKind)
+        );
+
+        let mut bytes = enc.snapshot().as_bytes().to_vec();
+        let index = bytes.len() - 2;
+        bytes[index] = SESSION_KEY_LENGTH;
+        let snapshot = CryptoSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            EncrypterHalf::restore(&snapshot),
+            Err(SnapshotError::InvalidState)
+        );
+    }
+
+    #[test]
+    fn verify_desync() {
+        use crate::desync::HeaderLimits;
+        use crate::error::ImplausibleHeader;
+        use crate::vanilla_header::ClientHeader;
+
+        let mut session_key = [0_u8; SESSION_KEY_LENGTH as usize];
+        for (i, b) in session_key.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(97).wrapping_add(13);
+        }
+        let limits = HeaderLimits::new(0x1000, 0, 0x500).with_search_distance(10);
+
+        let (mut client, _) = HeaderCrypto::new(session_key).split();
+        let (_, mut server) = HeaderCrypto::new(session_key).split();
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        let header = client.encrypt_client_header(8, 0x1ED);
+        assert_eq!(
+            server.decrypt_client_header_checked(header, &limits),
+            Ok(ClientHeader {
+                size: 8,
+                opcode: 0x1ED
+            })
+        );
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        // Header encrypted but never sent
+        let _ = client.encrypt_client_header(4, 0x37);
+        let header = client.encrypt_client_header(4, 0x38);
+        let error = server
+            .decrypt_client_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(6));
+        assert_ne!(client.fingerprint(), server.fingerprint());
+
+        // Header decrypted twice
+        let (mut server, mut client) = HeaderCrypto::new(session_key).split();
+        let header = server.encrypt_server_header(2, 0x1EC);
+        let _ = client.decrypt_server_header(header);
+        let _ = client.decrypt_server_header(header);
+        let header = server.encrypt_server_header(6, 0x1EE);
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(-4));
+
+        let limits = HeaderLimits::new(0x1000, 0x10, 0x20);
+        let (mut server, mut client) = HeaderCrypto::new(session_key).split();
+        let header = server.encrypt_server_header(2, 0x1EC);
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(
+            error.reason,
+            ImplausibleHeader::OpcodeOutOfRange {
+                minimum: 0x10,
+                maximum: 0x20
+            }
+        );
+        assert_eq!(error.offset, None);
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn verify_compression() {
+        use crate::compression::{Compression, SMSG_COMPRESSED_UPDATE_OBJECT};
+        use crate::error::{CompressionError, FrameError, PacketSizeError};
+
+        let session_key = [7_u8; crate::SESSION_KEY_LENGTH as usize];
+        let compression = Compression::new(0x10000);
+        let update_object: Vec<u8> = (0..0x9000_u32).map(|i| (i / 64) as u8).collect();
+
+        let (mut encrypter, _) = HeaderCrypto::new(session_key).split();
+        let mut stream = vec![0_u8; 0x1000];
+        assert_eq!(
+            encrypter.write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[&update_object],
+                &Compression::new(0x100),
+            ),
+            Err(PacketSizeError::BodyTooLarge {
+                size: 0x9000,
+                maximum: 0x100
+            })
+        );
+
+        let (first, second) = update_object.split_at(0x4000);
+        let mut length = encrypter
+            .write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[first, second],
+                &compression,
+            )
+            .unwrap();
+        length += encrypter
+            .write_compressed_server_packet(
+                &mut stream[length..],
+                0x1EE,
+                &[&[1, 2, 3]],
+                &compression,
+            )
+            .unwrap();
+        stream.truncate(length);
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(decrypter, 0x1000).with_compression(compression);
+        framer.push(&stream);
+        assert_eq!(
+            framer.next_frame().unwrap(),
+            Some((SMSG_COMPRESSED_UPDATE_OBJECT, update_object))
+        );
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1EE, vec![1, 2, 3])));
+
+        let (_, decrypter) = HeaderCrypto::new(session_key).split();
+        let mut framer =
+            ClientFramer::new(decrypter, 0x1000).with_compression(Compression::new(0x100));
+        framer.push(&stream);
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::InvalidCompressedBody {
+                opcode: SMSG_COMPRESSED_UPDATE_OBJECT,
+                error: CompressionError::DecompressedTooLarge {
+                    size: 0x9000,
+                    maximum: 0x100
+                }
+            })
+        ));
+    }
 }
@@ -0,0 +1,52 @@
+use crate::error::PacketSizeError;
+
+/// Total length of `body` if it is not larger than `maximum`.
+pub(crate) fn body_length(body: &[&[u8]], maximum: u32) -> Result<usize, PacketSizeError> {
+    let size = body
+        .iter()
+        .fold(0_usize, |size, part| size.saturating_add(part.len()));
+
+    if size > maximum as usize {
+        return Err(PacketSizeError::BodyTooLarge {
+            size,
+            maximum: maximum as usize,
+        });
+    }
+
+    Ok(size)
+}
+
+/// Returns the first `required` bytes of `buf`.
+pub(crate) fn packet_buffer(buf: &mut [u8], required: usize) -> Result<&mut [u8], PacketSizeError> {
+    let available = buf.len();
+
+    buf.get_mut(..required)
+        .ok_or(PacketSizeError::BufferTooSmall {
+            required,
+            available,
+        })
+}
+
+/// Writes `header` followed by every part of `body` into `buf`.
+///
+/// `buf` must be exactly as large as the header and body.
+pub(crate) fn write_packet(buf: &mut [u8], header: &[u8], body: &[&[u8]]) {
+    let (header_buf, mut buf) = buf.split_at_mut(header.len());
+    header_buf.copy_from_slice(header);
+
+    for part in body {
+        let (part_buf, rest) = buf.split_at_mut(part.len());
+        part_buf.copy_from_slice(part);
+        buf = rest;
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) fn put_packet(dst: &mut bytes::BytesMut, header: &[u8], body: &[&[u8]]) {
+    dst.reserve(header.len() + body.iter().map(|part| part.len()).sum::<usize>());
+
+    dst.extend_from_slice(header);
+    for part in body {
+        dst.extend_from_slice(part);
+    }
+}
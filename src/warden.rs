@@ -0,0 +1,323 @@
+//! Functionality for encrypting/decrypting Warden messages.
+//!
+//! The contents of the Warden messages (`SMSG_WARDEN_DATA` and `CMSG_WARDEN_DATA`)
+//! are encrypted with RC4 separately from the header encryption.
+//! Each direction has its own 16 byte key derived from the session key through a SHA-1 based
+//! generator (known as `SHA1Randx` in the client), described in [`WardenKeys`].
+//!
+//! The server uses [`ServerCrypto`] and the client uses [`ClientCrypto`].
+//! Both can be split into an [`EncrypterHalf`] and a [`DecrypterHalf`] in the same way as the
+//! header crypto.
+//!
+//! `wrath-header` must be enabled for this to be enabled, since the same RC4 implementation is used.
+//!
+//! # Example
+//!
+//! ```
+//! use wow_srp::warden::ServerCrypto;
+//! use wow_srp::SESSION_KEY_LENGTH;
+//!
+//! fn send_warden_data(session_key: [u8; SESSION_KEY_LENGTH as usize], mut data: Vec<u8>) {
+//!     // Usually session_key would be from SrpServer::session_key
+//!     let mut crypto = ServerCrypto::new(session_key);
+//!
+//!     crypto.encrypt(&mut data);
+//!     // Send data in SMSG_WARDEN_DATA
+//! }
+//! ```
+
+use crate::wrath_header::inner_crypto::rc4::Rc4;
+use crate::SESSION_KEY_LENGTH;
+use sha1::{Digest, Sha1};
+
+/// Length of the RC4 key used in each direction.
+pub const KEY_LENGTH: u8 = 16;
+
+/// Keys for both directions of Warden messages.
+///
+/// The session key is split into two halves which are hashed individually.
+/// The generator output is then `SHA1(first_half_hash, previous_output, second_half_hash)`,
+/// starting with a previous output of all zeros.
+/// The first 16 bytes are the client to server key, and the next 16 bytes are the
+/// server to client key.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct WardenKeys {
+    client_to_server: [u8; KEY_LENGTH as usize],
+    server_to_client: [u8; KEY_LENGTH as usize],
+}
+
+impl WardenKeys {
+    /// Derives the keys from the session key, usually from
+    /// [`SrpServer::session_key`](crate::server::SrpServer::session_key) or
+    /// [`SrpClient::session_key`](crate::client::SrpClient::session_key).
+    #[must_use]
+    pub fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
+        let mut generator = Sha1Randx::new(&session_key);
+
+        let mut client_to_server = [0_u8; KEY_LENGTH as usize];
+        generator.generate(&mut client_to_server);
+
+        let mut server_to_client = [0_u8; KEY_LENGTH as usize];
+        generator.generate(&mut server_to_client);
+
+        Self {
+            client_to_server,
+            server_to_client,
+        }
+    }
+
+    /// Key used for messages sent by the client.
+    #[must_use]
+    pub const fn client_to_server(&self) -> &[u8; KEY_LENGTH as usize] {
+        &self.client_to_server
+    }
+
+    /// Key used for messages sent by the server.
+    #[must_use]
+    pub const fn server_to_client(&self) -> &[u8; KEY_LENGTH as usize] {
+        &self.server_to_client
+    }
+}
+
+/// Encryption part of a [`ServerCrypto`] or [`ClientCrypto`].
+///
+/// Intended to be kept with the writer half of a connection.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct EncrypterHalf {
+    rc4: Rc4,
+}
+
+impl EncrypterHalf {
+    /// Encrypts `data` in place.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.rc4.apply_keystream(data);
+    }
+
+    fn new(key: &[u8; KEY_LENGTH as usize]) -> Self {
+        Self { rc4: Rc4::new(key) }
+    }
+}
+
+/// Decryption part of a [`ServerCrypto`] or [`ClientCrypto`].
+///
+/// Intended to be kept with the reader half of a connection.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct DecrypterHalf {
+    rc4: Rc4,
+}
+
+impl DecrypterHalf {
+    /// Decrypts `data` in place.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.rc4.apply_keystream(data);
+    }
+
+    fn new(key: &[u8; KEY_LENGTH as usize]) -> Self {
+        Self { rc4: Rc4::new(key) }
+    }
+}
+
+/// Main struct for encryption and decryption of Warden messages on the server.
+///
+/// Encrypts with the [server to client](WardenKeys::server_to_client) key and decrypts with
+/// the [client to server](WardenKeys::client_to_server) key.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ServerCrypto {
+    encrypt: EncrypterHalf,
+    decrypt: DecrypterHalf,
+}
+
+impl ServerCrypto {
+    /// Creates the crypto from the session key.
+    #[must_use]
+    pub fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
+        let keys = WardenKeys::new(session_key);
+
+        Self {
+            encrypt: EncrypterHalf::new(keys.server_to_client()),
+            decrypt: DecrypterHalf::new(keys.client_to_server()),
+        }
+    }
+
+    /// Direct access to the internal [`EncrypterHalf`].
+    #[must_use]
+    pub fn encrypter(&mut self) -> &mut EncrypterHalf {
+        &mut self.encrypt
+    }
+
+    /// Direct access to the internal [`DecrypterHalf`].
+    #[must_use]
+    pub fn decrypter(&mut self) -> &mut DecrypterHalf {
+        &mut self.decrypt
+    }
+
+    /// Encrypts `data` in place.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.encrypt.encrypt(data);
+    }
+
+    /// Decrypts `data` in place.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.decrypt.decrypt(data);
+    }
+
+    /// Split the [`ServerCrypto`] into two parts for use with split connections.
+    ///
+    /// It is intended for the [`EncrypterHalf`] to be stored with the write half of
+    /// the connection and for the [`DecrypterHalf`] to be stored with the read half
+    /// of the connection.
+    #[must_use]
+    pub const fn split(self) -> (EncrypterHalf, DecrypterHalf) {
+        (self.encrypt, self.decrypt)
+    }
+}
+
+/// Main struct for encryption and decryption of Warden messages on the client.
+///
+/// Encrypts with the [client to server](WardenKeys::client_to_server) key and decrypts with
+/// the [server to client](WardenKeys::server_to_client) key.
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ClientCrypto {
+    encrypt: EncrypterHalf,
+    decrypt: DecrypterHalf,
+}
+
+impl ClientCrypto {
+    /// Creates the crypto from the session key.
+    #[must_use]
+    pub fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
+        let keys = WardenKeys::new(session_key);
+
+        Self {
+            encrypt: EncrypterHalf::new(keys.client_to_server()),
+            decrypt: DecrypterHalf::new(keys.server_to_client()),
+        }
+    }
+
+    /// Direct access to the internal [`EncrypterHalf`].
+    #[must_use]
+    pub fn encrypter(&mut self) -> &mut EncrypterHalf {
+        &mut self.encrypt
+    }
+
+    /// Direct access to the internal [`DecrypterHalf`].
+    #[must_use]
+    pub fn decrypter(&mut self) -> &mut DecrypterHalf {
+        &mut self.decrypt
+    }
+
+    /// Encrypts `data` in place.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.encrypt.encrypt(data);
+    }
+
+    /// Decrypts `data` in place.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.decrypt.decrypt(data);
+    }
+
+    /// Split the [`ClientCrypto`] into two parts for use with split connections.
+    ///
+    /// It is intended for the [`EncrypterHalf`] to be stored with the write half of
+    /// the connection and for the [`DecrypterHalf`] to be stored with the read half
+    /// of the connection.
+    #[must_use]
+    pub const fn split(self) -> (EncrypterHalf, DecrypterHalf) {
+        (self.encrypt, self.decrypt)
+    }
+}
+
+/// SHA-1 based generator that outputs an endless stream of bytes from a seed.
+struct Sha1Randx {
+    first_half: [u8; 20],
+    second_half: [u8; 20],
+    output: [u8; 20],
+    taken: usize,
+}
+
+impl Sha1Randx {
+    fn new(seed: &[u8]) -> Self {
+        let (first, second) = seed.split_at(seed.len() / 2);
+
+        let mut generator = Self {
+            first_half: Sha1::digest(first).into(),
+            second_half: Sha1::digest(second).into(),
+            output: [0; 20],
+            taken: 0,
+        };
+        generator.fill();
+
+        generator
+    }
+
+    fn fill(&mut self) {
+        self.output = Sha1::new()
+            .chain_update(self.first_half)
+            .chain_update(self.output)
+            .chain_update(self.second_half)
+            .finalize()
+            .into();
+        self.taken = 0;
+    }
+
+    fn generate(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            if self.taken == self.output.len() {
+                self.fill();
+            }
+
+            *b = self.output[self.taken];
+            self.taken += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hex::hex_decode;
+    use crate::warden::{ClientCrypto, ServerCrypto, WardenKeys};
+    use crate::SESSION_KEY_LENGTH;
+
+    fn session_key() -> [u8; SESSION_KEY_LENGTH as usize] {
+        let mut session_key = [0_u8; SESSION_KEY_LENGTH as usize];
+        for (i, b) in session_key.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(97).wrapping_add(13);
+        }
+        session_key
+    }
+
+    #[test]
+    fn verify_keys() {
+        let keys = WardenKeys::new(session_key());
+
+        assert_eq!(
+            keys.client_to_server().as_slice(),
+            hex_decode("066ddf5c62fb48ddfed2ec8e2cd98933")
+        );
+        // Crosses the 20 byte boundary of the generator
+        assert_eq!(
+            keys.server_to_client().as_slice(),
+            hex_decode("409ab777e3c3bc10c8487505dcc9e36e")
+        );
+    }
+
+    #[test]
+    fn verify_crypto() {
+        let mut server = ServerCrypto::new(session_key());
+        let mut client = ClientCrypto::new(session_key());
+
+        let mut data = *b"warden";
+        client.encrypt(&mut data);
+        assert_eq!(data.as_slice(), hex_decode("995e18501de7"));
+        server.decrypt(&mut data);
+        assert_eq!(&data, b"warden");
+
+        let (mut encrypter, _) = server.split();
+        let (_, mut decrypter) = client.split();
+        let mut data = *b"warden";
+        encrypter.encrypt(&mut data);
+        assert_eq!(data.as_slice(), hex_decode("ad4bb9a3cc05"));
+        decrypter.decrypt(&mut data);
+        assert_eq!(&data, b"warden");
+    }
+}
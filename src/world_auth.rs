@@ -0,0 +1,541 @@
+//! Replay protection for the world server authentication.
+//!
+//! [`ProofSeed::into_server_header_crypto`](crate::vanilla_header::ProofSeed::into_server_header_crypto)
+//! accepts any client seed and proof that match the server seed.
+//! If a server seed is reused or can be predicted, a captured [`CMSG_AUTH_SESSION`] can be
+//! sent again by someone who does not know the session key.
+//!
+//! [`WorldAuthGuard`] issues the server seeds for [`SMSG_AUTH_CHALLENGE`] instead.
+//! Every seed is bound to a single connection and can only be used once,
+//! even if the proof does not match.
+//! Seeds expire after a timeout, and a username and client seed pair that has been accepted
+//! is rejected for the duration of the replay window.
+//!
+//! The guard keeps all state in memory and takes `&mut self`, so it must be put behind a
+//! [`Mutex`](std::sync::Mutex) if shared between connections.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use wow_srp::normalized_string::NormalizedString;
+//! use wow_srp::vanilla_header::HeaderCrypto;
+//! use wow_srp::world_auth::WorldAuthGuard;
+//! use wow_srp::{PROOF_LENGTH, SESSION_KEY_LENGTH};
+//!
+//! fn connection_opened(guard: &mut WorldAuthGuard, connection: u64) -> u32 {
+//!     // Send the seed in SMSG_AUTH_CHALLENGE
+//!     guard.issue_seed(connection)
+//! }
+//!
+//! fn auth_session_received(
+//!     guard: &mut WorldAuthGuard,
+//!     connection: u64,
+//!     username: &NormalizedString,
+//!     // From the database
+//!     session_key: [u8; SESSION_KEY_LENGTH as usize],
+//!     // From CMSG_AUTH_SESSION
+//!     client_proof: [u8; PROOF_LENGTH as usize],
+//!     client_seed: u32,
+//! ) -> Option<HeaderCrypto> {
+//!     guard
+//!         .verify_vanilla(connection, username, session_key, client_proof, client_seed)
+//!         .ok()
+//! }
+//!
+//! let mut guard = WorldAuthGuard::new(Duration::from_secs(30), Duration::from_secs(600));
+//! let server_seed = connection_opened(&mut guard, 1);
+//! ```
+//!
+//! [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
+//! [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{MatchProofsError, RngHealthError, WorldAuthError};
+use crate::key::RngSource;
+use crate::normalized_string::NormalizedString;
+use crate::observer::{AuthObserver, ObserverSlot};
+use crate::vanilla_header::ProofSeed;
+use crate::{Expansion, PROOF_LENGTH, SESSION_KEY_LENGTH};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Amount of seeds generated before giving up on finding one that is not outstanding.
+///
+/// Even with millions of outstanding seeds a healthy RNG practically never needs more than one.
+const MAXIMUM_SEED_ATTEMPTS: usize = 8;
+
+const NO_UNIQUE_SEED: &str =
+    "RNG did not produce a seed that is not already outstanding. The RNG is most likely broken.";
+
+/// Issues single use server seeds and rejects replayed client seeds.
+///
+/// Created through [`WorldAuthGuard::new`] or [`WorldAuthGuard::with_clock`].
+#[derive(Debug, Clone)]
+pub struct WorldAuthGuard<C: Clock = SystemClock> {
+    clock: C,
+    seed_timeout: Duration,
+    replay_window: Duration,
+    seeds: HashMap<u64, (ProofSeed, Instant)>,
+    /// Connection of every outstanding seed.
+    connections: HashMap<u32, u64>,
+    /// Issued seeds in the order they were issued, including seeds that have since been used.
+    issued: VecDeque<(Instant, u64, u32)>,
+    accepted: HashMap<(NormalizedString, u32), Instant>,
+    /// Accepted client seeds in the order they were accepted.
+    accepted_order: VecDeque<(Instant, (NormalizedString, u32))>,
+    observer: ObserverSlot,
+}
+
+impl WorldAuthGuard<SystemClock> {
+    /// Creates a guard where seeds expire `seed_timeout` after being issued and accepted
+    /// client seeds are remembered for `replay_window`.
+    #[must_use]
+    pub fn new(seed_timeout: Duration, replay_window: Duration) -> Self {
+        Self::with_clock(seed_timeout, replay_window, SystemClock)
+    }
+}
+
+impl<C: Clock> WorldAuthGuard<C> {
+    /// Same as [`WorldAuthGuard::new`] but with a specific [`Clock`].
+    #[must_use]
+    pub fn with_clock(seed_timeout: Duration, replay_window: Duration, clock: C) -> Self {
+        Self {
+            clock,
+            seed_timeout,
+            replay_window,
+            seeds: HashMap::new(),
+            connections: HashMap::new(),
+            issued: VecDeque::new(),
+            accepted: HashMap::new(),
+            accepted_order: VecDeque::new(),
+            observer: ObserverSlot::NONE,
+        }
+    }
+
+    /// Attaches an [`AuthObserver`] that is notified when a client proof is verified.
+    ///
+    /// See the [`observer`](crate::observer) module.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn AuthObserver>) -> Self {
+        self.observer = ObserverSlot::new(observer);
+        self
+    }
+
+    /// Generates a new server seed for `connection`.
+    ///
+    /// Any previous seed for `connection` is no longer valid.
+    /// The seed is never the same as the outstanding seed of another connection.
+    ///
+    /// # Panics
+    ///
+    /// If the RNG keeps producing seeds that are already outstanding.
+    /// Use [`WorldAuthGuard::try_issue_seed`] to get an error instead.
+    pub fn issue_seed(&mut self, connection: u64) -> u32 {
+        self.issue(connection, || Ok(ProofSeed::new()))
+            .expect(NO_UNIQUE_SEED)
+    }
+
+    /// Same as [`WorldAuthGuard::issue_seed`] but fails if the seed does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// No seed is issued for `connection` if this fails.
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the seed fails them, or
+    /// [`RngHealthError::RepeatedOutput`] if the RNG keeps producing seeds that are already
+    /// outstanding.
+    pub fn try_issue_seed(&mut self, connection: u64) -> Result<u32, RngHealthError> {
+        self.issue(connection, ProofSeed::try_new)
+    }
+
+    fn issue(
+        &mut self,
+        connection: u64,
+        mut new_seed: impl FnMut() -> Result<ProofSeed, RngHealthError>,
+    ) -> Result<u32, RngHealthError> {
+        let now = self.clock.now();
+        self.remove_expired(now);
+
+        self.cancel_seed(connection);
+
+        let mut unique_seed = None;
+        for _ in 0..MAXIMUM_SEED_ATTEMPTS {
+            let seed = new_seed()?;
+            if !self.connections.contains_key(&seed.seed()) {
+                unique_seed = Some(seed);
+                break;
+            }
+        }
+        let seed = unique_seed.ok_or(RngHealthError::RepeatedOutput {
+            source: RngSource::ProofSeed,
+        })?;
+
+        let server_seed = seed.seed();
+        self.connections.insert(server_seed, connection);
+        self.seeds.insert(connection, (seed, now));
+        self.issued.push_back((now, connection, server_seed));
+
+        Ok(server_seed)
+    }
+
+    /// Removes the outstanding seed of `connection`, if any.
+    ///
+    /// Should be called when a connection is closed before authenticating.
+    pub fn cancel_seed(&mut self, connection: u64) {
+        self.take_seed(connection);
+    }
+
+    /// Amount of seeds that have been issued but not used or cancelled.
+    ///
+    /// Expired seeds are counted until the next call that modifies the guard.
+    #[must_use]
+    pub fn outstanding_seeds(&self) -> usize {
+        self.seeds.len()
+    }
+
+    /// Verifies the [`CMSG_AUTH_SESSION`] of a Vanilla client and creates the
+    /// [`HeaderCrypto`](crate::vanilla_header::HeaderCrypto).
+    ///
+    /// # Errors
+    ///
+    /// See [`WorldAuthError`].
+    ///
+    /// [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+    pub fn verify_vanilla(
+        &mut self,
+        connection: u64,
+        username: &NormalizedString,
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<crate::vanilla_header::HeaderCrypto, WorldAuthError> {
+        self.verify(
+            connection,
+            username,
+            client_seed,
+            Expansion::Vanilla,
+            |seed| seed.into_server_header_crypto(username, session_key, client_proof, client_seed),
+        )
+    }
+
+    /// Verifies the [`CMSG_AUTH_SESSION`] of a TBC client and creates the
+    /// [`HeaderCrypto`](crate::tbc_header::HeaderCrypto).
+    ///
+    /// # Errors
+    ///
+    /// See [`WorldAuthError`].
+    ///
+    /// [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+    #[cfg(feature = "tbc-header")]
+    pub fn verify_tbc(
+        &mut self,
+        connection: u64,
+        username: &NormalizedString,
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<crate::tbc_header::HeaderCrypto, WorldAuthError> {
+        self.verify(connection, username, client_seed, Expansion::Tbc, |seed| {
+            crate::tbc_header::ProofSeed::from_issued(seed).into_server_header_crypto(
+                username,
+                session_key,
+                client_proof,
+                client_seed,
+            )
+        })
+    }
+
+    /// Verifies the [`CMSG_AUTH_SESSION`] of a Wrath client and creates the
+    /// [`ServerCrypto`](crate::wrath_header::ServerCrypto).
+    ///
+    /// # Errors
+    ///
+    /// See [`WorldAuthError`].
+    ///
+    /// [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/CMSG_AUTH_SESSION
+    #[cfg(feature = "wrath-header")]
+    pub fn verify_wrath(
+        &mut self,
+        connection: u64,
+        username: &NormalizedString,
+        session_key: [u8; SESSION_KEY_LENGTH as usize],
+        client_proof: [u8; PROOF_LENGTH as usize],
+        client_seed: u32,
+    ) -> Result<crate::wrath_header::ServerCrypto, WorldAuthError> {
+        self.verify(
+            connection,
+            username,
+            client_seed,
+            Expansion::Wrath,
+            |seed| {
+                crate::wrath_header::ProofSeed::from_issued(seed).into_server_header_crypto(
+                    username,
+                    session_key,
+                    client_proof,
+                    client_seed,
+                )
+            },
+        )
+    }
+
+    fn verify<T>(
+        &mut self,
+        connection: u64,
+        username: &NormalizedString,
+        client_seed: u32,
+        expansion: Expansion,
+        into_crypto: impl FnOnce(ProofSeed) -> Result<T, MatchProofsError>,
+    ) -> Result<T, WorldAuthError> {
+        let now = self.clock.now();
+
+        // Removed before anything else so that a seed can only be tried once
+        let (seed, issued) = self
+            .take_seed(connection)
+            .ok_or(WorldAuthError::NoSeedIssued)?;
+        if now.saturating_duration_since(issued) > self.seed_timeout {
+            return Err(WorldAuthError::SeedExpired);
+        }
+
+        self.remove_expired(now);
+
+        let key = (username.clone(), client_seed);
+        if self.accepted.contains_key(&key) {
+            return Err(WorldAuthError::Replayed);
+        }
+
+        let crypto = into_crypto(seed);
+        self.observer.emit_world_proof(
+            username.as_ref(),
+            expansion,
+            seed.seed(),
+            client_seed,
+            crypto.is_ok(),
+        );
+        let crypto = crypto?;
+
+        // Only accepted seeds are remembered, otherwise anyone could block a client seed
+        self.accepted.insert(key.clone(), now);
+        self.accepted_order.push_back((now, key));
+
+        Ok(crypto)
+    }
+
+    fn take_seed(&mut self, connection: u64) -> Option<(ProofSeed, Instant)> {
+        let (seed, issued) = self.seeds.remove(&connection)?;
+        self.connections.remove(&seed.seed());
+
+        Some((seed, issued))
+    }
+
+    /// Only looks at the oldest entries, so the cost is proportional to the amount of
+    /// entries that have expired since the last call.
+    fn remove_expired(&mut self, now: Instant) {
+        while let Some(&(issued, connection, seed)) = self.issued.front() {
+            if now.saturating_duration_since(issued) <= self.seed_timeout {
+                break;
+            }
+            self.issued.pop_front();
+
+            // The seed might have been used, cancelled, or replaced since it was issued
+            if matches!(self.seeds.get(&connection), Some((s, i)) if s.seed() == seed && *i == issued)
+            {
+                self.take_seed(connection);
+            }
+        }
+
+        while let Some((accepted, _)) = self.accepted_order.front() {
+            if now.saturating_duration_since(*accepted) < self.replay_window {
+                break;
+            }
+            if let Some((accepted, key)) = self.accepted_order.pop_front() {
+                if self.accepted.get(&key) == Some(&accepted) {
+                    self.accepted.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::clock::ManualClock;
+    use crate::error::{RngHealthError, WorldAuthError};
+    use crate::normalized_string::NormalizedString;
+    use crate::vanilla_header::ProofSeed;
+    use crate::world_auth::WorldAuthGuard;
+    use crate::{RngSource, SESSION_KEY_LENGTH};
+    use std::time::Duration;
+
+    const SESSION_KEY: [u8; SESSION_KEY_LENGTH as usize] = [3; SESSION_KEY_LENGTH as usize];
+
+    #[test]
+    fn verify_single_use() {
+        let clock = ManualClock::new();
+        let mut guard = WorldAuthGuard::with_clock(
+            Duration::from_secs(30),
+            Duration::from_secs(600),
+            clock.clone(),
+        );
+        let username = NormalizedString::new("A").unwrap();
+
+        let server_seed = guard.issue_seed(1);
+        let client = ProofSeed::new();
+        let client_seed = client.seed();
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, server_seed);
+
+        assert!(guard
+            .verify_vanilla(1, &username, SESSION_KEY, proof, client_seed)
+            .is_ok());
+        assert_eq!(guard.outstanding_seeds(), 0);
+
+        // Same message again on the same connection
+        assert!(matches!(
+            guard.verify_vanilla(1, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::NoSeedIssued)
+        ));
+
+        // Seed of another connection
+        let other_seed = guard.issue_seed(2);
+        assert!(matches!(
+            guard.verify_vanilla(3, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::NoSeedIssued)
+        ));
+
+        // A failed proof uses the seed
+        let client = ProofSeed::new();
+        let client_seed = client.seed();
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, other_seed);
+        assert!(matches!(
+            guard.verify_vanilla(2, &username, SESSION_KEY, [0; 20], client_seed),
+            Err(WorldAuthError::ProofsDoNotMatch(_))
+        ));
+        assert!(matches!(
+            guard.verify_vanilla(2, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::NoSeedIssued)
+        ));
+
+        // Issuing a new seed replaces the old one
+        let first = guard.issue_seed(4);
+        let second = guard.issue_seed(4);
+        assert_eq!(guard.outstanding_seeds(), 1);
+        let client = ProofSeed::new();
+        let client_seed = client.seed();
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, first);
+        assert!(matches!(
+            guard.verify_vanilla(4, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::ProofsDoNotMatch(_))
+        ));
+
+        guard.issue_seed(5);
+        guard.cancel_seed(5);
+        assert_eq!(guard.outstanding_seeds(), 0);
+        assert!(guard.connections.is_empty());
+
+        let seed = guard.try_issue_seed(6).unwrap();
+        assert_eq!(guard.connections.get(&seed), Some(&6));
+        assert_ne!(first, second);
+
+        // An RNG that only produces outstanding seeds does not loop forever
+        assert_eq!(
+            guard.issue(7, || Ok(ProofSeed::from_specific_seed(seed))),
+            Err(RngHealthError::RepeatedOutput {
+                source: RngSource::ProofSeed
+            })
+        );
+        assert_eq!(guard.connections.get(&seed), Some(&6));
+    }
+
+    #[test]
+    fn verify_expiry_and_replay() {
+        let clock = ManualClock::new();
+        let mut guard = WorldAuthGuard::with_clock(
+            Duration::from_secs(30),
+            Duration::from_secs(600),
+            clock.clone(),
+        );
+        let username = NormalizedString::new("A").unwrap();
+
+        let server_seed = guard.issue_seed(1);
+        clock.advance(Duration::from_secs(31));
+        let client = ProofSeed::new();
+        let client_seed = client.seed();
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, server_seed);
+        assert!(matches!(
+            guard.verify_vanilla(1, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::SeedExpired)
+        ));
+
+        // Expired seeds are removed when issuing
+        guard.issue_seed(2);
+        clock.advance(Duration::from_secs(31));
+        guard.issue_seed(3);
+        assert_eq!(guard.outstanding_seeds(), 1);
+        assert_eq!(guard.connections.len(), 1);
+        assert_eq!(guard.issued.len(), 1);
+
+        let client = ProofSeed::new();
+        let client_seed = client.seed();
+        let server_seed = guard.issue_seed(4);
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, server_seed);
+        assert!(guard
+            .verify_vanilla(4, &username, SESSION_KEY, proof, client_seed)
+            .is_ok());
+
+        // Same client seed on another connection within the window
+        let server_seed = guard.issue_seed(5);
+        let (proof, _) = ProofSeed::from_specific_seed(client_seed).into_client_header_crypto(
+            &username,
+            SESSION_KEY,
+            server_seed,
+        );
+        assert!(matches!(
+            guard.verify_vanilla(5, &username, SESSION_KEY, proof, client_seed),
+            Err(WorldAuthError::Replayed)
+        ));
+
+        // Other usernames are not affected
+        let other = NormalizedString::new("B").unwrap();
+        let server_seed = guard.issue_seed(6);
+        let (proof, _) = ProofSeed::from_specific_seed(client_seed).into_client_header_crypto(
+            &other,
+            SESSION_KEY,
+            server_seed,
+        );
+        assert!(guard
+            .verify_vanilla(6, &other, SESSION_KEY, proof, client_seed)
+            .is_ok());
+
+        // Allowed again after the window
+        clock.advance(Duration::from_secs(600));
+        let server_seed = guard.issue_seed(7);
+        assert!(guard.accepted.is_empty());
+        assert_eq!(guard.accepted_order.len(), 0);
+        let (proof, _) = ProofSeed::from_specific_seed(client_seed).into_client_header_crypto(
+            &username,
+            SESSION_KEY,
+            server_seed,
+        );
+        assert!(guard
+            .verify_vanilla(7, &username, SESSION_KEY, proof, client_seed)
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "wrath-header")]
+    fn verify_wrath() {
+        let mut guard = WorldAuthGuard::new(Duration::from_secs(30), Duration::from_secs(600));
+        let username = NormalizedString::new("A").unwrap();
+
+        let server_seed = guard.issue_seed(1);
+        let client = crate::wrath_header::ProofSeed::new();
+        let client_seed = client.seed();
+        let (proof, _) = client.into_client_header_crypto(&username, SESSION_KEY, server_seed);
+
+        assert!(guard
+            .verify_wrath(1, &username, SESSION_KEY, proof, client_seed)
+            .is_ok());
+    }
+}
@@ -1,3 +1,9 @@
+use crate::desync::CipherFingerprint;
+use crate::desync::HeaderLimits;
+use crate::error::DesyncError;
+use crate::error::SnapshotError;
+use crate::framing::{CLIENT_OPCODE_LENGTH, SERVER_OPCODE_LENGTH};
+use crate::snapshot::{CryptoSnapshot, SnapshotKind};
 use crate::wrath_header::{
     ClientHeader, ServerHeader, CLIENT_HEADER_LENGTH, R, S, SERVER_HEADER_MAXIMUM_LENGTH,
     SERVER_HEADER_MINIMUM_LENGTH,
@@ -56,6 +62,69 @@ impl ServerDecrypterHalf {
         ClientHeader { size, opcode }
     }
 
+    /// [`ServerDecrypterHalf::decrypt_client_header`] that returns an error for headers outside of `limits`.
+    ///
+    /// RC4 can not be rewound, so only offsets where the decrypter is behind are searched.
+    /// See the [`desync`](crate::desync) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the decrypted header is implausible according to `limits`.
+    /// The decryption state is advanced past the header regardless.
+    pub fn decrypt_client_header_checked(
+        &mut self,
+        data: [u8; CLIENT_HEADER_LENGTH as usize],
+        limits: &HeaderLimits,
+    ) -> Result<ClientHeader, DesyncError> {
+        let before = *self;
+        let header = self.decrypt_client_header(data);
+
+        let opcode_length = CLIENT_OPCODE_LENGTH.into();
+        limits.check_or_search(
+            header.size.into(),
+            header.opcode,
+            opcode_length,
+            true,
+            |offset| {
+                let mut half = before;
+                half.decrypt.skip(offset);
+                let header = half.decrypt_client_header(data);
+                limits
+                    .check(header.size.into(), header.opcode, opcode_length)
+                    .is_ok()
+            },
+        )?;
+
+        Ok(header)
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        self.decrypt.snapshot(SnapshotKind::WrathServerDecrypter)
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`ServerDecrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            decrypt: InnerCrypto::restore(snapshot, SnapshotKind::WrathServerDecrypter)?,
+        })
+    }
+
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         Self {
             decrypt: InnerCrypto::new(session_key, &S),
@@ -80,6 +149,63 @@ impl ClientDecrypterHalf {
         self.decrypt.apply(data);
     }
 
+    /// [`Read`](std::io::Read) wrapper for [`ClientDecrypterHalf::decrypt_server_header`].
+    ///
+    /// The first byte is decrypted before the rest of the header is read in order to
+    /// find out whether the header is [4](SERVER_HEADER_MINIMUM_LENGTH) or
+    /// [5](SERVER_HEADER_MAXIMUM_LENGTH) bytes long.
+    /// Only the header is read, so the body can be read directly afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`std::io::Read::read_exact`].
+    /// If an error occurs after the first byte has been read the decryption state has already
+    /// been advanced, so the connection should be dropped.
+    pub fn read_and_decrypt_server_header<R: Read>(
+        &mut self,
+        mut reader: R,
+    ) -> std::io::Result<ServerHeader> {
+        let mut buf = [0_u8; SERVER_HEADER_MAXIMUM_LENGTH as usize];
+        reader.read_exact(&mut buf[0..1])?;
+        self.decrypt(&mut buf[0..1]);
+
+        let length = server_header_length(buf[0]);
+        reader.read_exact(&mut buf[1..length])?;
+        self.decrypt(&mut buf[1..length]);
+
+        Ok(parse_server_header(buf))
+    }
+
+    /// Decrypts a server header from the start of `data`, which may contain more than just the header.
+    ///
+    /// Returns the header and the amount of bytes consumed from `data`, either
+    /// [4](SERVER_HEADER_MINIMUM_LENGTH) or [5](SERVER_HEADER_MAXIMUM_LENGTH).
+    ///
+    /// Returns [`None`] if `data` does not contain the full header.
+    /// The decryption state is not advanced in this case, so the same call can be repeated
+    /// when more data has arrived.
+    #[must_use]
+    pub fn decrypt_server_header_from_buffer(
+        &mut self,
+        data: &[u8],
+    ) -> Option<(ServerHeader, usize)> {
+        let first = *data.first()?;
+
+        let mut peek = [first];
+        let mut peek_crypto = self.decrypt;
+        peek_crypto.apply(&mut peek);
+        let length = server_header_length(peek[0]);
+
+        if data.len() < length {
+            return None;
+        }
+
+        let mut header = [0_u8; SERVER_HEADER_MAXIMUM_LENGTH as usize];
+        header[..length].copy_from_slice(&data[..length]);
+
+        Some((self.decrypt_server_header(header), length))
+    }
+
     /// Convenience wrapper for [`ServerDecrypterHalf::decrypt_client_header`].
     ///
     /// This handles situations where the size field is 3 bytes instead of 2.
@@ -92,27 +218,73 @@ impl ClientDecrypterHalf {
     ) -> ServerHeader {
         self.decrypt.apply(&mut data[0..1]);
 
-        if data[0] & 0x80 != 0 {
-            self.decrypt(&mut data[1..]);
-
-            // The most significant bit of the most significant byte is set
-            // in order to indicate that this is a 3-byte size.
-            // The 0x80 indicator must be cleared, otherwise the size is off
-            let most_significant_byte = data[0] & 0x7F;
-            let size = u32::from_be_bytes([0, most_significant_byte, data[1], data[2]]);
-            let opcode = u16::from_le_bytes([data[3], data[4]]);
-
-            ServerHeader { size, opcode }
-        } else {
-            self.decrypt(&mut data[1..SERVER_HEADER_MINIMUM_LENGTH as usize]);
-            let size = u16::from_be_bytes([data[0], data[1]]);
-            let opcode = u16::from_le_bytes([data[2], data[3]]);
-
-            ServerHeader {
-                size: size.into(),
-                opcode,
-            }
-        }
+        let length = server_header_length(data[0]);
+        self.decrypt(&mut data[1..length]);
+
+        parse_server_header(data)
+    }
+
+    /// [`ClientDecrypterHalf::decrypt_server_header`] that returns an error for headers outside of `limits`.
+    ///
+    /// RC4 can not be rewound, so only offsets where the decrypter is behind are searched.
+    /// See the [`desync`](crate::desync) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the decrypted header is implausible according to `limits`.
+    /// The decryption state is advanced past the header regardless.
+    pub fn decrypt_server_header_checked(
+        &mut self,
+        data: [u8; SERVER_HEADER_MAXIMUM_LENGTH as usize],
+        limits: &HeaderLimits,
+    ) -> Result<ServerHeader, DesyncError> {
+        let before = *self;
+        let header = self.decrypt_server_header(data);
+
+        let opcode_length = SERVER_OPCODE_LENGTH.into();
+        limits.check_or_search(
+            header.size,
+            header.opcode.into(),
+            opcode_length,
+            true,
+            |offset| {
+                let mut half = before;
+                half.decrypt.skip(offset);
+                let header = half.decrypt_server_header(data);
+                limits
+                    .check(header.size, header.opcode.into(), opcode_length)
+                    .is_ok()
+            },
+        )?;
+
+        Ok(header)
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        self.decrypt.snapshot(SnapshotKind::WrathClientDecrypter)
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`ClientDecrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            decrypt: InnerCrypto::restore(snapshot, SnapshotKind::WrathClientDecrypter)?,
+        })
     }
 
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
@@ -121,3 +293,33 @@ impl ClientDecrypterHalf {
         }
     }
 }
+
+/// Length of the server header based on the first decrypted byte.
+const fn server_header_length(first_decrypted_byte: u8) -> usize {
+    if first_decrypted_byte & 0x80 != 0 {
+        SERVER_HEADER_MAXIMUM_LENGTH as usize
+    } else {
+        SERVER_HEADER_MINIMUM_LENGTH as usize
+    }
+}
+
+fn parse_server_header(data: [u8; SERVER_HEADER_MAXIMUM_LENGTH as usize]) -> ServerHeader {
+    if data[0] & 0x80 != 0 {
+        // The most significant bit of the most significant byte is set
+        // in order to indicate that this is a 3-byte size.
+        // The 0x80 indicator must be cleared, otherwise the size is off
+        let most_significant_byte = data[0] & 0x7F;
+        let size = u32::from_be_bytes([0, most_significant_byte, data[1], data[2]]);
+        let opcode = u16::from_le_bytes([data[3], data[4]]);
+
+        ServerHeader { size, opcode }
+    } else {
+        let size = u16::from_be_bytes([data[0], data[1]]);
+        let opcode = u16::from_le_bytes([data[2], data[3]]);
+
+        ServerHeader {
+            size: size.into(),
+            opcode,
+        }
+    }
+}
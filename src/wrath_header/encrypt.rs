@@ -1,5 +1,16 @@
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::desync::CipherFingerprint;
+use crate::error::{PacketSizeError, SnapshotError};
+use crate::framing::SERVER_OPCODE_LENGTH;
+use crate::snapshot::{CryptoSnapshot, SnapshotKind};
+use crate::vanilla_header::encrypt::client_size;
+#[cfg(feature = "tokio")]
+use crate::vanilla_header::packet::put_packet;
+use crate::vanilla_header::packet::{body_length, packet_buffer, write_packet};
 use crate::wrath_header::{
-    CLIENT_HEADER_LENGTH, R, S, SERVER_HEADER_MAXIMUM_LENGTH, SERVER_HEADER_MINIMUM_LENGTH,
+    CLIENT_BODY_MAXIMUM_LENGTH, CLIENT_HEADER_LENGTH, R, S, SERVER_BODY_MAXIMUM_LENGTH,
+    SERVER_HEADER_MAXIMUM_LENGTH, SERVER_HEADER_MINIMUM_LENGTH,
 };
 use crate::SESSION_KEY_LENGTH;
 
@@ -47,7 +58,7 @@ impl ServerEncrypterHalf {
     /// Prefer this over directly using [`Self::encrypt`].
     #[must_use]
     pub fn encrypt_server_header(&mut self, size: u32, opcode: u16) -> &[u8] {
-        if size > 0x7FFF {
+        if server_header_length(size) == SERVER_HEADER_MAXIMUM_LENGTH {
             let size = size.to_be_bytes();
             let opcode = opcode.to_le_bytes();
 
@@ -89,6 +100,128 @@ impl ServerEncrypterHalf {
         }
     }
 
+    /// Writes an encrypted server header followed by `body` into the start of `buf`.
+    ///
+    /// The size field is calculated from the opcode and every part of `body`,
+    /// which is written in order.
+    /// The header is [`SERVER_HEADER_MAXIMUM_LENGTH`] bytes long if the size is larger than `0x7FFF`.
+    /// Returns the amount of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`] or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    pub fn write_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        let length = body_length(body, SERVER_BODY_MAXIMUM_LENGTH)?;
+        let size = server_size(length);
+        let buf = packet_buffer(buf, server_header_length(size) as usize + length)?;
+
+        let header = self.encrypt_server_header(size, opcode);
+        write_packet(buf, header, body);
+
+        Ok(buf.len())
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "tokio")]
+    pub fn put_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        let length = body_length(body, SERVER_BODY_MAXIMUM_LENGTH)?;
+
+        let header = self.encrypt_server_header(server_size(length), opcode);
+        put_packet(dst, header, body);
+
+        Ok(())
+    }
+
+    /// Same as [`Self::write_server_packet`] but first compresses `body` if `opcode` is compressed
+    /// according to `compression`.
+    ///
+    /// The size field is calculated from the compressed body.
+    /// See the [`compression`](crate::compression) module for more information.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than the [maximum decompressed size](Compression::maximum_decompressed_size),
+    /// if the compressed body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`], or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "compression")]
+    pub fn write_compressed_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<usize, PacketSizeError> {
+        match compression.compress_packet_body(opcode, body)? {
+            Some(compressed) => self.write_server_packet(buf, opcode, &[&compressed]),
+            None => self.write_server_packet(buf, opcode, body),
+        }
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than the [maximum decompressed size](Compression::maximum_decompressed_size)
+    /// or if the compressed body is larger than [`SERVER_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    pub fn put_compressed_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<(), PacketSizeError> {
+        match compression.compress_packet_body(opcode, body)? {
+            Some(compressed) => self.put_server_packet(dst, opcode, &[&compressed]),
+            None => self.put_server_packet(dst, opcode, body),
+        }
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        self.encrypt.snapshot(SnapshotKind::WrathServerEncrypter)
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`ServerEncrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            encrypt: InnerCrypto::restore(snapshot, SnapshotKind::WrathServerEncrypter)?,
+            server_header: [0_u8; SERVER_HEADER_MAXIMUM_LENGTH as usize],
+        })
+    }
+
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         Self {
             encrypt: InnerCrypto::new(session_key, &R),
@@ -150,9 +283,96 @@ impl ClientEncrypterHalf {
         header
     }
 
+    /// Writes an encrypted client header followed by `body` into the start of `buf`.
+    ///
+    /// The size field is calculated from the opcode and every part of `body`,
+    /// which is written in order.
+    /// Returns the amount of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`CLIENT_BODY_MAXIMUM_LENGTH`] or if `buf` is too small.
+    /// The encryption state is not advanced in this case.
+    pub fn write_client_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        let length = body_length(body, CLIENT_BODY_MAXIMUM_LENGTH)?;
+        let buf = packet_buffer(buf, CLIENT_HEADER_LENGTH as usize + length)?;
+
+        let header = self.encrypt_client_header(client_size(length), opcode);
+        write_packet(buf, &header, body);
+
+        Ok(buf.len())
+    }
+
+    /// [`BytesMut`](bytes::BytesMut) version of [`Self::write_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// If the body is larger than [`CLIENT_BODY_MAXIMUM_LENGTH`].
+    /// The encryption state is not advanced in this case.
+    #[cfg(feature = "tokio")]
+    pub fn put_client_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        let length = body_length(body, CLIENT_BODY_MAXIMUM_LENGTH)?;
+
+        let header = self.encrypt_client_header(client_size(length), opcode);
+        put_packet(dst, &header, body);
+
+        Ok(())
+    }
+
+    /// Identifier of the position of the cipher for comparing with the other side of the connection.
+    ///
+    /// See the [`desync`](crate::desync) module for more information.
+    #[must_use]
+    pub fn fingerprint(&self) -> CipherFingerprint {
+        CipherFingerprint::new(&self.snapshot())
+    }
+
+    /// Captures the exact position of the cipher.
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for more information.
+    #[must_use]
+    pub fn snapshot(&self) -> CryptoSnapshot {
+        self.encrypt.snapshot(SnapshotKind::WrathClientEncrypter)
+    }
+
+    /// Creates a half that continues from the position of the cipher in `snapshot`.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot was not created by a [`ClientEncrypterHalf`] or contains an invalid state.
+    pub fn restore(snapshot: &CryptoSnapshot) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            encrypt: InnerCrypto::restore(snapshot, SnapshotKind::WrathClientEncrypter)?,
+        })
+    }
+
     pub(crate) fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         Self {
             encrypt: InnerCrypto::new(session_key, &S),
         }
     }
 }
+
+/// Size field for a body that has already been checked against [`SERVER_BODY_MAXIMUM_LENGTH`].
+const fn server_size(body_length: usize) -> u32 {
+    (body_length + SERVER_OPCODE_LENGTH as usize) as u32
+}
+
+/// Sizes larger than `0x7FFF` require the 3 byte size field.
+const fn server_header_length(size: u32) -> u8 {
+    if size > 0x7FFF {
+        SERVER_HEADER_MAXIMUM_LENGTH
+    } else {
+        SERVER_HEADER_MINIMUM_LENGTH
+    }
+}
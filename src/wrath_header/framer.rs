@@ -0,0 +1,141 @@
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::FrameError;
+#[cfg(feature = "compression")]
+use crate::framing::decompress_frame;
+use crate::framing::{
+    decrypt_client_header_from_buffer, DecryptedHeader, FrameBuffer, CLIENT_OPCODE_LENGTH,
+    SERVER_OPCODE_LENGTH,
+};
+use crate::wrath_header::{ClientDecrypterHalf, ServerDecrypterHalf};
+
+/// Incremental framer for servers that turns raw bytes from a client into complete packets.
+///
+/// Bytes are added through [`ServerFramer::push`] in whatever chunks they arrive in,
+/// and complete `(opcode, body)` frames are returned from [`ServerFramer::next_frame`].
+/// This does not perform any IO, so it can be used with any kind of event loop.
+///
+/// Every [`ClientHeader`](crate::wrath_header::ClientHeader) is decrypted exactly once, and partial headers are kept
+/// in the buffer without advancing the [`ServerDecrypterHalf`].
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ServerFramer {
+    decrypter: ServerDecrypterHalf,
+    buffer: FrameBuffer,
+}
+
+impl ServerFramer {
+    /// Creates a new framer that rejects bodies larger than `maximum_body_size` bytes.
+    ///
+    /// The body size does not include the opcode.
+    #[must_use]
+    pub const fn new(decrypter: ServerDecrypterHalf, maximum_body_size: u32) -> Self {
+        Self {
+            decrypter,
+            buffer: FrameBuffer::new(maximum_body_size),
+        }
+    }
+
+    /// Adds bytes received from the client.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.push(data);
+    }
+
+    /// Returns the next complete frame, or [`None`] if more data is needed.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the header is smaller than the opcode or if the body is larger
+    /// than the maximum body size.
+    /// The header has already been decrypted when this happens, so the connection should be dropped.
+    pub fn next_frame(&mut self) -> Result<Option<(u32, Vec<u8>)>, FrameError> {
+        let decrypter = &mut self.decrypter;
+        self.buffer.next_frame(CLIENT_OPCODE_LENGTH, |data| {
+            decrypt_client_header_from_buffer(data, |header| {
+                let header = decrypter.decrypt_client_header(header);
+                (header.size, header.opcode)
+            })
+        })
+    }
+
+    /// Amount of bytes that have been pushed but not yet returned as frames.
+    #[must_use]
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// Incremental framer for clients that turns raw bytes from a server into complete packets.
+///
+/// Works the same as [`ServerFramer`] except for decrypting
+/// [`ServerHeader`](crate::wrath_header::ServerHeader)s, which are either 4 or 5 bytes long
+/// depending on the size of the message.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ClientFramer {
+    decrypter: ClientDecrypterHalf,
+    buffer: FrameBuffer,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
+}
+
+impl ClientFramer {
+    /// Creates a new framer that rejects bodies larger than `maximum_body_size` bytes.
+    ///
+    /// The body size does not include the opcode.
+    #[must_use]
+    pub const fn new(decrypter: ClientDecrypterHalf, maximum_body_size: u32) -> Self {
+        Self {
+            decrypter,
+            buffer: FrameBuffer::new(maximum_body_size),
+            #[cfg(feature = "compression")]
+            compression: None,
+        }
+    }
+
+    /// Decompresses the bodies of messages that are compressed according to `compression`.
+    ///
+    /// See the [`compression`](crate::compression) module for more information.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Adds bytes received from the server.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.push(data);
+    }
+
+    /// Returns the next complete frame, or [`None`] if more data is needed.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the header is smaller than the opcode, if the body is larger
+    /// than the maximum body size, or if a compressed body can not be decompressed.
+    /// The header has already been decrypted when this happens, so the connection should be dropped.
+    pub fn next_frame(&mut self) -> Result<Option<(u16, Vec<u8>)>, FrameError> {
+        let decrypter = &mut self.decrypter;
+        let frame = self.buffer.next_frame(SERVER_OPCODE_LENGTH, |data| {
+            let (header, length) = decrypter.decrypt_server_header_from_buffer(data)?;
+
+            Some(DecryptedHeader {
+                size: header.size,
+                opcode: header.opcode.into(),
+                length,
+            })
+        })?;
+
+        let frame = frame.map(|(opcode, body)| (opcode as u16, body));
+
+        #[cfg(feature = "compression")]
+        let frame = decompress_frame(self.compression.as_ref(), frame)?;
+
+        Ok(frame)
+    }
+
+    /// Amount of bytes that have been pushed but not yet returned as frames.
+    #[must_use]
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+}
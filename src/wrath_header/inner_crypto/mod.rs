@@ -1,9 +1,12 @@
-mod rc4;
+pub(crate) mod rc4;
 
+use crate::error::SnapshotError;
+use crate::snapshot::{CryptoSnapshot, SnapshotKind};
 use crate::wrath_header::inner_crypto::rc4::Rc4;
 use crate::SESSION_KEY_LENGTH;
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
+use std::convert::TryInto;
 use std::fmt::Debug;
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -18,6 +21,34 @@ impl InnerCrypto {
         self.inner.apply_keystream(data);
     }
 
+    /// Discards `amount` bytes of keystream.
+    pub(crate) fn skip(&mut self, amount: i32) {
+        let mut discarded = [0_u8; 1];
+        for _ in 0..amount {
+            self.apply(&mut discarded);
+        }
+    }
+
+    pub(crate) fn snapshot(&self, kind: SnapshotKind) -> CryptoSnapshot {
+        let (state, i, j) = self.inner.state();
+
+        CryptoSnapshot::new(kind, &[state, &[i, j]])
+    }
+
+    pub(crate) fn restore(
+        snapshot: &CryptoSnapshot,
+        kind: SnapshotKind,
+    ) -> Result<Self, SnapshotError> {
+        let payload = snapshot.payload(kind)?;
+        let (state, indices) = payload.split_at(payload.len() - 2);
+        let state = state.try_into().map_err(|_| SnapshotError::InvalidLength)?;
+
+        let inner =
+            Rc4::from_state(state, indices[0], indices[1]).ok_or(SnapshotError::InvalidState)?;
+
+        Ok(Self { inner })
+    }
+
     pub(crate) fn new(
         session_key: [u8; SESSION_KEY_LENGTH as usize],
         key: &[u8; KEY_LENGTH as usize],
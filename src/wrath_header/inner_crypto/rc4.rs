@@ -2,14 +2,14 @@
 // in the form of pointer indirection and available derives.
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub(super) struct Rc4 {
+pub(crate) struct Rc4 {
     state: [u8; 256],
     i: u8,
     j: u8,
 }
 
 impl Rc4 {
-    pub(super) fn new(key: &[u8]) -> Self {
+    pub(crate) fn new(key: &[u8]) -> Self {
         let mut state = Self {
             state: [0; 256],
             i: 0,
@@ -21,7 +21,24 @@ impl Rc4 {
         state
     }
 
-    pub(super) fn apply_keystream(&mut self, stream: &mut [u8]) {
+    pub(crate) const fn state(&self) -> (&[u8; 256], u8, u8) {
+        (&self.state, self.i, self.j)
+    }
+
+    /// Returns [`None`] if `state` is not a permutation of every byte value,
+    /// since that can not be reached from the key scheduling algorithm.
+    pub(crate) fn from_state(state: [u8; 256], i: u8, j: u8) -> Option<Self> {
+        let mut seen = [false; 256];
+        for value in state {
+            if std::mem::replace(&mut seen[value as usize], true) {
+                return None;
+            }
+        }
+
+        Some(Self { state, i, j })
+    }
+
+    pub(crate) fn apply_keystream(&mut self, stream: &mut [u8]) {
         for s in stream {
             let v = self.pseudo_random_generation();
             let old_s = *s;
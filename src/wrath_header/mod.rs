@@ -30,7 +30,7 @@
 //! 4. Retrieve the session key from the login server.
 //! 5. Create the [`ServerCrypto`] struct through [`ProofSeed::into_server_header_crypto`].
 //! 6. Optionally, split the [`ServerCrypto`] into [`ServerEncrypterHalf`] and [`ServerDecrypterHalf`] through
-//! [`ServerCrypto::split`].
+//!    [`ServerCrypto::split`].
 //!
 //! and for clients:
 //!
@@ -39,7 +39,7 @@
 //! 3. Create the [`ClientCrypto`] struct through [`ProofSeed::into_client_header_crypto`].
 //! 4. Send the proof and seed through [`CMSG_AUTH_SESSION`].
 //! 5. Optionally, split the [`ClientCrypto`] into [`ClientEncrypterHalf`] and [`ClientDecrypterHalf`] through
-//! [`ClientCrypto::split`].
+//!    [`ClientCrypto::split`].
 //!
 //! Unlike the [vanilla](crate::vanilla_header) version, the Wrath version does not support unsplitting.
 //! This is because there is no easy way to ensure that the structs being unsplit actually came from the same original struct.
@@ -64,27 +64,46 @@
 //!                            |
 //! ```
 //!
+//! # Framing
+//!
+//! Servers and clients that do not use blocking [`Read`](std::io::Read) can use the
+//! [`ServerFramer`] and [`ClientFramer`] instead. These take bytes in whatever chunks
+//! they arrive in from the socket and return complete `(opcode, body)` frames.
+//!
+//! With the `tokio` feature enabled, `ServerDecoder`, `ServerEncoder`, `ClientDecoder`, and
+//! `ClientEncoder` implement the [`tokio-util`](https://docs.rs/tokio-util) codec traits
+//! for use with `FramedRead` and `FramedWrite` on the halves of a split connection.
+//!
 //! [World Packet]: https://wowdev.wiki/World_Packet
 //! [Login Packets]: https://wowdev.wiki/Login_Packet
 //! [`SMSG_AUTH_CHALLENGE`]: https://wowdev.wiki/SMSG_AUTH_CHALLENGE
 //! [`CMSG_AUTH_SESSION`]: https://wowdev.wiki/SMSG_AUTH_SESSION
 use std::io::{Read, Write};
 
+#[cfg(feature = "tokio")]
+pub use codec::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
 pub use decrypt::ClientDecrypterHalf;
 pub use decrypt::ServerDecrypterHalf;
 pub use encrypt::ClientEncrypterHalf;
 pub use encrypt::ServerEncrypterHalf;
+pub use framer::ClientFramer;
+pub use framer::ServerFramer;
 
-use crate::error::MatchProofsError;
-use crate::key::{Proof, SessionKey};
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::{MatchProofsError, PacketSizeError, RngHealthError};
+use crate::key::{random_proof_seed, try_random_proof_seed, Proof, SessionKey};
 use crate::normalized_string::NormalizedString;
+use crate::trace::{enter_span, proof_mismatch};
 use crate::vanilla_header::calculate_world_server_proof;
 use crate::{PROOF_LENGTH, SESSION_KEY_LENGTH};
-use rand::{thread_rng, RngCore};
 
+#[cfg(feature = "tokio")]
+mod codec;
 pub(crate) mod decrypt;
 pub(crate) mod encrypt;
-mod inner_crypto;
+mod framer;
+pub(crate) mod inner_crypto;
 
 /// Size in bytes of the client [world packet] header.
 ///
@@ -118,6 +137,16 @@ pub const SERVER_HEADER_MINIMUM_LENGTH: u8 =
 pub const SERVER_HEADER_MAXIMUM_LENGTH: u8 =
     (std::mem::size_of::<u16>() + std::mem::size_of::<u16>() + std::mem::size_of::<u8>()) as u8;
 
+/// Largest body in bytes that fits in a server header.
+///
+/// The size field is up to 3 bytes with the most significant bit reserved
+/// for marking the header as large, and includes the 2 byte opcode.
+pub const SERVER_BODY_MAXIMUM_LENGTH: u32 = 0x7F_FFFF - 2;
+/// Largest body in bytes that fits in a client header.
+///
+/// The size field is 2 bytes and includes the 4 byte opcode.
+pub const CLIENT_BODY_MAXIMUM_LENGTH: u32 = u16::MAX as u32 - 4;
+
 // Used for Client (Encryption) to Server (Decryption)
 const S: [u8; 16] = [
     0xC2, 0xB3, 0x72, 0x3C, 0xC6, 0xAE, 0xD9, 0xB5, 0x34, 0x3C, 0x53, 0xEE, 0x2F, 0x43, 0x67, 0xCE,
@@ -210,6 +239,35 @@ impl ClientCrypto {
         self.encrypt.encrypt_client_header(size, opcode)
     }
 
+    /// Convenience wrapper for [`ClientEncrypterHalf::write_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ClientEncrypterHalf::write_client_packet`].
+    pub fn write_client_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_client_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`ClientEncrypterHalf::put_client_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ClientEncrypterHalf::put_client_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_client_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u32,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_client_packet(dst, opcode, body)
+    }
+
     /// Raw access to decryption.
     ///
     /// Use the
@@ -229,6 +287,32 @@ impl ClientCrypto {
         self.decrypt.decrypt_server_header(data)
     }
 
+    /// Convenience wrapper for [`ClientDecrypterHalf::read_and_decrypt_server_header`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ClientDecrypterHalf::read_and_decrypt_server_header`].
+    pub fn read_and_decrypt_server_header<R: Read>(
+        &mut self,
+        reader: R,
+    ) -> std::io::Result<ServerHeader> {
+        self.decrypt.read_and_decrypt_server_header(reader)
+    }
+
+    /// Convenience wrapper for [`ClientDecrypterHalf::decrypt_server_header_from_buffer`].
+    #[must_use]
+    pub fn decrypt_server_header_from_buffer(
+        &mut self,
+        data: &[u8],
+    ) -> Option<(ServerHeader, usize)> {
+        self.decrypt.decrypt_server_header_from_buffer(data)
+    }
+
+    /// Creates the crypto directly from a session key.
+    ///
+    /// This skips the proof verification of [`ProofSeed::into_client_header_crypto`],
+    /// prefer using that instead.
+    #[must_use]
     pub fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         Self {
             decrypt: ClientDecrypterHalf::new(session_key),
@@ -311,6 +395,69 @@ impl ServerCrypto {
         self.encrypt.encrypt_server_header(size, opcode)
     }
 
+    /// Convenience wrapper for [`ServerEncrypterHalf::write_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ServerEncrypterHalf::write_server_packet`].
+    pub fn write_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt.write_server_packet(buf, opcode, body)
+    }
+
+    /// Convenience wrapper for [`ServerEncrypterHalf::put_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ServerEncrypterHalf::put_server_packet`].
+    #[cfg(feature = "tokio")]
+    pub fn put_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt.put_server_packet(dst, opcode, body)
+    }
+
+    /// Convenience wrapper for [`ServerEncrypterHalf::write_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ServerEncrypterHalf::write_compressed_server_packet`].
+    #[cfg(feature = "compression")]
+    pub fn write_compressed_server_packet(
+        &mut self,
+        buf: &mut [u8],
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<usize, PacketSizeError> {
+        self.encrypt
+            .write_compressed_server_packet(buf, opcode, body, compression)
+    }
+
+    /// Convenience wrapper for [`ServerEncrypterHalf::put_compressed_server_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Has the same errors as [`ServerEncrypterHalf::put_compressed_server_packet`].
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    pub fn put_compressed_server_packet(
+        &mut self,
+        dst: &mut bytes::BytesMut,
+        opcode: u16,
+        body: &[&[u8]],
+        compression: &Compression,
+    ) -> Result<(), PacketSizeError> {
+        self.encrypt
+            .put_compressed_server_packet(dst, opcode, body, compression)
+    }
+
     /// Raw access to decryption.
     ///
     /// Use either [the server](Self::read_and_decrypt_client_header)
@@ -348,6 +495,11 @@ impl ServerCrypto {
         ClientHeader { size, opcode }
     }
 
+    /// Creates the crypto directly from a session key.
+    ///
+    /// This skips the proof verification of [`ProofSeed::into_server_header_crypto`],
+    /// prefer using that instead.
+    #[must_use]
     pub fn new(session_key: [u8; SESSION_KEY_LENGTH as usize]) -> Self {
         Self {
             decrypt: ServerDecrypterHalf::new(session_key),
@@ -390,8 +542,27 @@ impl ProofSeed {
         Self::default()
     }
 
+    /// Same as [`ProofSeed::new`] but fails if the seed does not pass the
+    /// [RNG health checks](crate::enable_rng_health_checks).
+    ///
+    /// # Errors
+    ///
+    /// If the checks are enabled and the seed fails them.
+    pub fn try_new() -> Result<Self, RngHealthError> {
+        Ok(Self {
+            seed: try_random_proof_seed()?,
+        })
+    }
+
+    /// Server seed issued by a [`WorldAuthGuard`](crate::world_auth::WorldAuthGuard).
+    pub(crate) const fn from_issued(server_seed: crate::vanilla_header::ProofSeed) -> Self {
+        Self {
+            seed: server_seed.seed(),
+        }
+    }
+
     #[cfg(test)]
-    const fn from_specific_seed(server_seed: u32) -> Self {
+    pub(crate) const fn from_specific_seed(server_seed: u32) -> Self {
         Self { seed: server_seed }
     }
 
@@ -447,6 +618,14 @@ impl ProofSeed {
         client_proof: [u8; PROOF_LENGTH as _],
         client_seed: u32,
     ) -> Result<ServerCrypto, MatchProofsError> {
+        enter_span!(
+            "verify_world_proof",
+            username = %username,
+            expansion = "wrath",
+            server_seed = self.seed,
+            client_seed
+        );
+
         let server_proof = calculate_world_server_proof(
             username,
             &SessionKey::from_le_bytes(session_key),
@@ -455,6 +634,7 @@ impl ProofSeed {
         );
 
         if server_proof != Proof::from_le_bytes(client_proof) {
+            proof_mismatch!(client_proof);
             return Err(MatchProofsError {
                 client_proof,
                 server_proof: *server_proof.as_le_bytes(),
@@ -468,7 +648,7 @@ impl ProofSeed {
 impl Default for ProofSeed {
     fn default() -> Self {
         Self {
-            seed: thread_rng().next_u32(),
+            seed: random_proof_seed(),
         }
     }
 }
@@ -477,11 +657,13 @@ impl Default for ProofSeed {
 mod test {
     use std::fs::read_to_string;
 
+    use crate::error::{FrameError, PacketSizeError};
     use crate::hex::*;
     use crate::key::SessionKey;
     use crate::normalized_string::NormalizedString;
     use crate::wrath_header::{
-        ClientCrypto, ProofSeed, ServerCrypto, SERVER_HEADER_MAXIMUM_LENGTH,
+        ClientCrypto, ClientFramer, ProofSeed, ServerCrypto, ServerFramer,
+        CLIENT_BODY_MAXIMUM_LENGTH, SERVER_BODY_MAXIMUM_LENGTH, SERVER_HEADER_MAXIMUM_LENGTH,
         SERVER_HEADER_MINIMUM_LENGTH,
     };
     use std::convert::TryInto;
@@ -730,7 +912,7 @@ mod test {
 
         let mut header = [0_u8; SERVER_HEADER_MAXIMUM_LENGTH as usize];
         server
-            .write_encrypted_server_header(&mut header.as_mut_slice(), 0x8008, 0x1ee)
+            .write_encrypted_server_header(header.as_mut_slice(), 0x8008, 0x1ee)
             .unwrap();
         let expected_header = [0x97, 0x27, 0x32, 0xa3, 0x1a];
         assert_eq!(header, expected_header);
@@ -741,7 +923,7 @@ mod test {
 
         let mut header = [0_u8; SERVER_HEADER_MINIMUM_LENGTH as usize];
         server
-            .write_encrypted_server_header(&mut header.as_mut_slice(), 0x08, 0x1ee)
+            .write_encrypted_server_header(header.as_mut_slice(), 0x08, 0x1ee)
             .unwrap();
         let expected_header = [0x89_u8, 0x4F, 0xFE, 0x11];
         assert_eq!(header, expected_header);
@@ -754,4 +936,477 @@ mod test {
         assert_eq!(header.opcode, 0x1ee);
         assert_eq!(header.size, 0x08);
     }
+
+    #[test]
+    fn verify_server_header_streaming() {
+        let session_key = [
+            1, 51, 81, 113, 146, 209, 181, 133, 131, 129, 50, 206, 122, 228, 208, 115, 52, 15, 132,
+            54, 189, 17, 178, 157, 178, 3, 35, 186, 202, 151, 226, 58, 162, 188, 65, 174, 60, 18,
+            152, 7,
+        ];
+
+        let mut server = ServerCrypto::new(session_key);
+        let mut stream = Vec::new();
+        server
+            .write_encrypted_server_header(&mut stream, 0x8008, 0x1ee)
+            .unwrap();
+        stream.extend_from_slice(&[0xAA; 3]);
+        server
+            .write_encrypted_server_header(&mut stream, 0x08, 0x1ee)
+            .unwrap();
+        stream.extend_from_slice(&[0xBB; 6]);
+
+        let mut client = ClientCrypto::new(session_key);
+        let mut reader = stream.as_slice();
+
+        let header = client.read_and_decrypt_server_header(&mut reader).unwrap();
+        assert_eq!(header.opcode, 0x1ee);
+        assert_eq!(header.size, 0x8008);
+        assert_eq!(&reader[..3], &[0xAA; 3]);
+        reader = &reader[3..];
+
+        let header = client.read_and_decrypt_server_header(&mut reader).unwrap();
+        assert_eq!(header.opcode, 0x1ee);
+        assert_eq!(header.size, 0x08);
+        assert_eq!(reader, &[0xBB; 6]);
+
+        let mut client = ClientCrypto::new(session_key);
+        for partial in 0..SERVER_HEADER_MAXIMUM_LENGTH as usize {
+            assert!(client
+                .decrypt_server_header_from_buffer(&stream[..partial])
+                .is_none());
+        }
+
+        let (header, consumed) = client.decrypt_server_header_from_buffer(&stream).unwrap();
+        assert_eq!(consumed, SERVER_HEADER_MAXIMUM_LENGTH as usize);
+        assert_eq!(header.opcode, 0x1ee);
+        assert_eq!(header.size, 0x8008);
+
+        let rest = &stream[consumed + 3..];
+        for partial in 0..SERVER_HEADER_MINIMUM_LENGTH as usize {
+            assert!(client
+                .decrypt_server_header_from_buffer(&rest[..partial])
+                .is_none());
+        }
+
+        let (header, consumed) = client.decrypt_server_header_from_buffer(rest).unwrap();
+        assert_eq!(consumed, SERVER_HEADER_MINIMUM_LENGTH as usize);
+        assert_eq!(header.opcode, 0x1ee);
+        assert_eq!(header.size, 0x08);
+    }
+
+    #[test]
+    fn verify_framer() {
+        let session_key = [
+            1, 51, 81, 113, 146, 209, 181, 133, 131, 129, 50, 206, 122, 228, 208, 115, 52, 15, 132,
+            54, 189, 17, 178, 157, 178, 3, 35, 186, 202, 151, 226, 58, 162, 188, 65, 174, 60, 18,
+            152, 7,
+        ];
+
+        let mut server = ServerCrypto::new(session_key);
+        let mut server_stream = Vec::new();
+        let large_body = vec![0xAA; 0x8008 - 2];
+        server
+            .write_encrypted_server_header(&mut server_stream, 0x8008, 0x1ee)
+            .unwrap();
+        server_stream.extend_from_slice(&large_body);
+        server
+            .write_encrypted_server_header(&mut server_stream, 0x08, 0x1ee)
+            .unwrap();
+        server_stream.extend_from_slice(&[0xBB; 6]);
+
+        let mut client = ClientCrypto::new(session_key);
+        let mut client_stream = Vec::new();
+        client
+            .write_encrypted_client_header(&mut client_stream, 4 + 1, 0x37)
+            .unwrap();
+        client_stream.push(0xCC);
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(decrypter, 0x10000);
+        let mut frames = Vec::new();
+        for chunk in server_stream.chunks(3) {
+            framer.push(chunk);
+            while let Some(frame) = framer.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![(0x1ee, large_body), (0x1ee, vec![0xBB; 6])]);
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(decrypter, 0x100);
+        framer.push(&server_stream[..SERVER_HEADER_MAXIMUM_LENGTH as usize]);
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::BodyTooLarge {
+                size: 0x8006,
+                maximum: 0x100
+            })
+        ));
+
+        let (_, decrypter) = ServerCrypto::new(session_key).split();
+        let mut framer = ServerFramer::new(decrypter, 0x100);
+        framer.push(&client_stream[..3]);
+        assert_eq!(framer.next_frame().unwrap(), None);
+        framer.push(&client_stream[3..]);
+        assert_eq!(framer.next_frame().unwrap(), Some((0x37, vec![0xCC])));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn verify_codec() {
+        use crate::wrath_header::{ClientDecoder, ClientEncoder, ServerDecoder, ServerEncoder};
+        use bytes::{Bytes, BytesMut};
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let session_key = [
+            1, 51, 81, 113, 146, 209, 181, 133, 131, 129, 50, 206, 122, 228, 208, 115, 52, 15, 132,
+            54, 189, 17, 178, 157, 178, 3, 35, 186, 202, 151, 226, 58, 162, 188, 65, 174, 60, 18,
+            152, 7,
+        ];
+
+        let (encrypter, _) = ServerCrypto::new(session_key).split();
+        let mut encoder = ServerEncoder::new(encrypter);
+        let large_body = Bytes::from(vec![0xAA; 0x8008]);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x1EE, large_body.clone()), &mut stream)
+            .unwrap();
+        encoder
+            .encode((0x1EE, Bytes::from_static(&[0xBB; 6])), &mut stream)
+            .unwrap();
+        assert!(encoder
+            .encode((0x1EE, Bytes::from(vec![0; 0x80_0000])), &mut stream)
+            .is_err());
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut decoder = ClientDecoder::new(decrypter, 0x10000);
+        let mut frames = Vec::new();
+        let mut buf = BytesMut::new();
+        for chunk in stream.chunks(3) {
+            buf.extend_from_slice(chunk);
+            while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![(0x1EE, large_body), (0x1EE, Bytes::from_static(&[0xBB; 6]))]
+        );
+
+        let (encrypter, _) = ClientCrypto::new(session_key).split();
+        let mut encoder = ClientEncoder::new(encrypter);
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((0x37, Bytes::from_static(&[0xCC])), &mut stream)
+            .unwrap();
+
+        let (_, decrypter) = ServerCrypto::new(session_key).split();
+        let mut decoder = ServerDecoder::new(decrypter, 0x100);
+        let mut partial = stream.split_to(3);
+        assert_eq!(decoder.decode(&mut partial).unwrap(), None);
+        assert!(decoder.decode_eof(&mut BytesMut::new()).is_err());
+        assert_eq!(
+            decoder.decode_eof(&mut stream).unwrap(),
+            Some((0x37, Bytes::from_static(&[0xCC])))
+        );
+    }
+
+    #[test]
+    fn verify_packet_writer() {
+        let session_key = [
+            1, 51, 81, 113, 146, 209, 181, 133, 131, 129, 50, 206, 122, 228, 208, 115, 52, 15, 132,
+            54, 189, 17, 178, 157, 178, 3, 35, 186, 202, 151, 226, 58, 162, 188, 65, 174, 60, 18,
+            152, 7,
+        ];
+
+        let large_body = vec![0xAA; 0x8000];
+        let mut expected = ServerCrypto::new(session_key);
+        let mut expected_packet = expected.encrypt_server_header(2 + 0x8000, 0x1EE).to_vec();
+        expected_packet.extend_from_slice(&large_body);
+        expected_packet.extend_from_slice(expected.encrypt_server_header(2 + 1, 0x1EE));
+        expected_packet.push(0xBB);
+
+        let mut server = ServerCrypto::new(session_key);
+        let mut buf = vec![0_u8; 0x8010];
+
+        assert_eq!(
+            server.write_server_packet(&mut buf[..0x8004], 0x1EE, &[&large_body]),
+            Err(PacketSizeError::BufferTooSmall {
+                required: 0x8005,
+                available: 0x8004
+            })
+        );
+        let body = vec![0_u8; SERVER_BODY_MAXIMUM_LENGTH as usize + 1];
+        assert_eq!(
+            server.write_server_packet(&mut buf, 0x1EE, &[&body]),
+            Err(PacketSizeError::BodyTooLarge {
+                size: SERVER_BODY_MAXIMUM_LENGTH as usize + 1,
+                maximum: SERVER_BODY_MAXIMUM_LENGTH as usize
+            })
+        );
+
+        let written = server
+            .write_server_packet(&mut buf, 0x1EE, &[&large_body[..0x10], &large_body[0x10..]])
+            .unwrap();
+        assert_eq!(written, SERVER_HEADER_MAXIMUM_LENGTH as usize + 0x8000);
+        let written = written
+            + server
+                .write_server_packet(&mut buf[written..], 0x1EE, &[&[0xBB]])
+                .unwrap();
+        assert_eq!(&buf[..written], expected_packet.as_slice());
+
+        let mut expected = ClientCrypto::new(session_key);
+        let mut expected_packet = expected.encrypt_client_header(4 + 1, 0x37).to_vec();
+        expected_packet.push(0xCC);
+
+        let mut client = ClientCrypto::new(session_key);
+        let body = vec![0_u8; CLIENT_BODY_MAXIMUM_LENGTH as usize + 1];
+        assert!(client
+            .write_client_packet(&mut buf, 0x37, &[&body])
+            .is_err());
+        let written = client
+            .write_client_packet(&mut buf, 0x37, &[&[0xCC]])
+            .unwrap();
+        assert_eq!(&buf[..written], expected_packet.as_slice());
+    }
+
+    #[test]
+    fn verify_snapshot() {
+        use crate::error::SnapshotError;
+        use crate::snapshot::CryptoSnapshot;
+        use crate::wrath_header::{
+            ClientDecrypterHalf, ClientEncrypterHalf, ServerDecrypterHalf, ServerEncrypterHalf,
+        };
+
+        fn hand_off(snapshot: CryptoSnapshot) -> CryptoSnapshot {
+            CryptoSnapshot::from_bytes(snapshot.as_bytes()).unwrap()
+        }
+
+        let contents =
+            read_to_string("tests/encryption/calculate_wrath_encrypt_values.txt").unwrap();
+
+        for line in contents.lines() {
+            let mut line = line.split_whitespace();
+
+            let session_key = SessionKey::from_le_hex_str(line.next().unwrap());
+            let mut data = hex_decode(line.next().unwrap());
+            let expected_client = hex_decode(line.next().unwrap());
+            let expected_server = hex_decode(line.next().unwrap());
+
+            let original_data = data.clone();
+            let middle = data.len() / 2;
+
+            let (mut client_enc, mut client_dec) =
+                ClientCrypto::new(*session_key.as_le_bytes()).split();
+            let (mut server_enc, mut server_dec) =
+                ServerCrypto::new(*session_key.as_le_bytes()).split();
+
+            client_enc.encrypt(&mut data[..middle]);
+            server_dec.decrypt(&mut data[..middle]);
+            let mut client_enc =
+                ClientEncrypterHalf::restore(&hand_off(client_enc.snapshot())).unwrap();
+            let mut server_dec =
+                ServerDecrypterHalf::restore(&hand_off(server_dec.snapshot())).unwrap();
+
+            client_enc.encrypt(&mut data[middle..]);
+            assert_eq!(data[middle..], expected_client[middle..]);
+            server_dec.decrypt(&mut data[middle..]);
+            assert_eq!(data, original_data);
+
+            server_enc.encrypt(&mut data[..middle]);
+            client_dec.decrypt(&mut data[..middle]);
+            let mut server_enc =
+                ServerEncrypterHalf::restore(&hand_off(server_enc.snapshot())).unwrap();
+            let mut client_dec =
+                ClientDecrypterHalf::restore(&hand_off(client_dec.snapshot())).unwrap();
+
+            server_enc.encrypt(&mut data[middle..]);
+            assert_eq!(data[middle..], expected_server[middle..]);
+            client_dec.decrypt(&mut data[middle..]);
+            assert_eq!(data, original_data);
+        }
+
+        let (enc, _) = ServerCrypto::new([7; crate::SESSION_KEY_LENGTH as usize]).split();
+        assert_eq!(
+            ClientEncrypterHalf::restore(&enc.snapshot()),
+            Err(SnapshotError::InvalidKind)
+        );
+
+        let mut bytes = enc.snapshot().as_bytes().to_vec();
+        bytes[2] = bytes[3];
+        let snapshot = CryptoSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            ServerEncrypterHalf::restore(&snapshot),
+            Err(SnapshotError::InvalidState)
+        );
+    }
+
+    #[test]
+    fn verify_desync() {
+        use crate::desync::HeaderLimits;
+        use crate::error::ImplausibleHeader;
+        use crate::wrath_header::{ClientHeader, SERVER_HEADER_MAXIMUM_LENGTH};
+
+        let mut session_key = [0_u8; crate::SESSION_KEY_LENGTH as usize];
+        for (i, b) in session_key.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(97).wrapping_add(13);
+        }
+        let limits = HeaderLimits::new(0x1000, 0, 0x500).with_search_distance(10);
+
+        let (mut client, _) = ClientCrypto::new(session_key).split();
+        let (_, mut server) = ServerCrypto::new(session_key).split();
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        let header = client.encrypt_client_header(8, 0x1ED);
+        assert_eq!(
+            server.decrypt_client_header_checked(header, &limits),
+            Ok(ClientHeader {
+                size: 8,
+                opcode: 0x1ED
+            })
+        );
+        assert_eq!(client.fingerprint(), server.fingerprint());
+
+        // Header encrypted but never sent
+        let _ = client.encrypt_client_header(4, 0x37);
+        let header = client.encrypt_client_header(4, 0x38);
+        let error = server
+            .decrypt_client_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, Some(6));
+        assert_ne!(client.fingerprint(), server.fingerprint());
+
+        // Header decrypted twice can not be found since RC4 can not be rewound
+        let (mut server, _) = ServerCrypto::new(session_key).split();
+        let (_, mut client) = ClientCrypto::new(session_key).split();
+        let mut header = [0_u8; SERVER_HEADER_MAXIMUM_LENGTH as usize];
+        header[..4].copy_from_slice(server.encrypt_server_header(2, 0x1EC));
+        let _ = client.decrypt_server_header(header);
+        let _ = client.decrypt_server_header(header);
+        header[..4].copy_from_slice(server.encrypt_server_header(6, 0x1EE));
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(error.offset, None);
+
+        // Large header
+        let (mut server, _) = ServerCrypto::new(session_key).split();
+        let (_, mut client) = ClientCrypto::new(session_key).split();
+        let limits = HeaderLimits::new(0x1000, 0x10, 0x20);
+        header.copy_from_slice(server.encrypt_server_header(0x8002, 0x10));
+        let error = client
+            .decrypt_server_header_checked(header, &limits)
+            .unwrap_err();
+        assert_eq!(
+            error.reason,
+            ImplausibleHeader::BodyTooLarge { maximum: 0x1000 }
+        );
+        assert_eq!(error.size, 0x8002);
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn verify_compression() {
+        use crate::compression::{Compression, SMSG_COMPRESSED_UPDATE_OBJECT};
+        use crate::error::{CompressionError, FrameError, PacketSizeError};
+
+        let session_key = [7_u8; crate::SESSION_KEY_LENGTH as usize];
+        let compression = Compression::new(0x10000);
+        let update_object: Vec<u8> = (0..0x9000_u32).map(|i| (i / 64) as u8).collect();
+
+        let (mut encrypter, _) = ServerCrypto::new(session_key).split();
+        let mut stream = vec![0_u8; 0x1000];
+        assert_eq!(
+            encrypter.write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[&update_object],
+                &Compression::new(0x100),
+            ),
+            Err(PacketSizeError::BodyTooLarge {
+                size: 0x9000,
+                maximum: 0x100
+            })
+        );
+
+        let (first, second) = update_object.split_at(0x4000);
+        let mut length = encrypter
+            .write_compressed_server_packet(
+                &mut stream,
+                SMSG_COMPRESSED_UPDATE_OBJECT,
+                &[first, second],
+                &compression,
+            )
+            .unwrap();
+        length += encrypter
+            .write_compressed_server_packet(
+                &mut stream[length..],
+                0x1EE,
+                &[&[1, 2, 3]],
+                &compression,
+            )
+            .unwrap();
+        stream.truncate(length);
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut framer = ClientFramer::new(decrypter, 0x1000).with_compression(compression);
+        framer.push(&stream);
+        assert_eq!(
+            framer.next_frame().unwrap(),
+            Some((SMSG_COMPRESSED_UPDATE_OBJECT, update_object))
+        );
+        assert_eq!(framer.next_frame().unwrap(), Some((0x1EE, vec![1, 2, 3])));
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut framer =
+            ClientFramer::new(decrypter, 0x1000).with_compression(Compression::new(0x100));
+        framer.push(&stream);
+        assert!(matches!(
+            framer.next_frame(),
+            Err(FrameError::InvalidCompressedBody {
+                opcode: SMSG_COMPRESSED_UPDATE_OBJECT,
+                error: CompressionError::DecompressedTooLarge {
+                    size: 0x9000,
+                    maximum: 0x100
+                }
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "tokio", feature = "compression"))]
+    fn verify_compression_codec() {
+        use crate::compression::{Compression, SMSG_COMPRESSED_MOVES};
+        use crate::wrath_header::{ClientDecoder, ServerEncoder};
+        use bytes::{Bytes, BytesMut};
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let session_key = [7_u8; crate::SESSION_KEY_LENGTH as usize];
+        let compression = Compression::new(0x10000);
+        let moves = Bytes::from(vec![0xAA; 0x8008]);
+
+        let (encrypter, _) = ServerCrypto::new(session_key).split();
+        let mut encoder = ServerEncoder::new(encrypter).with_compression(compression.clone());
+        let mut stream = BytesMut::new();
+        encoder
+            .encode((SMSG_COMPRESSED_MOVES, moves.clone()), &mut stream)
+            .unwrap();
+        let compressed_length = stream.len();
+        encoder
+            .encode((0x1EE, Bytes::from_static(&[0xBB; 6])), &mut stream)
+            .unwrap();
+        assert!(compressed_length < moves.len());
+
+        let (_, decrypter) = ClientCrypto::new(session_key).split();
+        let mut decoder = ClientDecoder::new(decrypter, 0x1000).with_compression(compression);
+        assert_eq!(
+            decoder.decode(&mut stream).unwrap(),
+            Some((SMSG_COMPRESSED_MOVES, moves))
+        );
+        assert_eq!(
+            decoder.decode(&mut stream).unwrap(),
+            Some((0x1EE, Bytes::from_static(&[0xBB; 6])))
+        );
+    }
 }